@@ -0,0 +1,204 @@
+use std::{
+    io::{self, Read, Write},
+    string::FromUtf8Error,
+};
+
+use varint::VarIntError;
+
+// Lets the derive macros' `::mcproto` paths resolve inside this crate too
+extern crate self as mcproto;
+
+mod packet;
+pub mod registry;
+mod string;
+mod types;
+pub mod versions;
+
+pub use mcproto_derive::{Packet, Packets};
+pub use packet::{Direction, Packet, State, write_frame};
+pub use string::{Identifier, McString, STRING_MAX_LEN};
+pub use types::{Position, PrefixedArray, VarInt, VarLong};
+pub use versions::{ProtocolRange, ProtocolVersion};
+
+#[derive(Debug)]
+pub enum ProtocolError {
+    Io(io::Error),
+    VarInt(VarIntError),
+    InvalidUtf8(FromUtf8Error),
+    /// A string had more characters than its field allows
+    StringTooLong {
+        len: usize,
+        max: usize,
+    },
+    /// An array had more elements than its field allows
+    ArrayTooLong {
+        len: usize,
+        max: usize,
+    },
+    NegativeLength(i32),
+    InvalidBool(u8),
+    InvalidIdentifier(String),
+}
+
+impl std::fmt::Display for ProtocolError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Io(e) => write!(f, "{}", e),
+            Self::VarInt(e) => write!(f, "{}", e),
+            Self::InvalidUtf8(e) => write!(f, "Invalid string: {}", e),
+            Self::StringTooLong { len, max } => {
+                write!(f, "String of length {} is longer than {}", len, max)
+            }
+            Self::ArrayTooLong { len, max } => {
+                write!(f, "Array of length {} is longer than {}", len, max)
+            }
+            Self::NegativeLength(len) => write!(f, "Negative length {}", len),
+            Self::InvalidBool(b) => write!(f, "Invalid boolean {}", b),
+            Self::InvalidIdentifier(s) => write!(f, "Invalid identifier '{}'", s),
+        }
+    }
+}
+
+impl std::error::Error for ProtocolError {}
+
+impl From<io::Error> for ProtocolError {
+    fn from(value: io::Error) -> Self {
+        ProtocolError::Io(value)
+    }
+}
+
+impl From<VarIntError> for ProtocolError {
+    fn from(value: VarIntError) -> Self {
+        match value {
+            VarIntError::Io(e) => ProtocolError::Io(e),
+            e => ProtocolError::VarInt(e),
+        }
+    }
+}
+
+impl From<FromUtf8Error> for ProtocolError {
+    fn from(value: FromUtf8Error) -> Self {
+        ProtocolError::InvalidUtf8(value)
+    }
+}
+
+/// A value that can be read from the Minecraft protocol
+pub trait McRead: Sized {
+    fn read_from<R: Read>(reader: &mut R) -> Result<Self, ProtocolError>;
+}
+
+/// A value that can be written to the Minecraft protocol
+pub trait McWrite {
+    fn write_to<W: Write>(&self, writer: &mut W) -> Result<(), ProtocolError>;
+}
+
+/// Reads a VarInt length prefix, rejecting negative lengths and ones bigger than `max`
+pub fn read_length<R: Read>(reader: &mut R, max: usize) -> Result<usize, ProtocolError> {
+    let len = varint::decode_stream(reader)?;
+    if len < 0 {
+        return Err(ProtocolError::NegativeLength(len));
+    }
+    let len = len as usize;
+    if len > max {
+        return Err(ProtocolError::ArrayTooLong { len, max });
+    }
+    Ok(len)
+}
+
+/// Writes a VarInt length prefix
+pub fn write_length<W: Write>(writer: &mut W, len: usize) -> Result<(), ProtocolError> {
+    let len = i32::try_from(len).map_err(|_| ProtocolError::ArrayTooLong {
+        len,
+        max: i32::MAX as usize,
+    })?;
+    varint::encode_into(len, writer)?;
+    Ok(())
+}
+
+macro_rules! impl_number {
+    ($($t:ty),*) => {
+        $(
+            impl McRead for $t {
+                fn read_from<R: Read>(reader: &mut R) -> Result<Self, ProtocolError> {
+                    let mut buf = [0u8; size_of::<$t>()];
+                    reader.read_exact(&mut buf)?;
+                    Ok(<$t>::from_be_bytes(buf))
+                }
+            }
+
+            impl McWrite for $t {
+                fn write_to<W: Write>(&self, writer: &mut W) -> Result<(), ProtocolError> {
+                    writer.write_all(&self.to_be_bytes())?;
+                    Ok(())
+                }
+            }
+        )*
+    };
+}
+
+impl_number!(u8, i8, u16, i16, u32, i32, u64, i64, u128, i128, f32, f64);
+
+impl McRead for bool {
+    fn read_from<R: Read>(reader: &mut R) -> Result<Self, ProtocolError> {
+        match u8::read_from(reader)? {
+            0 => Ok(false),
+            1 => Ok(true),
+            b => Err(ProtocolError::InvalidBool(b)),
+        }
+    }
+}
+
+impl McWrite for bool {
+    fn write_to<W: Write>(&self, writer: &mut W) -> Result<(), ProtocolError> {
+        (*self as u8).write_to(writer)
+    }
+}
+
+#[cfg(test)]
+pub(crate) fn roundtrip<T: McRead + McWrite + PartialEq + std::fmt::Debug>(value: T) -> Vec<u8> {
+    let mut out = Vec::new();
+    value.write_to(&mut out).expect("error writing value");
+    let mut input = out.as_slice();
+    let read = T::read_from(&mut input).expect("error reading value");
+    assert_eq!(read, value);
+    assert!(input.is_empty(), "value wasn't fully read");
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_numbers() {
+        assert_eq!(roundtrip(25565u16), vec![0x63, 0xdd]);
+        assert_eq!(roundtrip(-2i32), vec![0xff, 0xff, 0xff, 0xfe]);
+        roundtrip(u64::MAX);
+        roundtrip(i128::MIN);
+        roundtrip(1.5f32);
+        roundtrip(-0.25f64);
+    }
+
+    #[test]
+    fn test_bool() {
+        assert_eq!(roundtrip(true), vec![1]);
+        assert_eq!(roundtrip(false), vec![0]);
+        assert!(matches!(
+            bool::read_from(&mut [2u8].as_slice()),
+            Err(ProtocolError::InvalidBool(2))
+        ));
+    }
+
+    #[test]
+    fn test_length() {
+        let mut out = Vec::new();
+        write_length(&mut out, 300).unwrap();
+        assert_eq!(read_length(&mut out.as_slice(), 300).unwrap(), 300);
+        assert!(read_length(&mut out.as_slice(), 299).is_err());
+        let negative = varint::encode(-1);
+        assert!(matches!(
+            read_length(&mut negative.as_slice(), 10),
+            Err(ProtocolError::NegativeLength(-1))
+        ));
+    }
+}
@@ -0,0 +1,221 @@
+use std::{fmt, io::Write};
+
+use crate::{McRead, McWrite, ProtocolError, registry::PacketRegistry};
+
+/// The protocol state a packet is sent in
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum State {
+    Handshaking,
+    Status,
+    Login,
+    Configuration,
+    Play,
+}
+
+impl fmt::Display for State {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Handshaking => write!(f, "Handshaking"),
+            Self::Status => write!(f, "Status"),
+            Self::Login => write!(f, "Login"),
+            Self::Configuration => write!(f, "Configuration"),
+            Self::Play => write!(f, "Play"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Direction {
+    /// Sent by the client
+    Serverbound,
+    /// Sent by the server
+    Clientbound,
+}
+
+impl fmt::Display for Direction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Serverbound => write!(f, "Serverbound"),
+            Self::Clientbound => write!(f, "Clientbound"),
+        }
+    }
+}
+
+/// A packet with a fixed id, usually implemented with `#[derive(Packet)]`
+pub trait Packet: McRead + McWrite {
+    const ID: i32;
+    const STATE: State;
+    const DIRECTION: Direction;
+    /// The packet's name in the packet id tables, for packets whose id changes between versions
+    const NAME: Option<&'static str> = None;
+
+    /// The packet's id in `protocol`, falling back to `ID` if it isn't in the tables
+    fn id_for(protocol: i32) -> i32 {
+        Self::NAME
+            .and_then(|name| {
+                PacketRegistry::builtin().packet_id(protocol, Self::STATE, Self::DIRECTION, name)
+            })
+            .unwrap_or(Self::ID)
+    }
+
+    /// Writes the packet with its length and id in front
+    fn write_packet<W: Write>(&self, writer: &mut W) -> Result<(), ProtocolError> {
+        let mut body = Vec::new();
+        self.write_to(&mut body)?;
+        write_frame(writer, Self::ID, &body)
+    }
+
+    /// Like `write_packet`, using the id the packet has in `protocol`
+    fn write_packet_for<W: Write>(
+        &self,
+        protocol: i32,
+        writer: &mut W,
+    ) -> Result<(), ProtocolError> {
+        let mut body = Vec::new();
+        self.write_to(&mut body)?;
+        write_frame(writer, Self::id_for(protocol), &body)
+    }
+}
+
+/// Writes a packet body with its length and id in front, as a single write
+pub fn write_frame<W: Write>(writer: &mut W, id: i32, body: &[u8]) -> Result<(), ProtocolError> {
+    let length = varint::encoded_len(id) + body.len();
+    let mut frame = Vec::with_capacity(varint::MAX_VARINT_LEN + length);
+    crate::write_length(&mut frame, length)?;
+    varint::encode_into(id, &mut frame)?;
+    frame.extend_from_slice(body);
+    writer.write_all(&frame)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{McString, Packet, Packets, VarInt, roundtrip};
+
+    #[derive(Debug, PartialEq, Packet)]
+    #[packet(id = 0x00, state = Handshaking, direction = Serverbound)]
+    struct Handshake {
+        protocol: VarInt,
+        host: McString<255>,
+        port: u16,
+        intent: VarInt,
+    }
+
+    #[derive(Debug, PartialEq, Packet)]
+    #[packet(id = 0x00, state = Status, direction = Serverbound)]
+    struct StatusRequest;
+
+    #[derive(Debug, PartialEq, Packet)]
+    #[packet(id = 0x01, state = Status, direction = Serverbound)]
+    struct PingRequest(u64);
+
+    #[derive(Debug, PartialEq, Packet)]
+    #[packet(id = 0x02, state = Configuration, direction = Clientbound, name = "disconnect")]
+    struct ConfigurationDisconnect(String);
+
+    #[derive(Debug, PartialEq, Packet)]
+    #[packet(id = 0x03, state = Configuration, direction = Serverbound, name = "finish_configuration")]
+    struct FinishConfiguration;
+
+    #[derive(Debug, PartialEq, Packets)]
+    enum Serverbound {
+        Handshake(Handshake),
+        StatusRequest(StatusRequest),
+        PingRequest(PingRequest),
+        FinishConfiguration(FinishConfiguration),
+    }
+
+    #[derive(Debug, PartialEq, Packets)]
+    enum Clientbound {
+        ConfigurationDisconnect(ConfigurationDisconnect),
+    }
+
+    fn handshake() -> Handshake {
+        Handshake {
+            protocol: VarInt(767),
+            host: McString::new("localhost").unwrap(),
+            port: 25565,
+            intent: VarInt(1),
+        }
+    }
+
+    #[test]
+    fn test_derive_roundtrip() {
+        let out = roundtrip(handshake());
+        assert_eq!(&out[..2], &[0xff, 0x05]);
+        assert_eq!(&out[out.len() - 3..], &[0x63, 0xdd, 0x01]);
+        assert!(roundtrip(StatusRequest).is_empty());
+        assert_eq!(roundtrip(PingRequest(1)), vec![0, 0, 0, 0, 0, 0, 0, 1]);
+        assert_eq!(PingRequest::ID, 1);
+        assert_eq!(PingRequest::STATE, State::Status);
+        assert_eq!(PingRequest::DIRECTION, Direction::Serverbound);
+    }
+
+    #[test]
+    fn test_write_packet() {
+        let mut out = Vec::new();
+        PingRequest(2).write_packet(&mut out).unwrap();
+        assert_eq!(out, vec![9, 1, 0, 0, 0, 0, 0, 0, 0, 2]);
+    }
+
+    #[test]
+    fn test_versioned_ids() {
+        assert_eq!(ConfigurationDisconnect::id_for(764), 0x01);
+        assert_eq!(ConfigurationDisconnect::id_for(766), 0x02);
+        // Unknown versions keep the declared id
+        assert_eq!(ConfigurationDisconnect::id_for(0), 0x02);
+        assert_eq!(PingRequest::id_for(764), 0x01);
+        let mut out = Vec::new();
+        ConfigurationDisconnect(String::from("a"))
+            .write_packet_for(764, &mut out)
+            .unwrap();
+        assert_eq!(out, vec![3, 0x01, 1, b'a']);
+    }
+
+    #[test]
+    fn test_versioned_dispatch() {
+        let decode = |protocol, id| {
+            Serverbound::decode_versioned(protocol, State::Configuration, id, &mut [].as_slice())
+                .unwrap()
+        };
+        assert_eq!(
+            decode(764, 0x02),
+            Some(Serverbound::FinishConfiguration(FinishConfiguration))
+        );
+        assert_eq!(decode(764, 0x03), None);
+        assert_eq!(
+            decode(766, 0x03),
+            Some(Serverbound::FinishConfiguration(FinishConfiguration))
+        );
+    }
+
+    #[test]
+    fn test_versioned_dispatch_clientbound() {
+        let mut body = Vec::new();
+        String::from("a").write_to(&mut body).unwrap();
+        let decode = |protocol, id| {
+            Clientbound::decode_versioned(protocol, State::Configuration, id, &mut body.as_slice())
+                .unwrap()
+        };
+        let disconnect =
+            Clientbound::ConfigurationDisconnect(ConfigurationDisconnect(String::from("a")));
+        assert_eq!(decode(764, 0x01), Some(disconnect));
+        assert_eq!(decode(764, 0x02), None);
+    }
+
+    #[test]
+    fn test_dispatch() {
+        let mut body = Vec::new();
+        handshake().write_to(&mut body).unwrap();
+        let packet = Serverbound::decode(State::Handshaking, 0, &mut body.as_slice()).unwrap();
+        assert_eq!(packet, Some(Serverbound::Handshake(handshake())));
+        let packet = Serverbound::decode(State::Status, 0, &mut [].as_slice()).unwrap();
+        assert_eq!(packet, Some(Serverbound::StatusRequest(StatusRequest)));
+        assert_eq!(packet.unwrap().id(), 0);
+        let packet = Serverbound::decode(State::Login, 0, &mut [].as_slice()).unwrap();
+        assert_eq!(packet, None);
+        let packet = Serverbound::decode(State::Status, 2, &mut [].as_slice()).unwrap();
+        assert_eq!(packet, None);
+    }
+}
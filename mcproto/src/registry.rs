@@ -0,0 +1,263 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    fmt,
+    sync::OnceLock,
+};
+
+use serde::Deserialize;
+
+use crate::{Direction, State};
+
+/// The packet tables embedded in the crate, in the minecraft-data `protocol.json` layout
+const BUILTIN: &[&str] = &[
+    include_str!("../data/protocol/1.7.2.json"),
+    include_str!("../data/protocol/1.8.json"),
+    include_str!("../data/protocol/1.13.json"),
+    include_str!("../data/protocol/1.20.2.json"),
+    include_str!("../data/protocol/1.20.3.json"),
+    include_str!("../data/protocol/1.20.5.json"),
+    include_str!("../data/protocol/1.21.json"),
+];
+
+#[derive(Debug)]
+pub enum RegistryError {
+    Json(serde_json::Error),
+    InvalidState(String),
+    InvalidId(String),
+}
+
+impl fmt::Display for RegistryError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Json(e) => write!(f, "{}", e),
+            Self::InvalidState(s) => write!(f, "Unknown protocol state '{}'", s),
+            Self::InvalidId(s) => write!(f, "Invalid packet id '{}'", s),
+        }
+    }
+}
+
+impl std::error::Error for RegistryError {}
+
+impl From<serde_json::Error> for RegistryError {
+    fn from(value: serde_json::Error) -> Self {
+        RegistryError::Json(value)
+    }
+}
+
+#[derive(Deserialize)]
+struct ProtocolFile {
+    version: VersionFile,
+    #[serde(flatten)]
+    states: HashMap<String, StateFile>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct VersionFile {
+    minecraft_version: String,
+    version: i32,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct StateFile {
+    to_client: Option<MappingsFile>,
+    to_server: Option<MappingsFile>,
+}
+
+#[derive(Deserialize)]
+struct MappingsFile {
+    mappings: BTreeMap<String, String>,
+}
+
+fn parse_state(name: &str) -> Result<State, RegistryError> {
+    match name {
+        "handshaking" => Ok(State::Handshaking),
+        "status" => Ok(State::Status),
+        "login" => Ok(State::Login),
+        "configuration" => Ok(State::Configuration),
+        "play" => Ok(State::Play),
+        s => Err(RegistryError::InvalidState(s.to_string())),
+    }
+}
+
+fn parse_id(id: &str) -> Result<i32, RegistryError> {
+    let parsed = match id.strip_prefix("0x") {
+        Some(hex) => i32::from_str_radix(hex, 16),
+        None => id.parse(),
+    };
+    parsed.map_err(|_| RegistryError::InvalidId(id.to_string()))
+}
+
+/// The packet ids of a single protocol version
+#[derive(Debug)]
+pub struct VersionPackets {
+    pub minecraft_version: String,
+    pub protocol: i32,
+    ids: HashMap<(State, Direction, String), i32>,
+    names: HashMap<(State, Direction, i32), String>,
+}
+
+impl VersionPackets {
+    pub fn parse(text: &str) -> Result<Self, RegistryError> {
+        let file: ProtocolFile = serde_json::from_str(text)?;
+        let mut ids = HashMap::new();
+        let mut names = HashMap::new();
+        for (state, packets) in file.states {
+            let state = parse_state(&state)?;
+            let directions = [
+                (Direction::Clientbound, packets.to_client),
+                (Direction::Serverbound, packets.to_server),
+            ];
+            for (direction, mappings) in directions {
+                let Some(mappings) = mappings else { continue };
+                for (id, name) in mappings.mappings {
+                    let id = parse_id(&id)?;
+                    ids.insert((state, direction, name.clone()), id);
+                    names.insert((state, direction, id), name);
+                }
+            }
+        }
+        Ok(VersionPackets {
+            minecraft_version: file.version.minecraft_version,
+            protocol: file.version.version,
+            ids,
+            names,
+        })
+    }
+
+    pub fn packet_id(&self, state: State, direction: Direction, name: &str) -> Option<i32> {
+        self.ids.get(&(state, direction, name.to_string())).copied()
+    }
+
+    pub fn packet_name(&self, state: State, direction: Direction, id: i32) -> Option<&str> {
+        self.names.get(&(state, direction, id)).map(String::as_str)
+    }
+}
+
+/// Maps packet names to ids for every known protocol version.
+/// A protocol uses the table of the newest version at or below it,
+/// so releases between two tables share the older one
+#[derive(Debug)]
+pub struct PacketRegistry {
+    versions: Vec<VersionPackets>,
+}
+
+impl PacketRegistry {
+    pub fn parse(files: &[&str]) -> Result<Self, RegistryError> {
+        let mut versions = files
+            .iter()
+            .map(|f| VersionPackets::parse(f))
+            .collect::<Result<Vec<_>, _>>()?;
+        versions.sort_by_key(|v| v.protocol);
+        Ok(PacketRegistry { versions })
+    }
+
+    /// The registry built from the embedded data files
+    pub fn builtin() -> &'static PacketRegistry {
+        static REGISTRY: OnceLock<PacketRegistry> = OnceLock::new();
+        REGISTRY
+            .get_or_init(|| PacketRegistry::parse(BUILTIN).expect("invalid builtin packet data"))
+    }
+
+    pub fn for_protocol(&self, protocol: i32) -> Option<&VersionPackets> {
+        let idx = self.versions.partition_point(|v| v.protocol <= protocol);
+        idx.checked_sub(1).map(|i| &self.versions[i])
+    }
+
+    pub fn packet_id(
+        &self,
+        protocol: i32,
+        state: State,
+        direction: Direction,
+        name: &str,
+    ) -> Option<i32> {
+        self.for_protocol(protocol)?
+            .packet_id(state, direction, name)
+    }
+
+    pub fn packet_name(
+        &self,
+        protocol: i32,
+        state: State,
+        direction: Direction,
+        id: i32,
+    ) -> Option<&str> {
+        self.for_protocol(protocol)?
+            .packet_name(state, direction, id)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_builtin() {
+        let registry = PacketRegistry::builtin();
+        assert_eq!(registry.versions.len(), BUILTIN.len());
+        for v in &registry.versions {
+            assert_eq!(
+                v.packet_id(State::Status, Direction::Clientbound, "server_info"),
+                Some(0x00)
+            );
+        }
+    }
+
+    #[test]
+    fn test_version_lookup() {
+        let registry = PacketRegistry::builtin();
+        assert!(registry.for_protocol(3).is_none());
+        assert_eq!(registry.for_protocol(5).unwrap().minecraft_version, "1.7.2");
+        assert_eq!(registry.for_protocol(340).unwrap().minecraft_version, "1.8");
+        assert_eq!(
+            registry.for_protocol(767).unwrap().minecraft_version,
+            "1.21"
+        );
+        assert_eq!(
+            registry.for_protocol(900).unwrap().minecraft_version,
+            "1.21"
+        );
+    }
+
+    #[test]
+    fn test_packet_ids() {
+        let registry = PacketRegistry::builtin();
+        let disconnect = |protocol| {
+            registry.packet_id(
+                protocol,
+                State::Configuration,
+                Direction::Clientbound,
+                "disconnect",
+            )
+        };
+        assert_eq!(disconnect(763), None);
+        assert_eq!(disconnect(764), Some(0x01));
+        assert_eq!(disconnect(766), Some(0x02));
+        assert_eq!(
+            registry.packet_name(765, State::Login, Direction::Serverbound, 0x03),
+            Some("login_acknowledged")
+        );
+        assert_eq!(
+            registry.packet_id(47, State::Login, Direction::Clientbound, "compress"),
+            Some(0x03)
+        );
+    }
+
+    #[test]
+    fn test_invalid() {
+        assert!(matches!(
+            VersionPackets::parse(
+                r#"{"version": {"minecraftVersion": "a", "version": 1}, "lobby": {}}"#
+            ),
+            Err(RegistryError::InvalidState(_))
+        ));
+        assert!(matches!(
+            VersionPackets::parse(
+                r#"{"version": {"minecraftVersion": "a", "version": 1},
+                    "status": {"toClient": {"mappings": {"0xzz": "a"}}}}"#
+            ),
+            Err(RegistryError::InvalidId(_))
+        ));
+    }
+}
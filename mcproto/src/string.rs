@@ -0,0 +1,213 @@
+use std::{
+    fmt,
+    io::{Read, Write},
+    ops::Deref,
+};
+
+use crate::{McRead, McWrite, ProtocolError};
+
+/// The default maximum length of a protocol string, in UTF-16 code units
+pub const STRING_MAX_LEN: usize = 32767;
+
+fn read_string<R: Read>(reader: &mut R, max: usize) -> Result<String, ProtocolError> {
+    // Each UTF-16 code unit takes at most 3 bytes in UTF-8
+    let len = crate::read_length(reader, max * 3).map_err(|e| match e {
+        ProtocolError::ArrayTooLong { len, .. } => ProtocolError::StringTooLong { len, max },
+        e => e,
+    })?;
+    let mut buf = vec![0u8; len];
+    reader.read_exact(&mut buf)?;
+    let s = String::from_utf8(buf)?;
+    check_len(&s, max)?;
+    Ok(s)
+}
+
+fn write_string<W: Write>(writer: &mut W, s: &str, max: usize) -> Result<(), ProtocolError> {
+    check_len(s, max)?;
+    crate::write_length(writer, s.len())?;
+    writer.write_all(s.as_bytes())?;
+    Ok(())
+}
+
+fn check_len(s: &str, max: usize) -> Result<(), ProtocolError> {
+    let len = s.encode_utf16().count();
+    if len > max {
+        return Err(ProtocolError::StringTooLong { len, max });
+    }
+    Ok(())
+}
+
+impl McRead for String {
+    fn read_from<R: Read>(reader: &mut R) -> Result<Self, ProtocolError> {
+        read_string(reader, STRING_MAX_LEN)
+    }
+}
+
+impl McWrite for String {
+    fn write_to<W: Write>(&self, writer: &mut W) -> Result<(), ProtocolError> {
+        self.as_str().write_to(writer)
+    }
+}
+
+impl McWrite for str {
+    fn write_to<W: Write>(&self, writer: &mut W) -> Result<(), ProtocolError> {
+        write_string(writer, self, STRING_MAX_LEN)
+    }
+}
+
+/// A string with at most `MAX` UTF-16 code units, like `String (16)` for player names
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Default)]
+pub struct McString<const MAX: usize>(pub String);
+
+impl<const MAX: usize> McString<MAX> {
+    pub fn new(s: impl Into<String>) -> Result<Self, ProtocolError> {
+        let s = s.into();
+        check_len(&s, MAX)?;
+        Ok(McString(s))
+    }
+
+    pub fn into_inner(self) -> String {
+        self.0
+    }
+}
+
+impl<const MAX: usize> Deref for McString<MAX> {
+    type Target = str;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl<const MAX: usize> fmt::Display for McString<MAX> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl<const MAX: usize> McRead for McString<MAX> {
+    fn read_from<R: Read>(reader: &mut R) -> Result<Self, ProtocolError> {
+        Ok(McString(read_string(reader, MAX)?))
+    }
+}
+
+impl<const MAX: usize> McWrite for McString<MAX> {
+    fn write_to<W: Write>(&self, writer: &mut W) -> Result<(), ProtocolError> {
+        write_string(writer, &self.0, MAX)
+    }
+}
+
+/// A namespaced location like `minecraft:item/diamond`
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Identifier {
+    pub namespace: String,
+    pub path: String,
+}
+
+impl Identifier {
+    pub const DEFAULT_NAMESPACE: &str = "minecraft";
+
+    /// Parses an identifier, using the `minecraft` namespace if there is none
+    pub fn parse(s: &str) -> Result<Self, ProtocolError> {
+        let (namespace, path) = s.split_once(':').unwrap_or((Self::DEFAULT_NAMESPACE, s));
+        let namespace = if namespace.is_empty() {
+            Self::DEFAULT_NAMESPACE
+        } else {
+            namespace
+        };
+        let valid_namespace = namespace
+            .chars()
+            .all(|c| matches!(c, 'a'..='z' | '0'..='9' | '.' | '-' | '_'));
+        let valid_path = !path.is_empty()
+            && path
+                .chars()
+                .all(|c| matches!(c, 'a'..='z' | '0'..='9' | '.' | '-' | '_' | '/'));
+        if !valid_namespace || !valid_path {
+            return Err(ProtocolError::InvalidIdentifier(s.to_string()));
+        }
+        Ok(Identifier {
+            namespace: namespace.to_string(),
+            path: path.to_string(),
+        })
+    }
+}
+
+impl fmt::Display for Identifier {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}", self.namespace, self.path)
+    }
+}
+
+impl McRead for Identifier {
+    fn read_from<R: Read>(reader: &mut R) -> Result<Self, ProtocolError> {
+        Identifier::parse(&read_string(reader, STRING_MAX_LEN)?)
+    }
+}
+
+impl McWrite for Identifier {
+    fn write_to<W: Write>(&self, writer: &mut W) -> Result<(), ProtocolError> {
+        write_string(writer, &self.to_string(), STRING_MAX_LEN)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::roundtrip;
+
+    #[test]
+    fn test_string() {
+        assert_eq!(roundtrip(String::from("abc")), vec![3, b'a', b'b', b'c']);
+        roundtrip(String::from("§d§lstatus server"));
+        roundtrip(String::new());
+    }
+
+    #[test]
+    fn test_bounded_string() {
+        roundtrip(McString::<16>::new("jeb_").unwrap());
+        assert!(McString::<4>::new("jeb__").is_err());
+        let mut out = Vec::new();
+        "Herobrine".write_to(&mut out).unwrap();
+        assert!(matches!(
+            McString::<4>::read_from(&mut out.as_slice()),
+            Err(ProtocolError::StringTooLong { .. })
+        ));
+        assert_eq!(
+            McString::<9>::read_from(&mut out.as_slice()).unwrap().0,
+            "Herobrine"
+        );
+    }
+
+    #[test]
+    fn test_string_utf16_len() {
+        // 'é' is 2 bytes in UTF-8 but a single UTF-16 code unit
+        roundtrip(McString::<3>::new("ééé").unwrap());
+        // Characters outside the BMP count as 2 code units
+        assert!(McString::<1>::new("🎉").is_err());
+    }
+
+    #[test]
+    fn test_invalid_string() {
+        let data = [2u8, 0xff, 0xfe];
+        assert!(matches!(
+            String::read_from(&mut data.as_slice()),
+            Err(ProtocolError::InvalidUtf8(_))
+        ));
+        let data = [5u8, b'a'];
+        assert!(matches!(
+            String::read_from(&mut data.as_slice()),
+            Err(ProtocolError::Io(_))
+        ));
+    }
+
+    #[test]
+    fn test_identifier() {
+        let id = Identifier::parse("item/nether_star").unwrap();
+        assert_eq!(id.to_string(), "minecraft:item/nether_star");
+        roundtrip(id);
+        roundtrip(Identifier::parse("forge:network").unwrap());
+        assert!(Identifier::parse("Minecraft:stone").is_err());
+        assert!(Identifier::parse("minecraft:").is_err());
+        assert!(Identifier::parse("a:b:c").is_err());
+    }
+}
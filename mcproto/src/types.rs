@@ -0,0 +1,264 @@
+use std::{
+    io::{Read, Write},
+    ops::Deref,
+};
+
+use uuid::Uuid;
+
+use crate::{McRead, McWrite, ProtocolError};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default, PartialOrd, Ord)]
+pub struct VarInt(pub i32);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default, PartialOrd, Ord)]
+pub struct VarLong(pub i64);
+
+impl From<i32> for VarInt {
+    fn from(value: i32) -> Self {
+        VarInt(value)
+    }
+}
+
+impl From<VarInt> for i32 {
+    fn from(value: VarInt) -> Self {
+        value.0
+    }
+}
+
+impl From<i64> for VarLong {
+    fn from(value: i64) -> Self {
+        VarLong(value)
+    }
+}
+
+impl From<VarLong> for i64 {
+    fn from(value: VarLong) -> Self {
+        value.0
+    }
+}
+
+impl McRead for VarInt {
+    fn read_from<R: Read>(reader: &mut R) -> Result<Self, ProtocolError> {
+        Ok(VarInt(varint::decode_stream(reader)?))
+    }
+}
+
+impl McWrite for VarInt {
+    fn write_to<W: Write>(&self, writer: &mut W) -> Result<(), ProtocolError> {
+        varint::encode_into(self.0, writer)?;
+        Ok(())
+    }
+}
+
+impl McRead for VarLong {
+    fn read_from<R: Read>(reader: &mut R) -> Result<Self, ProtocolError> {
+        Ok(VarLong(varint::decode_long_stream(reader)?))
+    }
+}
+
+impl McWrite for VarLong {
+    fn write_to<W: Write>(&self, writer: &mut W) -> Result<(), ProtocolError> {
+        varint::encode_long_into(self.0, writer)?;
+        Ok(())
+    }
+}
+
+impl McRead for Uuid {
+    fn read_from<R: Read>(reader: &mut R) -> Result<Self, ProtocolError> {
+        Ok(Uuid::from_u128(u128::read_from(reader)?))
+    }
+}
+
+impl McWrite for Uuid {
+    fn write_to<W: Write>(&self, writer: &mut W) -> Result<(), ProtocolError> {
+        self.as_u128().write_to(writer)
+    }
+}
+
+/// A block position, packed into 26 bits for x and z and 12 bits for y
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct Position {
+    pub x: i32,
+    pub y: i32,
+    pub z: i32,
+}
+
+impl McRead for Position {
+    fn read_from<R: Read>(reader: &mut R) -> Result<Self, ProtocolError> {
+        let packed = i64::read_from(reader)?;
+        // Arithmetic shifts sign-extend each field
+        Ok(Position {
+            x: (packed >> 38) as i32,
+            y: (packed << 52 >> 52) as i32,
+            z: (packed << 26 >> 38) as i32,
+        })
+    }
+}
+
+impl McWrite for Position {
+    fn write_to<W: Write>(&self, writer: &mut W) -> Result<(), ProtocolError> {
+        let packed = ((self.x as i64 & 0x3ff_ffff) << 38)
+            | ((self.z as i64 & 0x3ff_ffff) << 12)
+            | (self.y as i64 & 0xfff);
+        packed.write_to(writer)
+    }
+}
+
+/// Reads a prefixed list of values without trusting the length for the allocation
+fn read_array<T: McRead, R: Read>(reader: &mut R, max: usize) -> Result<Vec<T>, ProtocolError> {
+    let len = crate::read_length(reader, max)?;
+    let mut values = Vec::with_capacity(len.min(1024));
+    for _ in 0..len {
+        values.push(T::read_from(reader)?);
+    }
+    Ok(values)
+}
+
+fn write_array<T: McWrite, W: Write>(
+    writer: &mut W,
+    values: &[T],
+    max: usize,
+) -> Result<(), ProtocolError> {
+    if values.len() > max {
+        return Err(ProtocolError::ArrayTooLong {
+            len: values.len(),
+            max,
+        });
+    }
+    crate::write_length(writer, values.len())?;
+    for v in values {
+        v.write_to(writer)?;
+    }
+    Ok(())
+}
+
+/// A VarInt-prefixed array with at most `MAX` elements
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Default)]
+pub struct PrefixedArray<T, const MAX: usize>(pub Vec<T>);
+
+impl<T, const MAX: usize> Deref for PrefixedArray<T, MAX> {
+    type Target = [T];
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl<T: McRead, const MAX: usize> McRead for PrefixedArray<T, MAX> {
+    fn read_from<R: Read>(reader: &mut R) -> Result<Self, ProtocolError> {
+        Ok(PrefixedArray(read_array(reader, MAX)?))
+    }
+}
+
+impl<T: McWrite, const MAX: usize> McWrite for PrefixedArray<T, MAX> {
+    fn write_to<W: Write>(&self, writer: &mut W) -> Result<(), ProtocolError> {
+        write_array(writer, &self.0, MAX)
+    }
+}
+
+impl<T: McRead> McRead for Vec<T> {
+    fn read_from<R: Read>(reader: &mut R) -> Result<Self, ProtocolError> {
+        read_array(reader, i32::MAX as usize)
+    }
+}
+
+impl<T: McWrite> McWrite for Vec<T> {
+    fn write_to<W: Write>(&self, writer: &mut W) -> Result<(), ProtocolError> {
+        write_array(writer, self, i32::MAX as usize)
+    }
+}
+
+/// A boolean-prefixed optional value
+impl<T: McRead> McRead for Option<T> {
+    fn read_from<R: Read>(reader: &mut R) -> Result<Self, ProtocolError> {
+        if bool::read_from(reader)? {
+            Ok(Some(T::read_from(reader)?))
+        } else {
+            Ok(None)
+        }
+    }
+}
+
+impl<T: McWrite> McWrite for Option<T> {
+    fn write_to<W: Write>(&self, writer: &mut W) -> Result<(), ProtocolError> {
+        match self {
+            Some(v) => {
+                true.write_to(writer)?;
+                v.write_to(writer)
+            }
+            None => false.write_to(writer),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{McString, roundtrip};
+
+    #[test]
+    fn test_varint() {
+        assert_eq!(roundtrip(VarInt(128)), vec![0x80, 0x01]);
+        assert_eq!(roundtrip(VarInt(-1)).len(), 5);
+        assert_eq!(roundtrip(VarLong(-1)).len(), 10);
+        roundtrip(VarLong(i64::MAX));
+    }
+
+    #[test]
+    fn test_uuid() {
+        let uuid = Uuid::parse_str("853c80ef-3c37-49fd-aa49-938b674adae6").unwrap();
+        let out = roundtrip(uuid);
+        assert_eq!(out, uuid.as_bytes().to_vec());
+    }
+
+    #[test]
+    fn test_position() {
+        // Example from https://minecraft.wiki/w/Java_Edition_protocol/Data_types#Position
+        let pos = Position {
+            x: 18357644,
+            y: 831,
+            z: -20882616,
+        };
+        let out = roundtrip(pos);
+        assert_eq!(out, 0x4607_632c_15b4_833fu64.to_be_bytes().to_vec());
+        roundtrip(Position {
+            x: -1,
+            y: -64,
+            z: -33554432,
+        });
+    }
+
+    #[test]
+    fn test_arrays() {
+        assert_eq!(roundtrip(vec![1u8, 2, 3]), vec![3, 1, 2, 3]);
+        roundtrip(Vec::<VarInt>::new());
+        roundtrip(PrefixedArray::<McString<16>, 2>(vec![
+            McString::new("jeb_").unwrap(),
+            McString::new("Herobrine").unwrap(),
+        ]));
+        let mut out = Vec::new();
+        assert!(
+            PrefixedArray::<u8, 2>(vec![1, 2, 3])
+                .write_to(&mut out)
+                .is_err()
+        );
+        vec![1u8, 2, 3].write_to(&mut out).unwrap();
+        assert!(matches!(
+            PrefixedArray::<u8, 2>::read_from(&mut out.as_slice()),
+            Err(ProtocolError::ArrayTooLong { len: 3, max: 2 })
+        ));
+    }
+
+    #[test]
+    fn test_hostile_array_length() {
+        let data = varint::encode(i32::MAX);
+        assert!(Vec::<u64>::read_from(&mut data.as_slice()).is_err());
+    }
+
+    #[test]
+    fn test_option() {
+        assert_eq!(roundtrip(Some(VarInt(1))), vec![1, 1]);
+        assert_eq!(roundtrip(None::<VarInt>), vec![0]);
+        roundtrip(Some(String::from("abc")));
+    }
+}
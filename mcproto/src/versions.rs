@@ -0,0 +1,338 @@
+use std::{
+    fmt,
+    io::{Read, Write},
+    ops::{Bound, RangeBounds},
+    sync::OnceLock,
+};
+
+use serde::{Deserialize, Deserializer, de};
+
+use crate::{McRead, McWrite, ProtocolError, VarInt};
+
+/// Every release and its protocol number, newest first, in the minecraft-data
+/// `protocolVersions.json` layout
+const BUILTIN: &str = include_str!("../data/protocol_versions.json");
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct Release {
+    pub minecraft_version: String,
+    pub version: i32,
+    /// Whether the release uses the 1.7+ protocol; older releases number their protocols separately
+    pub uses_netty: bool,
+}
+
+fn releases() -> &'static [Release] {
+    static RELEASES: OnceLock<Vec<Release>> = OnceLock::new();
+    RELEASES.get_or_init(|| serde_json::from_str(BUILTIN).expect("invalid builtin version data"))
+}
+
+/// The releases using `protocol`, oldest first
+pub fn releases_for(protocol: i32, netty: bool) -> Vec<&'static Release> {
+    let mut found: Vec<_> = releases()
+        .iter()
+        .filter(|r| r.version == protocol && r.uses_netty == netty)
+        .collect();
+    found.reverse();
+    found
+}
+
+fn join_names(releases: &[&Release]) -> Option<String> {
+    match releases {
+        [] => None,
+        [r] => Some(r.minecraft_version.clone()),
+        [a, b] => Some(format!("{}/{}", a.minecraft_version, b.minecraft_version)),
+        [first, .., last] => Some(format!(
+            "{}-{}",
+            first.minecraft_version, last.minecraft_version
+        )),
+    }
+}
+
+/// The releases using a 1.7+ protocol number, like `1.21/1.21.1` for 767
+pub fn release_name(protocol: i32) -> Option<String> {
+    join_names(&releases_for(protocol, true))
+}
+
+/// The releases using a pre-1.7 protocol number, like `1.6.4` for 78
+pub fn legacy_release_name(protocol: i32) -> Option<String> {
+    join_names(&releases_for(protocol, false))
+}
+
+/// The protocol number of a release name like `1.20.4`
+pub fn protocol_for_release(name: &str) -> Option<i32> {
+    releases()
+        .iter()
+        .find(|r| r.uses_netty && r.minecraft_version == name)
+        .map(|r| r.version)
+}
+
+/// The newest known release
+pub fn latest() -> &'static Release {
+    &releases()[0]
+}
+
+/// A protocol number as sent in the handshake.
+/// Snapshots and pre-releases set bit 30 and count up from there
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct ProtocolVersion(pub i32);
+
+impl ProtocolVersion {
+    pub const SNAPSHOT_BIT: i32 = 0x4000_0000;
+
+    pub fn snapshot(n: i32) -> Self {
+        ProtocolVersion(Self::SNAPSHOT_BIT | n)
+    }
+
+    pub fn is_snapshot(self) -> bool {
+        self.0 > 0 && self.0 & Self::SNAPSHOT_BIT != 0
+    }
+
+    /// The snapshot's number, counting up since the snapshot protocol numbering started
+    pub fn snapshot_number(self) -> Option<i32> {
+        if self.is_snapshot() {
+            Some(self.0 & !Self::SNAPSHOT_BIT)
+        } else {
+            None
+        }
+    }
+
+    /// The releases using this protocol like `1.21/1.21.1`, or `Snapshot 261` for snapshots
+    pub fn name(self) -> Option<String> {
+        match self.snapshot_number() {
+            Some(n) => Some(format!("Snapshot {}", n)),
+            None => release_name(self.0),
+        }
+    }
+
+    /// The protocol number followed by its name if it's known, like `767 (1.21/1.21.1)`
+    pub fn describe(self) -> String {
+        match self.name() {
+            Some(name) => format!("{} ({})", self, name),
+            None => self.to_string(),
+        }
+    }
+}
+
+impl fmt::Display for ProtocolVersion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.is_snapshot() {
+            write!(f, "0x{:08x}", self.0)
+        } else {
+            write!(f, "{}", self.0)
+        }
+    }
+}
+
+impl From<i32> for ProtocolVersion {
+    fn from(value: i32) -> Self {
+        ProtocolVersion(value)
+    }
+}
+
+impl McRead for ProtocolVersion {
+    fn read_from<R: Read>(reader: &mut R) -> Result<Self, ProtocolError> {
+        Ok(ProtocolVersion(VarInt::read_from(reader)?.0))
+    }
+}
+
+impl McWrite for ProtocolVersion {
+    fn write_to<W: Write>(&self, writer: &mut W) -> Result<(), ProtocolError> {
+        VarInt(self.0).write_to(writer)
+    }
+}
+
+/// Reads a protocol number, or the name of a release like "1.20.4"
+impl<'de> Deserialize<'de> for ProtocolVersion {
+    fn deserialize<D: Deserializer<'de>>(d: D) -> Result<Self, D::Error> {
+        #[derive(Deserialize)]
+        #[serde(untagged)]
+        enum Protocol {
+            Number(i32),
+            Release(String),
+        }
+        match Protocol::deserialize(d)? {
+            Protocol::Number(p) => Ok(ProtocolVersion(p)),
+            Protocol::Release(name) => match protocol_for_release(&name) {
+                Some(p) => Ok(ProtocolVersion(p)),
+                None => Err(de::Error::custom(format!("unknown release '{}'", name))),
+            },
+        }
+    }
+}
+
+/// A range of protocols, written like a Rust range: `..=763`, `764..`, `760..=763`, `..764`
+/// or a single protocol. Each end can be a number or the name of a release like `1.20.4`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ProtocolRange {
+    pub start: Bound<ProtocolVersion>,
+    pub end: Bound<ProtocolVersion>,
+}
+
+impl ProtocolRange {
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let protocol = |p: &str| -> Result<ProtocolVersion, String> {
+            let p = p.trim();
+            let number = match p.strip_prefix("0x") {
+                Some(hex) => i32::from_str_radix(hex, 16).ok(),
+                None => p.parse().ok(),
+            };
+            number
+                .or_else(|| protocol_for_release(p))
+                .map(ProtocolVersion)
+                .ok_or_else(|| format!("unknown protocol or release '{}'", p))
+        };
+        let Some((start, end)) = text.split_once("..") else {
+            let p = protocol(text)?;
+            return Ok(ProtocolRange {
+                start: Bound::Included(p),
+                end: Bound::Included(p),
+            });
+        };
+        let start = match start.trim() {
+            "" => Bound::Unbounded,
+            p => Bound::Included(protocol(p)?),
+        };
+        let end = match end.strip_prefix('=') {
+            Some(p) => Bound::Included(protocol(p)?),
+            None if end.trim().is_empty() => Bound::Unbounded,
+            None => Bound::Excluded(protocol(end)?),
+        };
+        let empty = match (start, end) {
+            (Bound::Included(s), Bound::Included(e)) => s > e,
+            (Bound::Included(s), Bound::Excluded(e)) => s >= e,
+            _ => false,
+        };
+        if empty {
+            return Err(format!("'{}' doesn't have any protocols", text));
+        }
+        Ok(ProtocolRange { start, end })
+    }
+
+    pub fn contains(&self, protocol: ProtocolVersion) -> bool {
+        RangeBounds::contains(&(self.start, self.end), &protocol)
+    }
+}
+
+impl fmt::Display for ProtocolRange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match (self.start, self.end) {
+            (Bound::Included(s), Bound::Included(e)) if s == e => return write!(f, "{}", s),
+            (Bound::Included(s), _) => write!(f, "{}..", s)?,
+            _ => write!(f, "..")?,
+        }
+        match self.end {
+            Bound::Included(e) => write!(f, "={}", e),
+            Bound::Excluded(e) => write!(f, "{}", e),
+            Bound::Unbounded => Ok(()),
+        }
+    }
+}
+
+/// Reads a protocol number, or a range written as a string
+impl<'de> Deserialize<'de> for ProtocolRange {
+    fn deserialize<D: Deserializer<'de>>(d: D) -> Result<Self, D::Error> {
+        #[derive(Deserialize)]
+        #[serde(untagged)]
+        enum Range {
+            Number(i32),
+            Text(String),
+        }
+        match Range::deserialize(d)? {
+            Range::Number(p) => Ok(ProtocolRange {
+                start: Bound::Included(ProtocolVersion(p)),
+                end: Bound::Included(ProtocolVersion(p)),
+            }),
+            Range::Text(text) => ProtocolRange::parse(&text).map_err(de::Error::custom),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_release_names() {
+        assert_eq!(release_name(767).unwrap(), "1.21/1.21.1");
+        assert_eq!(release_name(764).unwrap(), "1.20.2");
+        assert_eq!(release_name(47).unwrap(), "1.8-1.8.9");
+        assert_eq!(release_name(12345), None);
+        assert_eq!(legacy_release_name(78).unwrap(), "1.6.4");
+        assert_eq!(legacy_release_name(47).unwrap(), "1.4.2");
+    }
+
+    #[test]
+    fn test_protocol_for_release() {
+        assert_eq!(protocol_for_release("1.20.4"), Some(765));
+        assert_eq!(protocol_for_release("1.7.10"), Some(5));
+        assert_eq!(protocol_for_release("1.6.4"), None);
+        assert_eq!(protocol_for_release("2.0"), None);
+    }
+
+    #[test]
+    fn test_snapshot() {
+        let snapshot = ProtocolVersion(0x4000_0105);
+        assert!(snapshot.is_snapshot());
+        assert_eq!(snapshot, ProtocolVersion::snapshot(261));
+        assert_eq!(snapshot.snapshot_number(), Some(261));
+        assert_eq!(snapshot.describe(), "0x40000105 (Snapshot 261)");
+        assert!(!ProtocolVersion(767).is_snapshot());
+        assert!(!ProtocolVersion(-1).is_snapshot());
+        assert_eq!(ProtocolVersion(767).describe(), "767 (1.21/1.21.1)");
+        assert_eq!(ProtocolVersion(9999).describe(), "9999");
+        crate::roundtrip(snapshot);
+    }
+
+    #[test]
+    fn test_deserialize() {
+        let v: Vec<ProtocolVersion> =
+            serde_json::from_str(r#"[767, 1073742085, "1.20.4"]"#).unwrap();
+        assert_eq!(
+            v,
+            vec![
+                ProtocolVersion(767),
+                ProtocolVersion::snapshot(261),
+                ProtocolVersion(765)
+            ]
+        );
+        assert!(serde_json::from_str::<ProtocolVersion>(r#""1.99""#).is_err());
+    }
+
+    #[test]
+    fn test_sorted() {
+        let netty: Vec<_> = releases().iter().filter(|r| r.uses_netty).collect();
+        assert!(netty.windows(2).all(|w| w[0].version >= w[1].version));
+        assert_eq!(latest().version, netty[0].version);
+    }
+
+    #[test]
+    fn test_protocol_range() {
+        let old = ProtocolRange::parse("..=763").unwrap();
+        assert!(old.contains(ProtocolVersion(763)));
+        assert!(old.contains(ProtocolVersion(47)));
+        assert!(!old.contains(ProtocolVersion(764)));
+        let new = ProtocolRange::parse("1.20.2..").unwrap();
+        assert_eq!(new.start, Bound::Included(ProtocolVersion(764)));
+        assert!(new.contains(ProtocolVersion(767)));
+        assert!(new.contains(ProtocolVersion::snapshot(261)));
+        let between = ProtocolRange::parse("760 .. 0x300").unwrap();
+        assert!(between.contains(ProtocolVersion(767)));
+        assert!(!between.contains(ProtocolVersion(768)));
+        assert_eq!(ProtocolRange::parse("1.21").unwrap().to_string(), "767");
+        assert_eq!(between.to_string(), "760..768");
+        assert_eq!(old.to_string(), "..=763");
+        assert_eq!(new.to_string(), "764..");
+        assert!(
+            ProtocolRange::parse("..")
+                .unwrap()
+                .contains(ProtocolVersion(5))
+        );
+        assert!(ProtocolRange::parse("763..=760").is_err());
+        assert!(ProtocolRange::parse("763..763").is_err());
+        assert!(ProtocolRange::parse("..=1.99").is_err());
+        let v: Vec<ProtocolRange> = serde_json::from_str(r#"[767, "..=763"]"#).unwrap();
+        assert_eq!(v[0], ProtocolRange::parse("767").unwrap());
+        assert!(serde_json::from_str::<ProtocolRange>(r#""abc""#).is_err());
+    }
+}
@@ -0,0 +1,256 @@
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::{format_ident, quote};
+use syn::{
+    Data, DeriveInput, Error, Expr, Fields, Ident, Index, LitStr, parse_macro_input,
+    spanned::Spanned,
+};
+
+/// Derives `McRead`, `McWrite` and `Packet` for a struct, reading and writing its fields in order.
+///
+/// ```ignore
+/// #[derive(Packet)]
+/// #[packet(id = 0x00, state = Handshaking, direction = Serverbound)]
+/// struct Handshake { protocol: VarInt, host: McString<255>, port: u16, intent: VarInt }
+/// ```
+#[proc_macro_derive(Packet, attributes(packet))]
+pub fn derive_packet(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    expand_packet(input)
+        .unwrap_or_else(Error::into_compile_error)
+        .into()
+}
+
+/// Derives a `decode` function for an enum whose variants each wrap a single packet,
+/// picking the variant from the connection state and packet id.
+#[proc_macro_derive(Packets)]
+pub fn derive_packets(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    expand_packets(input)
+        .unwrap_or_else(Error::into_compile_error)
+        .into()
+}
+
+struct PacketAttrs {
+    id: Expr,
+    state: Ident,
+    direction: Ident,
+    name: Option<LitStr>,
+}
+
+fn parse_packet_attrs(input: &DeriveInput) -> Result<PacketAttrs, Error> {
+    let mut id = None;
+    let mut state = None;
+    let mut direction = None;
+    let mut name = None;
+    for attr in input.attrs.iter().filter(|a| a.path().is_ident("packet")) {
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("id") {
+                id = Some(meta.value()?.parse::<Expr>()?);
+            } else if meta.path.is_ident("state") {
+                state = Some(meta.value()?.parse::<Ident>()?);
+            } else if meta.path.is_ident("direction") {
+                direction = Some(meta.value()?.parse::<Ident>()?);
+            } else if meta.path.is_ident("name") {
+                name = Some(meta.value()?.parse::<LitStr>()?);
+            } else {
+                return Err(meta.error("expected `id`, `state`, `direction` or `name`"));
+            }
+            Ok(())
+        })?;
+    }
+    let missing = |name: &str| {
+        Error::new(
+            input.ident.span(),
+            format!("missing `#[packet({name} = ...)]` attribute"),
+        )
+    };
+    Ok(PacketAttrs {
+        id: id.ok_or_else(|| missing("id"))?,
+        state: state.ok_or_else(|| missing("state"))?,
+        direction: direction.ok_or_else(|| missing("direction"))?,
+        name,
+    })
+}
+
+fn expand_packet(input: DeriveInput) -> Result<TokenStream2, Error> {
+    let attrs = parse_packet_attrs(&input)?;
+    let Data::Struct(data) = &input.data else {
+        return Err(Error::new(
+            input.span(),
+            "Packet can only be derived for structs",
+        ));
+    };
+    let name = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+
+    let (read_body, write_body) = match &data.fields {
+        Fields::Named(fields) => {
+            let names: Vec<_> = fields.named.iter().map(|f| &f.ident).collect();
+            let types = fields.named.iter().map(|f| &f.ty);
+            (
+                quote! {
+                    Ok(Self {
+                        #(#names: <#types as ::mcproto::McRead>::read_from(reader)?,)*
+                    })
+                },
+                quote! {
+                    #(::mcproto::McWrite::write_to(&self.#names, writer)?;)*
+                },
+            )
+        }
+        Fields::Unnamed(fields) => {
+            let indices = (0..fields.unnamed.len()).map(Index::from);
+            let types = fields.unnamed.iter().map(|f| &f.ty);
+            (
+                quote! {
+                    Ok(Self(#(<#types as ::mcproto::McRead>::read_from(reader)?,)*))
+                },
+                quote! {
+                    #(::mcproto::McWrite::write_to(&self.#indices, writer)?;)*
+                },
+            )
+        }
+        Fields::Unit => (quote! { Ok(Self) }, quote! {}),
+    };
+    let PacketAttrs {
+        id,
+        state,
+        direction,
+        name: packet_name,
+    } = attrs;
+    let packet_name = packet_name.map(|n| quote! { const NAME: Option<&'static str> = Some(#n); });
+
+    Ok(quote! {
+        impl #impl_generics ::mcproto::McRead for #name #ty_generics #where_clause {
+            #[allow(unused_variables)]
+            fn read_from<R: ::std::io::Read>(reader: &mut R) -> Result<Self, ::mcproto::ProtocolError> {
+                #read_body
+            }
+        }
+
+        impl #impl_generics ::mcproto::McWrite for #name #ty_generics #where_clause {
+            #[allow(unused_variables)]
+            fn write_to<W: ::std::io::Write>(&self, writer: &mut W) -> Result<(), ::mcproto::ProtocolError> {
+                #write_body
+                Ok(())
+            }
+        }
+
+        impl #impl_generics ::mcproto::Packet for #name #ty_generics #where_clause {
+            const ID: i32 = #id;
+            const STATE: ::mcproto::State = ::mcproto::State::#state;
+            const DIRECTION: ::mcproto::Direction = ::mcproto::Direction::#direction;
+            #packet_name
+        }
+    })
+}
+
+fn expand_packets(input: DeriveInput) -> Result<TokenStream2, Error> {
+    let Data::Enum(data) = &input.data else {
+        return Err(Error::new(
+            input.span(),
+            "Packets can only be derived for enums",
+        ));
+    };
+    let name = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+
+    let mut arms = Vec::new();
+    let mut ids = Vec::new();
+    for variant in &data.variants {
+        let Fields::Unnamed(fields) = &variant.fields else {
+            return Err(Error::new(
+                variant.span(),
+                "each variant must wrap a single packet",
+            ));
+        };
+        if fields.unnamed.len() != 1 {
+            return Err(Error::new(
+                variant.span(),
+                "each variant must wrap a single packet",
+            ));
+        }
+        let ident = &variant.ident;
+        let ty = &fields.unnamed[0].ty;
+        let const_name = format_ident!("{}_ID", ident.to_string().to_uppercase());
+        ids.push(quote! {
+            const #const_name: (::mcproto::State, i32) =
+                (<#ty as ::mcproto::Packet>::STATE, <#ty as ::mcproto::Packet>::ID);
+        });
+        arms.push((ident, ty, const_name));
+    }
+    let decode_arms = arms.iter().map(|(ident, ty, const_name)| {
+        quote! {
+            #const_name => Ok(Some(Self::#ident(<#ty as ::mcproto::McRead>::read_from(reader)?))),
+        }
+    });
+    let name_arms = arms.iter().map(|(ident, ty, _)| {
+        quote! {
+            if state == <#ty as ::mcproto::Packet>::STATE
+                && <#ty as ::mcproto::Packet>::NAME.is_some()
+                && name(<#ty as ::mcproto::Packet>::DIRECTION) == <#ty as ::mcproto::Packet>::NAME
+            {
+                return Ok(Some(Self::#ident(<#ty as ::mcproto::McRead>::read_from(reader)?)));
+            }
+        }
+    });
+    let fallback_arms = arms.iter().map(|(ident, ty, const_name)| {
+        quote! {
+            #const_name
+                if <#ty as ::mcproto::Packet>::NAME.is_none()
+                    || name(<#ty as ::mcproto::Packet>::DIRECTION).is_none() =>
+            {
+                Ok(Some(Self::#ident(<#ty as ::mcproto::McRead>::read_from(reader)?)))
+            }
+        }
+    });
+    let id_arms = arms.iter().map(|(ident, ty, _)| {
+        quote! { Self::#ident(_) => <#ty as ::mcproto::Packet>::ID, }
+    });
+
+    Ok(quote! {
+        impl #impl_generics #name #ty_generics #where_clause {
+            /// Decodes the packet body with the given id, or returns `None` for unknown packets
+            pub fn decode<R: ::std::io::Read>(
+                state: ::mcproto::State,
+                id: i32,
+                reader: &mut R,
+            ) -> Result<Option<Self>, ::mcproto::ProtocolError> {
+                #(#ids)*
+                match (state, id) {
+                    #(#decode_arms)*
+                    _ => Ok(None),
+                }
+            }
+
+            /// Like `decode`, but looks named packets up in the packet id tables for `protocol`
+            pub fn decode_versioned<R: ::std::io::Read>(
+                protocol: i32,
+                state: ::mcproto::State,
+                id: i32,
+                reader: &mut R,
+            ) -> Result<Option<Self>, ::mcproto::ProtocolError> {
+                // Each packet is looked up in the tables of its own direction
+                let registry = ::mcproto::registry::PacketRegistry::builtin();
+                let name = |direction: ::mcproto::Direction| {
+                    registry.packet_name(protocol, state, direction, id)
+                };
+                #(#ids)*
+                #(#name_arms)*
+                // Packets without a name keep their declared id, as do all packets
+                // of versions the tables don't cover
+                match (state, id) {
+                    #(#fallback_arms)*
+                    _ => Ok(None),
+                }
+            }
+
+            pub fn id(&self) -> i32 {
+                match self {
+                    #(#id_arms)*
+                }
+            }
+        }
+    })
+}
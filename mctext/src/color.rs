@@ -0,0 +1,216 @@
+/// The 16 colors that have a '§' formatting code
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum NamedColor {
+    Black,
+    DarkBlue,
+    DarkGreen,
+    DarkAqua,
+    DarkRed,
+    DarkPurple,
+    Gold,
+    Gray,
+    DarkGray,
+    Blue,
+    Green,
+    Aqua,
+    Red,
+    LightPurple,
+    Yellow,
+    White,
+}
+
+impl NamedColor {
+    pub const ALL: [NamedColor; 16] = [
+        Self::Black,
+        Self::DarkBlue,
+        Self::DarkGreen,
+        Self::DarkAqua,
+        Self::DarkRed,
+        Self::DarkPurple,
+        Self::Gold,
+        Self::Gray,
+        Self::DarkGray,
+        Self::Blue,
+        Self::Green,
+        Self::Aqua,
+        Self::Red,
+        Self::LightPurple,
+        Self::Yellow,
+        Self::White,
+    ];
+
+    /// The name used in JSON text components, like `dark_green`
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Black => "black",
+            Self::DarkBlue => "dark_blue",
+            Self::DarkGreen => "dark_green",
+            Self::DarkAqua => "dark_aqua",
+            Self::DarkRed => "dark_red",
+            Self::DarkPurple => "dark_purple",
+            Self::Gold => "gold",
+            Self::Gray => "gray",
+            Self::DarkGray => "dark_gray",
+            Self::Blue => "blue",
+            Self::Green => "green",
+            Self::Aqua => "aqua",
+            Self::Red => "red",
+            Self::LightPurple => "light_purple",
+            Self::Yellow => "yellow",
+            Self::White => "white",
+        }
+    }
+
+    /// The character following '§' for this color
+    pub fn code(self) -> char {
+        std::char::from_digit(self as u32, 16).unwrap()
+    }
+
+    pub fn rgb(self) -> u32 {
+        match self {
+            Self::Black => 0x000000,
+            Self::DarkBlue => 0x0000aa,
+            Self::DarkGreen => 0x00aa00,
+            Self::DarkAqua => 0x00aaaa,
+            Self::DarkRed => 0xaa0000,
+            Self::DarkPurple => 0xaa00aa,
+            Self::Gold => 0xffaa00,
+            Self::Gray => 0xaaaaaa,
+            Self::DarkGray => 0x555555,
+            Self::Blue => 0x5555ff,
+            Self::Green => 0x55ff55,
+            Self::Aqua => 0x55ffff,
+            Self::Red => 0xff5555,
+            Self::LightPurple => 0xff55ff,
+            Self::Yellow => 0xffff55,
+            Self::White => 0xffffff,
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|c| c.name() == name)
+    }
+
+    pub fn from_code(code: char) -> Option<Self> {
+        let idx = code.to_ascii_lowercase().to_digit(16)?;
+        Some(Self::ALL[idx as usize])
+    }
+
+    /// The named color closest to an RGB color
+    pub fn nearest(rgb: u32) -> Self {
+        let channels = |c: u32| {
+            [
+                (c >> 16) as i32 & 0xff,
+                (c >> 8) as i32 & 0xff,
+                c as i32 & 0xff,
+            ]
+        };
+        let target = channels(rgb);
+        let distance = |c: &NamedColor| {
+            let other = channels(c.rgb());
+            (0..3).map(|i| (target[i] - other[i]).pow(2)).sum::<i32>()
+        };
+        Self::ALL.into_iter().min_by_key(distance).unwrap()
+    }
+}
+
+/// The color of a text component: a named color, or `#RRGGBB` for 1.16+ clients
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Color {
+    Named(NamedColor),
+    Hex(u32),
+}
+
+impl Color {
+    /// Parses a color name like `dark_green`, or a hex color like `#00AA00`
+    pub fn parse(s: &str) -> Option<Self> {
+        if let Some(hex) = s.strip_prefix('#') {
+            if hex.len() != 6 || !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+                return None;
+            }
+            return u32::from_str_radix(hex, 16).ok().map(Color::Hex);
+        }
+        NamedColor::from_name(s).map(Color::Named)
+    }
+
+    pub fn rgb(self) -> u32 {
+        match self {
+            Self::Named(c) => c.rgb(),
+            Self::Hex(rgb) => rgb,
+        }
+    }
+
+    /// The color for clients that only have named colors
+    pub fn to_named(self) -> NamedColor {
+        match self {
+            Self::Named(c) => c,
+            Self::Hex(rgb) => NamedColor::nearest(rgb),
+        }
+    }
+
+    /// Mixes two colors, `t` going from 0 (`self`) to 1 (`other`)
+    pub fn lerp(self, other: Color, t: f32) -> Color {
+        let (a, b) = (self.rgb(), other.rgb());
+        let mut rgb = 0;
+        for shift in [16, 8, 0] {
+            let (x, y) = (((a >> shift) & 0xff) as f32, ((b >> shift) & 0xff) as f32);
+            rgb |= ((x + (y - x) * t).round() as u32) << shift;
+        }
+        Color::Hex(rgb)
+    }
+
+    /// The color of the `i`th of `len` characters in a gradient going through at least 2 `colors`
+    pub fn gradient(colors: &[Color], i: usize, len: usize) -> Color {
+        let steps = (len.max(2) - 1) as f32;
+        let t = i as f32 / steps * (colors.len() - 1) as f32;
+        let segment = (t.floor() as usize).min(colors.len() - 2);
+        colors[segment].lerp(colors[segment + 1], t - segment as f32)
+    }
+}
+
+impl std::fmt::Display for Color {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Named(c) => write!(f, "{}", c.name()),
+            Self::Hex(rgb) => write!(f, "#{:06X}", rgb),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_color() {
+        assert_eq!(Color::parse("gold"), Some(Color::Named(NamedColor::Gold)));
+        assert_eq!(Color::parse("#ff00AA"), Some(Color::Hex(0xff00aa)));
+        assert_eq!(Color::Hex(0xff00aa).to_string(), "#FF00AA");
+        assert_eq!(Color::parse("#ff00a"), None);
+        assert_eq!(Color::parse("#+f00aa"), None);
+        assert_eq!(Color::parse("orange"), None);
+    }
+
+    #[test]
+    fn test_nearest() {
+        assert_eq!(NamedColor::nearest(0xfe5656), NamedColor::Red);
+        assert_eq!(NamedColor::nearest(0x101010), NamedColor::Black);
+        assert_eq!(Color::Hex(0x0000ff).to_named(), NamedColor::DarkBlue);
+        assert_eq!(Color::Hex(0x5050f0).to_named(), NamedColor::Blue);
+        assert_eq!(
+            Color::Hex(0xff0000).lerp(Color::Hex(0x0000ff), 0.5),
+            Color::Hex(0x800080)
+        );
+    }
+
+    #[test]
+    fn test_codes() {
+        for c in NamedColor::ALL {
+            assert_eq!(NamedColor::from_code(c.code()), Some(c));
+            assert_eq!(NamedColor::from_name(c.name()), Some(c));
+        }
+        assert_eq!(NamedColor::LightPurple.code(), 'd');
+        assert_eq!(NamedColor::from_code('A'), Some(NamedColor::Green));
+        assert_eq!(NamedColor::from_code('l'), None);
+    }
+}
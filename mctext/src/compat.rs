@@ -0,0 +1,124 @@
+use std::collections::HashMap;
+
+use serde::Deserialize;
+use serde_json::{Map, Value};
+
+/// The first protocol with `object` text components (1.21.9)
+pub const OBJECT_PROTOCOL: i32 = 773;
+
+/// The first snapshot protocol with `object` text components (25w32a)
+pub const OBJECT_SNAPSHOT: i32 = 0x4000_0103;
+
+/// Snapshots and pre-releases set bit 30, see `mcproto::ProtocolVersion`
+const SNAPSHOT_BIT: i32 = 0x4000_0000;
+
+/// Keys only used by `object` components
+const OBJECT_KEYS: [&str; 6] = ["object", "atlas", "sprite", "player", "hat", "type"];
+
+/// What `object` components are replaced with for clients that can't show them
+#[derive(Deserialize, Debug, Clone, Default, PartialEq)]
+#[serde(default)]
+pub struct ObjectFallback {
+    /// The text for objects without an entry in `sprites`, or `None` to drop them
+    pub text: Option<String>,
+    /// The text for each sprite, like `"item/diamond" = "♦"`
+    pub sprites: HashMap<String, String>,
+}
+
+impl ObjectFallback {
+    fn replacement(&self, obj: &Map<String, Value>) -> &str {
+        obj.get("sprite")
+            .and_then(Value::as_str)
+            .and_then(|sprite| self.sprites.get(sprite))
+            .or(self.text.as_ref())
+            .map(String::as_str)
+            .unwrap_or("")
+    }
+}
+
+pub fn supports_objects(protocol: i32) -> bool {
+    if protocol > 0 && protocol & SNAPSHOT_BIT != 0 {
+        protocol >= OBJECT_SNAPSHOT
+    } else {
+        protocol >= OBJECT_PROTOCOL
+    }
+}
+
+fn is_object(obj: &Map<String, Value>) -> bool {
+    obj.get("type").and_then(Value::as_str) == Some("object")
+        || obj.get("object").is_some_and(Value::is_string)
+}
+
+/// Replaces every `object` component with a text component keeping its style and children
+pub fn replace_objects(value: &mut Value, fallback: &ObjectFallback) {
+    match value {
+        Value::Array(values) => {
+            for v in values {
+                replace_objects(v, fallback);
+            }
+        }
+        Value::Object(obj) => {
+            if is_object(obj) {
+                let text = fallback.replacement(obj).to_string();
+                obj.retain(|k, _| !OBJECT_KEYS.contains(&k.as_str()));
+                obj.insert("text".to_string(), Value::String(text));
+            }
+            // Children, translation arguments and hover text can have objects too
+            for v in obj.values_mut() {
+                replace_objects(v, fallback);
+            }
+        }
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn test_drop_objects() {
+        let mut motd = json!([{"object": "atlas", "sprite": "item/nether_star"}, {"text": "status server", "bold": true}]);
+        replace_objects(&mut motd, &ObjectFallback::default());
+        assert_eq!(
+            motd,
+            json!([{"text": ""}, {"text": "status server", "bold": true}])
+        );
+    }
+
+    #[test]
+    fn test_fallback_text() {
+        let fallback = ObjectFallback {
+            text: Some("*".to_string()),
+            sprites: HashMap::from([("item/diamond".to_string(), "♦".to_string())]),
+        };
+        let mut kick = json!({"text": "", "extra": [
+            {"type": "object", "object": "atlas", "sprite": "item/diamond", "color": "aqua"},
+            {"object": "player", "player": {"name": "jeb_"}},
+            {"translate": "a", "with": [{"object": "atlas", "sprite": "item/stick"}]},
+        ]});
+        replace_objects(&mut kick, &fallback);
+        assert_eq!(
+            kick,
+            json!({"text": "", "extra": [
+                {"text": "♦", "color": "aqua"},
+                {"text": "*"},
+                {"translate": "a", "with": [{"text": "*"}]},
+            ]})
+        );
+    }
+
+    #[test]
+    fn test_supports_objects() {
+        assert!(supports_objects(OBJECT_PROTOCOL));
+        assert!(supports_objects(0x4000_0105));
+        assert!(supports_objects(OBJECT_SNAPSHOT));
+        // 1.21.9 was preceded by snapshots that don't know objects yet
+        assert!(!supports_objects(0x4000_0102));
+        assert!(!supports_objects(0x4000_0001));
+        assert!(!supports_objects(772));
+        assert!(!supports_objects(0));
+    }
+}
@@ -0,0 +1,1162 @@
+use std::fmt;
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer, de};
+use serde_json::{Map, Value, json};
+
+use crate::{color::Color, legacy, minimessage};
+
+/// The first protocol with snake_case `click_event` and `hover_event` (1.21.5)
+pub const SNAKE_CASE_EVENTS_PROTOCOL: i32 = 770;
+
+/// The first protocol with hex colors (1.16)
+pub const HEX_COLOR_PROTOCOL: i32 = 735;
+
+/// The newest protocol, used when the client's protocol isn't known
+const LATEST_PROTOCOL: i32 = i32::MAX;
+
+#[derive(Debug)]
+pub enum TextError {
+    /// The text looked like JSON, but couldn't be parsed
+    Syntax(serde_json::Error),
+    /// The JSON isn't a valid text component; `path` is like `$.extra[1].color`
+    Invalid { path: String, message: String },
+}
+
+impl fmt::Display for TextError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Syntax(e) => write!(f, "invalid JSON text component: {}", e),
+            Self::Invalid { path, message } => write!(f, "{} at {}", message, path),
+        }
+    }
+}
+
+impl std::error::Error for TextError {}
+
+impl From<serde_json::Error> for TextError {
+    fn from(value: serde_json::Error) -> Self {
+        TextError::Syntax(value)
+    }
+}
+
+fn invalid(path: &str, message: impl Into<String>) -> TextError {
+    TextError::Invalid {
+        path: path.to_string(),
+        message: message.into(),
+    }
+}
+
+fn kind(value: &Value) -> &'static str {
+    match value {
+        Value::Null => "null",
+        Value::Bool(_) => "a boolean",
+        Value::Number(_) => "a number",
+        Value::String(_) => "a string",
+        Value::Array(_) => "a list",
+        Value::Object(_) => "an object",
+    }
+}
+
+/// How strings that aren't JSON are read
+#[derive(Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum TextFormat {
+    /// '§'-formatted text, as in [`legacy::parse`]
+    #[default]
+    Legacy,
+    /// As in [`minimessage::parse`]
+    MiniMessage,
+}
+
+/// A text component, as in https://minecraft.wiki/w/Text_component_format
+#[derive(Debug, Clone, PartialEq)]
+pub enum TextComponent {
+    /// A plain string, which can still have '§' codes
+    Text(String),
+    /// The first component followed by the others, which inherit its style
+    List(Vec<TextComponent>),
+    Component(Box<Component>),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Component {
+    pub content: Content,
+    pub style: Style,
+    pub extra: Vec<TextComponent>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Content {
+    Text(String),
+    Translatable {
+        key: String,
+        fallback: Option<String>,
+        with: Vec<TextComponent>,
+    },
+    Score {
+        name: String,
+        objective: String,
+    },
+    Selector {
+        selector: String,
+        separator: Option<TextComponent>,
+    },
+    Keybind(String),
+    Nbt {
+        path: String,
+        source: NbtSource,
+        interpret: Option<bool>,
+        separator: Option<TextComponent>,
+    },
+    /// Sprites and player heads, shown by 1.21.9+ clients
+    Object(ObjectContent),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum NbtSource {
+    Block(String),
+    Entity(String),
+    Storage(String),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ObjectContent {
+    Atlas {
+        atlas: Option<String>,
+        sprite: String,
+    },
+    /// `player` is either a name or a profile object
+    Player { player: Value, hat: Option<bool> },
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Style {
+    pub color: Option<Color>,
+    pub font: Option<String>,
+    pub bold: Option<bool>,
+    pub italic: Option<bool>,
+    pub underlined: Option<bool>,
+    pub strikethrough: Option<bool>,
+    pub obfuscated: Option<bool>,
+    /// ARGB
+    pub shadow_color: Option<u32>,
+    pub insertion: Option<String>,
+    pub click_event: Option<ClickEvent>,
+    pub hover_event: Option<HoverEvent>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ClickAction {
+    OpenUrl,
+    OpenFile,
+    RunCommand,
+    SuggestCommand,
+    ChangePage,
+    CopyToClipboard,
+}
+
+impl ClickAction {
+    const ALL: [ClickAction; 6] = [
+        Self::OpenUrl,
+        Self::OpenFile,
+        Self::RunCommand,
+        Self::SuggestCommand,
+        Self::ChangePage,
+        Self::CopyToClipboard,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::OpenUrl => "open_url",
+            Self::OpenFile => "open_file",
+            Self::RunCommand => "run_command",
+            Self::SuggestCommand => "suggest_command",
+            Self::ChangePage => "change_page",
+            Self::CopyToClipboard => "copy_to_clipboard",
+        }
+    }
+
+    /// The key of the action's value in 1.21.5+ click events
+    fn value_key(self) -> &'static str {
+        match self {
+            Self::OpenUrl => "url",
+            Self::OpenFile => "path",
+            Self::RunCommand | Self::SuggestCommand => "command",
+            Self::ChangePage => "page",
+            Self::CopyToClipboard => "value",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|a| a.name() == name)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ClickEvent {
+    pub action: ClickAction,
+    /// The url, path, command, page number or text to copy
+    pub value: String,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum HoverEvent {
+    ShowText(TextComponent),
+    ShowItem {
+        id: String,
+        count: Option<i64>,
+        components: Option<Value>,
+    },
+    ShowEntity {
+        entity_type: String,
+        uuid: String,
+        name: Option<TextComponent>,
+    },
+}
+
+/// The fields of a JSON object, remembering which ones were read to report the rest
+struct Fields<'a> {
+    map: &'a Map<String, Value>,
+    path: &'a str,
+    used: Vec<&'static str>,
+}
+
+impl<'a> Fields<'a> {
+    fn new(map: &'a Map<String, Value>, path: &'a str) -> Self {
+        Fields {
+            map,
+            path,
+            used: Vec::new(),
+        }
+    }
+
+    fn path(&self, key: &str) -> String {
+        format!("{}.{}", self.path, key)
+    }
+
+    fn has(&self, key: &str) -> bool {
+        self.map.contains_key(key)
+    }
+
+    fn get(&mut self, key: &'static str) -> Option<&'a Value> {
+        self.used.push(key);
+        self.map.get(key)
+    }
+
+    fn string(&mut self, key: &'static str) -> Result<Option<String>, TextError> {
+        match self.get(key) {
+            None => Ok(None),
+            Some(Value::String(s)) => Ok(Some(s.clone())),
+            Some(v) => Err(invalid(
+                &self.path(key),
+                format!("expected a string, found {}", kind(v)),
+            )),
+        }
+    }
+
+    fn required_string(&mut self, key: &'static str) -> Result<String, TextError> {
+        self.string(key)?
+            .ok_or_else(|| invalid(self.path, format!("missing field `{}`", key)))
+    }
+
+    fn bool(&mut self, key: &'static str) -> Result<Option<bool>, TextError> {
+        match self.get(key) {
+            None => Ok(None),
+            Some(Value::Bool(b)) => Ok(Some(*b)),
+            Some(v) => Err(invalid(
+                &self.path(key),
+                format!("expected a boolean, found {}", kind(v)),
+            )),
+        }
+    }
+
+    fn int(&mut self, key: &'static str) -> Result<Option<i64>, TextError> {
+        match self.get(key) {
+            None => Ok(None),
+            Some(Value::Number(n)) if n.is_i64() => Ok(n.as_i64()),
+            Some(v) => Err(invalid(
+                &self.path(key),
+                format!("expected an integer, found {}", kind(v)),
+            )),
+        }
+    }
+
+    fn object(&mut self, key: &'static str) -> Result<Option<&'a Map<String, Value>>, TextError> {
+        match self.get(key) {
+            None => Ok(None),
+            Some(Value::Object(m)) => Ok(Some(m)),
+            Some(v) => Err(invalid(
+                &self.path(key),
+                format!("expected an object, found {}", kind(v)),
+            )),
+        }
+    }
+
+    fn component(&mut self, key: &'static str) -> Result<Option<TextComponent>, TextError> {
+        match self.get(key) {
+            None => Ok(None),
+            Some(v) => parse_value(v, &self.path(key)).map(Some),
+        }
+    }
+
+    fn components(&mut self, key: &'static str) -> Result<Vec<TextComponent>, TextError> {
+        match self.get(key) {
+            None => Ok(Vec::new()),
+            Some(Value::Array(values)) => {
+                let path = self.path(key);
+                values
+                    .iter()
+                    .enumerate()
+                    .map(|(i, v)| parse_value(v, &format!("{}[{}]", path, i)))
+                    .collect()
+            }
+            Some(v) => Err(invalid(
+                &self.path(key),
+                format!("expected a list, found {}", kind(v)),
+            )),
+        }
+    }
+
+    /// Fails on the first field that wasn't read, which is most likely a typo
+    fn finish(self) -> Result<(), TextError> {
+        match self.map.keys().find(|k| !self.used.contains(&k.as_str())) {
+            Some(k) => Err(invalid(&self.path(k), format!("unknown field `{}`", k))),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Content types and the field that marks them when `type` isn't set, in the order the client checks them
+const CONTENT_TYPES: [(&str, &str); 7] = [
+    ("text", "text"),
+    ("translatable", "translate"),
+    ("score", "score"),
+    ("selector", "selector"),
+    ("keybind", "keybind"),
+    ("nbt", "nbt"),
+    ("object", "object"),
+];
+
+fn parse_content(fields: &mut Fields) -> Result<Content, TextError> {
+    let content_type = match fields.string("type")? {
+        Some(t) => match CONTENT_TYPES.iter().find(|(name, _)| *name == t) {
+            Some((name, _)) => *name,
+            None => {
+                return Err(invalid(
+                    &fields.path("type"),
+                    format!("unknown component type `{}`", t),
+                ));
+            }
+        },
+        None => match CONTENT_TYPES.iter().find(|(_, key)| fields.has(key)) {
+            Some((name, _)) => *name,
+            // `object` can be left out for sprites
+            None if fields.has("sprite") => "object",
+            None => {
+                return Err(invalid(
+                    fields.path,
+                    "missing content, expected one of `text`, `translate`, `score`, `selector`, `keybind`, `nbt` or `object`",
+                ));
+            }
+        },
+    };
+    Ok(match content_type {
+        "text" => Content::Text(fields.required_string("text")?),
+        "translatable" => Content::Translatable {
+            key: fields.required_string("translate")?,
+            fallback: fields.string("fallback")?,
+            with: fields.components("with")?,
+        },
+        "score" => {
+            let path = fields.path("score");
+            let Some(score) = fields.object("score")? else {
+                return Err(invalid(fields.path, "missing field `score`"));
+            };
+            let mut score = Fields::new(score, &path);
+            let content = Content::Score {
+                name: score.required_string("name")?,
+                objective: score.required_string("objective")?,
+            };
+            score.finish()?;
+            content
+        }
+        "selector" => Content::Selector {
+            selector: fields.required_string("selector")?,
+            separator: fields.component("separator")?,
+        },
+        "keybind" => Content::Keybind(fields.required_string("keybind")?),
+        "nbt" => {
+            let path = fields.required_string("nbt")?;
+            let sources = [
+                fields.string("block")?.map(NbtSource::Block),
+                fields.string("entity")?.map(NbtSource::Entity),
+                fields.string("storage")?.map(NbtSource::Storage),
+            ];
+            let mut sources = sources.into_iter().flatten();
+            let (Some(source), None) = (sources.next(), sources.next()) else {
+                return Err(invalid(
+                    fields.path,
+                    "expected exactly one of `block`, `entity` or `storage`",
+                ));
+            };
+            Content::Nbt {
+                path,
+                source,
+                interpret: fields.bool("interpret")?,
+                separator: fields.component("separator")?,
+            }
+        }
+        _ => Content::Object(parse_object_content(fields)?),
+    })
+}
+
+fn parse_object_content(fields: &mut Fields) -> Result<ObjectContent, TextError> {
+    match fields.string("object")?.as_deref() {
+        Some("atlas") | None => Ok(ObjectContent::Atlas {
+            atlas: fields.string("atlas")?,
+            sprite: fields.required_string("sprite")?,
+        }),
+        Some("player") => {
+            let player = match fields.get("player") {
+                Some(v @ (Value::String(_) | Value::Object(_))) => v.clone(),
+                Some(v) => {
+                    return Err(invalid(
+                        &fields.path("player"),
+                        format!("expected a name or a profile, found {}", kind(v)),
+                    ));
+                }
+                None => return Err(invalid(fields.path, "missing field `player`")),
+            };
+            Ok(ObjectContent::Player {
+                player,
+                hat: fields.bool("hat")?,
+            })
+        }
+        Some(o) => Err(invalid(
+            &fields.path("object"),
+            format!("unknown object type `{}`, expected `atlas` or `player`", o),
+        )),
+    }
+}
+
+fn parse_shadow_color(fields: &mut Fields) -> Result<Option<u32>, TextError> {
+    let path = fields.path("shadow_color");
+    match fields.get("shadow_color") {
+        None => Ok(None),
+        Some(Value::Number(n)) if n.is_i64() => Ok(n.as_i64().map(|n| n as u32)),
+        Some(Value::Array(values)) if values.len() == 4 => {
+            let mut argb = 0u32;
+            // The list is RGBA, from 0 to 1
+            for (i, v) in values.iter().enumerate() {
+                let Some(f) = v.as_f64() else {
+                    return Err(invalid(&format!("{}[{}]", path, i), "expected a number"));
+                };
+                argb = argb << 8 | (f.clamp(0.0, 1.0) * 255.0).round() as u32;
+            }
+            Ok(Some(argb.rotate_right(8)))
+        }
+        Some(_) => Err(invalid(
+            &path,
+            "expected an ARGB integer or a list of 4 numbers",
+        )),
+    }
+}
+
+fn parse_click_event(map: &Map<String, Value>, path: &str) -> Result<ClickEvent, TextError> {
+    let mut fields = Fields::new(map, path);
+    let action = fields.required_string("action")?;
+    let Some(action) = ClickAction::from_name(&action) else {
+        return Err(invalid(
+            &fields.path("action"),
+            format!("unknown click action `{}`", action),
+        ));
+    };
+    // Before 1.21.5 every action used `value`
+    let key = if fields.has("value") {
+        "value"
+    } else {
+        action.value_key()
+    };
+    let value = match fields.get(key) {
+        Some(Value::String(s)) => s.clone(),
+        Some(Value::Number(n)) if action == ClickAction::ChangePage && n.is_i64() => n.to_string(),
+        Some(v) => {
+            return Err(invalid(
+                &fields.path(key),
+                format!("expected a string, found {}", kind(v)),
+            ));
+        }
+        None => return Err(invalid(path, format!("missing field `{}`", key))),
+    };
+    if action == ClickAction::ChangePage && value.parse::<i32>().is_err() {
+        return Err(invalid(&fields.path(key), "expected a page number"));
+    }
+    fields.finish()?;
+    Ok(ClickEvent { action, value })
+}
+
+fn parse_hover_event(map: &Map<String, Value>, path: &str) -> Result<HoverEvent, TextError> {
+    let mut fields = Fields::new(map, path);
+    let action = fields.required_string("action")?;
+    // Before 1.21.5 everything but the action was in `contents`
+    let contents_path = fields.path("contents");
+    let mut contents = match map.get("contents") {
+        Some(Value::Object(m)) if action != "show_text" => {
+            fields.used.push("contents");
+            Some(Fields::new(m, &contents_path))
+        }
+        _ => None,
+    };
+    let old_format = contents.is_some();
+    let inner = contents.as_mut().unwrap_or(&mut fields);
+    let event = match action.as_str() {
+        "show_text" => {
+            // Even older versions used `value` instead of `contents`
+            let key = if inner.has("contents") {
+                "contents"
+            } else {
+                "value"
+            };
+            match inner.component(key)? {
+                Some(text) => HoverEvent::ShowText(text),
+                None => return Err(invalid(path, "missing field `value`")),
+            }
+        }
+        // The contents can also be just the item id
+        "show_item" if matches!(map.get("contents"), Some(Value::String(_))) => {
+            HoverEvent::ShowItem {
+                id: inner.required_string("contents")?,
+                count: None,
+                components: None,
+            }
+        }
+        "show_item" => HoverEvent::ShowItem {
+            id: inner.required_string("id")?,
+            count: inner.int("count")?,
+            components: inner.get("components").cloned(),
+        },
+        "show_entity" if old_format => HoverEvent::ShowEntity {
+            entity_type: inner.required_string("type")?,
+            uuid: inner.required_string("id")?,
+            name: inner.component("name")?,
+        },
+        "show_entity" => HoverEvent::ShowEntity {
+            entity_type: inner.required_string("id")?,
+            uuid: inner.required_string("uuid")?,
+            name: inner.component("name")?,
+        },
+        a => {
+            return Err(invalid(
+                &fields.path("action"),
+                format!("unknown hover action `{}`", a),
+            ));
+        }
+    };
+    if let Some(contents) = contents {
+        contents.finish()?;
+    }
+    fields.finish()?;
+    Ok(event)
+}
+
+fn parse_style(fields: &mut Fields) -> Result<Style, TextError> {
+    let color = match fields.string("color")? {
+        Some(c) => match Color::parse(&c) {
+            Some(c) => Some(c),
+            None => {
+                return Err(invalid(
+                    &fields.path("color"),
+                    format!("unknown color `{}`, expected a color name or #RRGGBB", c),
+                ));
+            }
+        },
+        None => None,
+    };
+    let mut event = |new: &'static str, old: &'static str| -> Result<_, TextError> {
+        let key = if fields.has(new) { new } else { old };
+        let path = fields.path(key);
+        Ok(fields.object(key)?.map(|m| (m, path)))
+    };
+    let click_event = event("click_event", "clickEvent")?;
+    let hover_event = event("hover_event", "hoverEvent")?;
+    Ok(Style {
+        color,
+        font: fields.string("font")?,
+        bold: fields.bool("bold")?,
+        italic: fields.bool("italic")?,
+        underlined: fields.bool("underlined")?,
+        strikethrough: fields.bool("strikethrough")?,
+        obfuscated: fields.bool("obfuscated")?,
+        shadow_color: parse_shadow_color(fields)?,
+        insertion: fields.string("insertion")?,
+        click_event: match click_event {
+            Some((m, path)) => Some(parse_click_event(m, &path)?),
+            None => None,
+        },
+        hover_event: match hover_event {
+            Some((m, path)) => Some(parse_hover_event(m, &path)?),
+            None => None,
+        },
+    })
+}
+
+fn parse_value(value: &Value, path: &str) -> Result<TextComponent, TextError> {
+    match value {
+        Value::String(s) => Ok(TextComponent::Text(s.clone())),
+        Value::Bool(_) | Value::Number(_) => Ok(TextComponent::Text(value.to_string())),
+        Value::Array(values) if values.is_empty() => Err(invalid(path, "empty list of components")),
+        Value::Array(values) => values
+            .iter()
+            .enumerate()
+            .map(|(i, v)| parse_value(v, &format!("{}[{}]", path, i)))
+            .collect::<Result<_, _>>()
+            .map(TextComponent::List),
+        Value::Object(map) => {
+            let mut fields = Fields::new(map, path);
+            let content = parse_content(&mut fields)?;
+            let style = parse_style(&mut fields)?;
+            let extra = fields.components("extra")?;
+            fields.finish()?;
+            Ok(TextComponent::Component(Box::new(Component {
+                content,
+                style,
+                extra,
+            })))
+        }
+        Value::Null => Err(invalid(path, "expected a text component, found null")),
+    }
+}
+
+impl ClickEvent {
+    fn to_json(&self, protocol: i32) -> Value {
+        let mut map = Map::new();
+        map.insert("action".into(), self.action.name().into());
+        if protocol < SNAKE_CASE_EVENTS_PROTOCOL {
+            map.insert("value".into(), self.value.clone().into());
+        } else if self.action == ClickAction::ChangePage {
+            let page: i32 = self.value.parse().unwrap_or(1);
+            map.insert("page".into(), page.into());
+        } else {
+            map.insert(self.action.value_key().into(), self.value.clone().into());
+        }
+        Value::Object(map)
+    }
+}
+
+impl HoverEvent {
+    fn to_json(&self, protocol: i32) -> Value {
+        let snake_case = protocol >= SNAKE_CASE_EVENTS_PROTOCOL;
+        let mut map = Map::new();
+        let mut contents = Map::new();
+        let action = match self {
+            Self::ShowText(text) => {
+                let key = if snake_case { "value" } else { "contents" };
+                map.insert(key.into(), text.to_json(protocol));
+                "show_text"
+            }
+            Self::ShowItem {
+                id,
+                count,
+                components,
+            } => {
+                contents.insert("id".into(), id.clone().into());
+                if let Some(count) = count {
+                    contents.insert("count".into(), (*count).into());
+                }
+                if let Some(components) = components {
+                    contents.insert("components".into(), components.clone());
+                }
+                "show_item"
+            }
+            Self::ShowEntity {
+                entity_type,
+                uuid,
+                name,
+            } => {
+                if snake_case {
+                    contents.insert("id".into(), entity_type.clone().into());
+                    contents.insert("uuid".into(), uuid.clone().into());
+                } else {
+                    contents.insert("type".into(), entity_type.clone().into());
+                    contents.insert("id".into(), uuid.clone().into());
+                }
+                if let Some(name) = name {
+                    contents.insert("name".into(), name.to_json(protocol));
+                }
+                "show_entity"
+            }
+        };
+        map.insert("action".into(), action.into());
+        if snake_case {
+            map.extend(contents);
+        } else if !contents.is_empty() {
+            map.insert("contents".into(), Value::Object(contents));
+        }
+        Value::Object(map)
+    }
+}
+
+impl Content {
+    fn write_json(&self, map: &mut Map<String, Value>, protocol: i32) {
+        let mut insert = |key: &str, value: Value| {
+            map.insert(key.to_string(), value);
+        };
+        match self {
+            Self::Text(text) => insert("text", text.clone().into()),
+            Self::Translatable {
+                key,
+                fallback,
+                with,
+            } => {
+                insert("translate", key.clone().into());
+                if let Some(fallback) = fallback {
+                    insert("fallback", fallback.clone().into());
+                }
+                if !with.is_empty() {
+                    insert("with", with.iter().map(|c| c.to_json(protocol)).collect());
+                }
+            }
+            Self::Score { name, objective } => {
+                insert("score", json!({ "name": name, "objective": objective }));
+            }
+            Self::Selector {
+                selector,
+                separator,
+            } => {
+                insert("selector", selector.clone().into());
+                if let Some(separator) = separator {
+                    insert("separator", separator.to_json(protocol));
+                }
+            }
+            Self::Keybind(key) => insert("keybind", key.clone().into()),
+            Self::Nbt {
+                path,
+                source,
+                interpret,
+                separator,
+            } => {
+                insert("nbt", path.clone().into());
+                match source {
+                    NbtSource::Block(b) => insert("block", b.clone().into()),
+                    NbtSource::Entity(e) => insert("entity", e.clone().into()),
+                    NbtSource::Storage(s) => insert("storage", s.clone().into()),
+                }
+                if let Some(interpret) = interpret {
+                    insert("interpret", (*interpret).into());
+                }
+                if let Some(separator) = separator {
+                    insert("separator", separator.to_json(protocol));
+                }
+            }
+            Self::Object(ObjectContent::Atlas { atlas, sprite }) => {
+                insert("object", "atlas".into());
+                if let Some(atlas) = atlas {
+                    insert("atlas", atlas.clone().into());
+                }
+                insert("sprite", sprite.clone().into());
+            }
+            Self::Object(ObjectContent::Player { player, hat }) => {
+                insert("object", "player".into());
+                insert("player", player.clone());
+                if let Some(hat) = hat {
+                    insert("hat", (*hat).into());
+                }
+            }
+        }
+    }
+}
+
+impl Style {
+    fn write_json(&self, map: &mut Map<String, Value>, protocol: i32) {
+        if let Some(color) = self.color {
+            let color = match color {
+                Color::Hex(_) if protocol < HEX_COLOR_PROTOCOL => {
+                    color.to_named().name().to_string()
+                }
+                _ => color.to_string(),
+            };
+            map.insert("color".into(), color.into());
+        }
+        if let Some(font) = &self.font {
+            map.insert("font".into(), font.clone().into());
+        }
+        let flags = [
+            ("bold", self.bold),
+            ("italic", self.italic),
+            ("underlined", self.underlined),
+            ("strikethrough", self.strikethrough),
+            ("obfuscated", self.obfuscated),
+        ];
+        for (key, flag) in flags {
+            if let Some(flag) = flag {
+                map.insert(key.into(), flag.into());
+            }
+        }
+        if let Some(shadow) = self.shadow_color {
+            map.insert("shadow_color".into(), (shadow as i32).into());
+        }
+        if let Some(insertion) = &self.insertion {
+            map.insert("insertion".into(), insertion.clone().into());
+        }
+        let snake_case = protocol >= SNAKE_CASE_EVENTS_PROTOCOL;
+        if let Some(click) = &self.click_event {
+            let key = if snake_case {
+                "click_event"
+            } else {
+                "clickEvent"
+            };
+            map.insert(key.into(), click.to_json(protocol));
+        }
+        if let Some(hover) = &self.hover_event {
+            let key = if snake_case {
+                "hover_event"
+            } else {
+                "hoverEvent"
+            };
+            map.insert(key.into(), hover.to_json(protocol));
+        }
+    }
+}
+
+impl TextComponent {
+    /// Like [`TextComponent::parse_with`], with '§'-formatted text
+    pub fn parse(text: &str) -> Result<Self, TextError> {
+        Self::parse_with(text, TextFormat::Legacy)
+    }
+
+    /// Parses text from the config: MiniMessage if it starts with `mm:`, JSON if it's a JSON
+    /// value, and otherwise in `format`. Text like `[1.21] My Server` isn't JSON,
+    /// so it's read in `format` too, but JSON that isn't a valid component is an error
+    pub fn parse_with(text: &str, format: TextFormat) -> Result<Self, TextError> {
+        if let Some(text) = text.strip_prefix(minimessage::PREFIX) {
+            return Ok(minimessage::parse(text));
+        }
+        if text.trim_start().starts_with(['{', '[', '"'])
+            && let Ok(value) = serde_json::from_str::<Value>(text)
+        {
+            return Self::from_value(&value);
+        }
+        Ok(match format {
+            TextFormat::Legacy => legacy::parse(text),
+            TextFormat::MiniMessage => minimessage::parse(text),
+        })
+    }
+
+    pub fn from_value(value: &Value) -> Result<Self, TextError> {
+        parse_value(value, "$")
+    }
+
+    /// The text as a '§'-formatted string, with the closest named colors
+    pub fn to_legacy(&self) -> String {
+        match self {
+            Self::Text(text) => text.clone(),
+            c => legacy::to_legacy(&c.to_json(0)),
+        }
+    }
+
+    /// The JSON sent to a client using `protocol`
+    pub fn to_json(&self, protocol: i32) -> Value {
+        match self {
+            Self::Text(text) => Value::String(text.clone()),
+            Self::List(list) => list.iter().map(|c| c.to_json(protocol)).collect(),
+            Self::Component(c) => {
+                let mut map = Map::new();
+                c.content.write_json(&mut map, protocol);
+                c.style.write_json(&mut map, protocol);
+                if !c.extra.is_empty() {
+                    let extra = c.extra.iter().map(|c| c.to_json(protocol)).collect();
+                    map.insert("extra".into(), extra);
+                }
+                Value::Object(map)
+            }
+        }
+    }
+}
+
+impl Default for TextComponent {
+    fn default() -> Self {
+        TextComponent::Text(String::new())
+    }
+}
+
+impl From<&str> for TextComponent {
+    fn from(value: &str) -> Self {
+        TextComponent::Text(value.to_string())
+    }
+}
+
+impl From<String> for TextComponent {
+    fn from(value: String) -> Self {
+        TextComponent::Text(value)
+    }
+}
+
+impl fmt::Display for TextComponent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Text(text) => write!(f, "{}", text),
+            c => write!(f, "{}", c.to_json(LATEST_PROTOCOL)),
+        }
+    }
+}
+
+impl Serialize for TextComponent {
+    fn serialize<S: Serializer>(&self, s: S) -> Result<S::Ok, S::Error> {
+        self.to_json(LATEST_PROTOCOL).serialize(s)
+    }
+}
+
+/// Reads a string like [`TextComponent::parse`], or a component written out in the config itself.
+/// Text in another format is read with [`RawText`]
+impl<'de> Deserialize<'de> for TextComponent {
+    fn deserialize<D: Deserializer<'de>>(d: D) -> Result<Self, D::Error> {
+        RawText::deserialize(d)?
+            .parse(TextFormat::Legacy)
+            .map_err(de::Error::custom)
+    }
+}
+
+/// Text as it's written in a config, for when its format is only known once the rest is read
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+#[serde(transparent)]
+pub struct RawText(pub Value);
+
+impl RawText {
+    /// Reads a string with [`TextComponent::parse_with`], or a component written out in the config itself
+    pub fn parse(&self, format: TextFormat) -> Result<TextComponent, TextError> {
+        match &self.0 {
+            Value::String(s) => TextComponent::parse_with(s, format),
+            v => TextComponent::from_value(v),
+        }
+    }
+}
+
+impl Default for RawText {
+    fn default() -> Self {
+        RawText(Value::String(String::new()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::color::NamedColor;
+
+    use super::*;
+
+    fn error(text: &str) -> String {
+        TextComponent::parse(text).unwrap_err().to_string()
+    }
+
+    #[test]
+    fn test_plain() {
+        assert_eq!(
+            TextComponent::parse("§d§lstatus server").unwrap(),
+            TextComponent::Text("§d§lstatus server".into())
+        );
+        assert_eq!(
+            TextComponent::parse(r#""[quoted]""#).unwrap(),
+            TextComponent::Text("[quoted]".into())
+        );
+    }
+
+    #[test]
+    fn test_parse() {
+        let motd = TextComponent::parse(
+            r##"{"text": "a", "color": "#FF0000", "bold": true, "extra": [{"translate": "b", "with": ["c"]}, {"keybind": "key.jump"}]}"##,
+        )
+        .unwrap();
+        let TextComponent::Component(c) = &motd else {
+            panic!("{:?}", motd);
+        };
+        assert_eq!(c.content, Content::Text("a".into()));
+        assert_eq!(c.style.color, Some(Color::Hex(0xff0000)));
+        assert_eq!(c.style.bold, Some(true));
+        assert_eq!(
+            c.extra[0],
+            TextComponent::Component(Box::new(Component {
+                content: Content::Translatable {
+                    key: "b".into(),
+                    fallback: None,
+                    with: vec!["c".into()],
+                },
+                style: Style::default(),
+                extra: vec![],
+            }))
+        );
+        let objects = TextComponent::parse(
+            r#"[{"object": "atlas", "sprite": "item/diamond"}, {"sprite": "item/stick"}, {"type": "object", "object": "player", "player": "jeb_"}]"#,
+        )
+        .unwrap();
+        assert_eq!(
+            objects.to_json(773),
+            json!([{"object": "atlas", "sprite": "item/diamond"}, {"object": "atlas", "sprite": "item/stick"}, {"object": "player", "player": "jeb_"}])
+        );
+    }
+
+    #[test]
+    fn test_roundtrip() {
+        let text = json!({"text": "a", "color": "gold", "italic": false, "insertion": "x", "extra": [
+            {"score": {"name": "@s", "objective": "kills"}},
+            {"selector": "@a", "separator": ", "},
+            {"nbt": "Pos", "entity": "@s", "interpret": true},
+        ]});
+        let component = TextComponent::from_value(&text).unwrap();
+        assert_eq!(component.to_json(767), text);
+        assert_eq!(
+            TextComponent::from_value(&component.to_json(767)).unwrap(),
+            component
+        );
+    }
+
+    #[test]
+    fn test_events() {
+        let old = json!({"text": "a",
+            "clickEvent": {"action": "open_url", "value": "https://example.com"},
+            "hoverEvent": {"action": "show_text", "contents": {"text": "b", "color": "red"}}});
+        let new = json!({"text": "a",
+            "click_event": {"action": "open_url", "url": "https://example.com"},
+            "hover_event": {"action": "show_text", "value": {"text": "b", "color": "red"}}});
+        let component = TextComponent::from_value(&old).unwrap();
+        assert_eq!(component, TextComponent::from_value(&new).unwrap());
+        assert_eq!(component.to_json(767), old);
+        assert_eq!(component.to_json(770), new);
+
+        let old = json!({"text": "a", "hoverEvent": {"action": "show_entity",
+            "contents": {"type": "minecraft:pig", "id": "853c80ef-3c37-49fd-aa49-938b674adae6"}}});
+        let new = json!({"text": "a", "hover_event": {"action": "show_entity",
+            "id": "minecraft:pig", "uuid": "853c80ef-3c37-49fd-aa49-938b674adae6"}});
+        let component = TextComponent::from_value(&old).unwrap();
+        assert_eq!(component, TextComponent::from_value(&new).unwrap());
+        assert_eq!(component.to_json(767), old);
+        assert_eq!(component.to_json(770), new);
+
+        let page = json!({"text": "a", "click_event": {"action": "change_page", "page": 2}});
+        let component = TextComponent::from_value(&page).unwrap();
+        assert_eq!(component.to_json(770), page);
+        let item = json!({"text": "a", "hover_event": {"action": "show_item", "id": "minecraft:stick", "count": 2}});
+        let component = TextComponent::from_value(&item).unwrap();
+        assert_eq!(component.to_json(770), item);
+    }
+
+    #[test]
+    fn test_hex_downsampling() {
+        let text = TextComponent::parse(r##"{"text": "a", "color": "#FF5050"}"##).unwrap();
+        assert_eq!(text.to_json(735), json!({"text": "a", "color": "#FF5050"}));
+        assert_eq!(text.to_json(578), json!({"text": "a", "color": "red"}));
+    }
+
+    #[test]
+    fn test_shadow_color() {
+        let text = json!({"text": "a", "shadow_color": [1.0, 0.0, 0.0, 0.5]});
+        let TextComponent::Component(c) = TextComponent::from_value(&text).unwrap() else {
+            panic!();
+        };
+        assert_eq!(c.style.shadow_color, Some(0x80ff0000));
+    }
+
+    #[test]
+    fn test_not_json() {
+        for text in [
+            "[1.21] My Server",
+            "{Survival} fun",
+            r#""Quoted" server"#,
+            r#"{"text": "a""#,
+        ] {
+            assert_eq!(
+                TextComponent::parse(text).unwrap(),
+                TextComponent::Text(text.into())
+            );
+        }
+        let text = TextComponent::parse_with("[1.21] <red>My Server", TextFormat::MiniMessage);
+        assert_eq!(
+            text.unwrap().to_json(767),
+            json!(["", "[1.21] ", {"text": "My Server", "color": "red"}])
+        );
+    }
+
+    #[test]
+    fn test_errors() {
+        assert_eq!(
+            error(r#"{"text": "a", "extra": ["b", {"text": "c", "colour": "red"}]}"#),
+            "unknown field `colour` at $.extra[1].colour"
+        );
+        assert_eq!(
+            error(r#"[{"text": "a"}, {"text": "b", "color": "orange"}]"#),
+            "unknown color `orange`, expected a color name or #RRGGBB at $[1].color"
+        );
+        assert_eq!(
+            error(r#"{"bold": true}"#),
+            "missing content, expected one of `text`, `translate`, `score`, `selector`, `keybind`, `nbt` or `object` at $"
+        );
+        assert_eq!(
+            error(r#"{"text": "a", "bold": "yes"}"#),
+            "expected a boolean, found a string at $.bold"
+        );
+        assert_eq!(
+            error(r#"{"type": "translatable", "text": "a"}"#),
+            "missing field `translate` at $"
+        );
+        assert_eq!(
+            error(r#"{"text": "a", "clickEvent": {"action": "open_door", "value": "x"}}"#),
+            "unknown click action `open_door` at $.clickEvent.action"
+        );
+        assert_eq!(
+            error(r#"{"nbt": "Pos", "entity": "@s", "block": "~ ~ ~"}"#),
+            "expected exactly one of `block`, `entity` or `storage` at $"
+        );
+        assert_eq!(error("[]"), "empty list of components at $");
+    }
+
+    #[test]
+    fn test_format() {
+        let red = json!({"text": "a", "color": "red"});
+        assert_eq!(TextComponent::parse("mm:<red>a").unwrap().to_json(767), red);
+        assert_eq!(
+            TextComponent::parse("<red>a").unwrap().to_json(767),
+            json!("<red>a")
+        );
+        let text = TextComponent::parse_with("<red>a", TextFormat::MiniMessage);
+        assert_eq!(text.unwrap().to_json(767), red);
+    }
+
+    #[test]
+    fn test_raw_text() {
+        let raw: RawText = serde_json::from_value(json!("<red>a")).unwrap();
+        let red = json!({"text": "a", "color": "red"});
+        assert_eq!(
+            raw.parse(TextFormat::MiniMessage).unwrap().to_json(767),
+            red
+        );
+        assert_eq!(
+            raw.parse(TextFormat::Legacy).unwrap().to_json(767),
+            json!("<red>a")
+        );
+        let raw: RawText = serde_json::from_value(red.clone()).unwrap();
+        assert_eq!(
+            raw.parse(TextFormat::MiniMessage).unwrap().to_json(767),
+            red
+        );
+        assert_eq!(
+            RawText::default().parse(TextFormat::Legacy).unwrap(),
+            TextComponent::default()
+        );
+    }
+
+    #[test]
+    fn test_deserialize() {
+        #[derive(Deserialize)]
+        struct Config {
+            motd: TextComponent,
+        }
+        let config: Config =
+            serde_json::from_value(json!({"motd": {"text": "a", "color": "gold"}})).unwrap();
+        let TextComponent::Component(c) = config.motd else {
+            panic!();
+        };
+        assert_eq!(c.style.color, Some(Color::Named(NamedColor::Gold)));
+        let config: Config = serde_json::from_value(json!({"motd": r#"{"text": "a"}"#})).unwrap();
+        assert_eq!(config.motd.to_string(), r#"{"text":"a"}"#);
+        let err = serde_json::from_value::<Config>(json!({"motd": r#"{"text": "a", "bold": 1}"#}));
+        assert!(err.is_err());
+    }
+}
@@ -0,0 +1,478 @@
+use serde_json::{Map, Value};
+
+use crate::{
+    color::{Color, NamedColor},
+    component::{self, Component, Content, TextComponent},
+};
+
+/// The style of a piece of text, after inheriting from its parents
+#[derive(Debug, Clone, Default, PartialEq)]
+struct Style {
+    color: Option<NamedColor>,
+    bold: bool,
+    italic: bool,
+    underlined: bool,
+    strikethrough: bool,
+    obfuscated: bool,
+}
+
+impl Style {
+    fn inherit(&self, obj: &Map<String, Value>) -> Style {
+        let flag =
+            |key: &str, parent: bool| obj.get(key).and_then(Value::as_bool).unwrap_or(parent);
+        Style {
+            color: match obj.get("color").and_then(Value::as_str) {
+                Some(c) => Color::parse(c).map(Color::to_named).or(self.color),
+                None => self.color,
+            },
+            bold: flag("bold", self.bold),
+            italic: flag("italic", self.italic),
+            underlined: flag("underlined", self.underlined),
+            strikethrough: flag("strikethrough", self.strikethrough),
+            obfuscated: flag("obfuscated", self.obfuscated),
+        }
+    }
+
+    fn codes(&self) -> String {
+        let mut codes = String::new();
+        match self.color {
+            Some(c) => {
+                codes.push('§');
+                codes.push(c.code());
+            }
+            None => codes.push_str("§r"),
+        }
+        let flags = [
+            (self.obfuscated, 'k'),
+            (self.bold, 'l'),
+            (self.strikethrough, 'm'),
+            (self.underlined, 'n'),
+            (self.italic, 'o'),
+        ];
+        for (set, code) in flags {
+            if set {
+                codes.push('§');
+                codes.push(code);
+            }
+        }
+        codes
+    }
+}
+
+/// Fills in `%s` and `%1$s` style arguments of a translation
+fn format_translation(format: &str, args: &[String]) -> String {
+    let mut out = String::new();
+    let mut next_arg = 0;
+    let mut rest = format;
+    while let Some(idx) = rest.find('%') {
+        out.push_str(&rest[..idx]);
+        rest = &rest[idx + 1..];
+        if let Some(r) = rest.strip_prefix('%') {
+            out.push('%');
+            rest = r;
+        } else if let Some(r) = rest.strip_prefix('s') {
+            out.push_str(args.get(next_arg).map(String::as_str).unwrap_or(""));
+            next_arg += 1;
+            rest = r;
+        } else {
+            let digits = rest.chars().take_while(char::is_ascii_digit).count();
+            match rest[digits..].strip_prefix("$s") {
+                Some(r) if digits > 0 => {
+                    let n: usize = rest[..digits].parse().unwrap_or(0);
+                    out.push_str(
+                        args.get(n.wrapping_sub(1))
+                            .map(String::as_str)
+                            .unwrap_or(""),
+                    );
+                    rest = r;
+                }
+                _ => out.push('%'),
+            }
+        }
+    }
+    out.push_str(rest);
+    out
+}
+
+/// The text a component shows by itself, without its children
+fn content(obj: &Map<String, Value>) -> String {
+    let string = |key: &str| obj.get(key).and_then(Value::as_str);
+    if let Some(text) = string("text") {
+        return text.to_string();
+    }
+    if let Some(key) = string("translate") {
+        let format = string("fallback").unwrap_or(key);
+        let args: Vec<String> = match obj.get("with") {
+            Some(Value::Array(args)) => args.iter().map(to_plain).collect(),
+            _ => vec![],
+        };
+        return format_translation(format, &args);
+    }
+    if let Some(key) = string("keybind") {
+        return key.to_string();
+    }
+    if let Some(selector) = string("selector") {
+        return selector.to_string();
+    }
+    // Scores, nbt and objects can't be resolved without a world
+    String::new()
+}
+
+fn flatten(value: &Value, parent: &Style, out: &mut Vec<(String, Style)>) {
+    match value {
+        Value::String(s) => out.push((s.clone(), parent.clone())),
+        Value::Array(values) => {
+            // Every element after the first inherits the first one's style
+            let Some((first, rest)) = values.split_first() else {
+                return;
+            };
+            let style = match first {
+                Value::Object(obj) => parent.inherit(obj),
+                _ => parent.clone(),
+            };
+            flatten(first, parent, out);
+            for v in rest {
+                flatten(v, &style, out);
+            }
+        }
+        Value::Object(obj) => {
+            let style = parent.inherit(obj);
+            out.push((content(obj), style.clone()));
+            if let Some(Value::Array(extra)) = obj.get("extra") {
+                for v in extra {
+                    flatten(v, &style, out);
+                }
+            }
+        }
+        Value::Null => {}
+        v => out.push((v.to_string(), parent.clone())),
+    }
+}
+
+/// Renders a JSON text component as a '§'-formatted string
+pub fn to_legacy(value: &Value) -> String {
+    let mut parts = Vec::new();
+    flatten(value, &Style::default(), &mut parts);
+    let mut out = String::new();
+    // `None` once codes inside the text changed the style in ways we don't track
+    let mut current = Some(Style::default());
+    for (text, style) in parts {
+        if text.is_empty() {
+            continue;
+        }
+        if current.as_ref() != Some(&style) {
+            out.push_str(&style.codes());
+        }
+        out.push_str(&text);
+        current = if text.contains('§') {
+            None
+        } else {
+            Some(style)
+        };
+    }
+    out
+}
+
+/// Renders a JSON text component without any formatting
+pub fn to_plain(value: &Value) -> String {
+    let mut parts = Vec::new();
+    flatten(value, &Style::default(), &mut parts);
+    parts.into_iter().map(|(text, _)| text).collect()
+}
+
+/// The formatting of a character in a '§'-formatted string
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+struct Format {
+    color: Option<Color>,
+    bold: bool,
+    italic: bool,
+    underlined: bool,
+    strikethrough: bool,
+    obfuscated: bool,
+}
+
+impl Format {
+    fn apply(&mut self, code: char) -> bool {
+        match code.to_ascii_lowercase() {
+            'k' => self.obfuscated = true,
+            'l' => self.bold = true,
+            'm' => self.strikethrough = true,
+            'n' => self.underlined = true,
+            'o' => self.italic = true,
+            'r' => *self = Format::default(),
+            c => match NamedColor::from_code(c) {
+                // Colors reset the formatting
+                Some(color) => self.set_color(Color::Named(color)),
+                None => return false,
+            },
+        }
+        true
+    }
+
+    fn set_color(&mut self, color: Color) {
+        *self = Format {
+            color: Some(color),
+            ..Default::default()
+        };
+    }
+
+    fn style(&self) -> component::Style {
+        let flag = |set: bool| if set { Some(true) } else { None };
+        component::Style {
+            color: self.color,
+            bold: flag(self.bold),
+            italic: flag(self.italic),
+            underlined: flag(self.underlined),
+            strikethrough: flag(self.strikethrough),
+            obfuscated: flag(self.obfuscated),
+            ..Default::default()
+        }
+    }
+}
+
+/// Reads `&#RRGGBB`
+fn hex_color(s: &str) -> Option<Color> {
+    let hex = s.get(..7)?.strip_prefix('#')?;
+    Color::parse(&format!("#{}", hex))
+}
+
+/// Reads the `§x§R§R§G§G§B§B` form of hex colors, after the `§x`
+fn bukkit_hex_color(s: &str) -> Option<Color> {
+    let mut hex = String::from("#");
+    let mut chars = s.chars();
+    for _ in 0..6 {
+        if chars.next()? != '§' {
+            return None;
+        }
+        hex.push(chars.next()?);
+    }
+    Color::parse(&hex)
+}
+
+/// Reads the colors of `<gradient:#ff0000:blue>`, returning them and the tag's length
+fn gradient_tag(s: &str) -> Option<(Vec<Color>, usize)> {
+    let end = s.find('>')?;
+    let colors: Option<Vec<_>> = s[..end]
+        .strip_prefix("<gradient:")?
+        .split(':')
+        .map(Color::parse)
+        .collect();
+    match colors {
+        Some(colors) if colors.len() >= 2 => Some((colors, end + 1)),
+        _ => None,
+    }
+}
+
+const GRADIENT_END: &str = "</gradient>";
+
+/// Colors `chars` with a gradient going through `colors`
+fn apply_gradient(chars: &mut [(char, Format)], colors: &[Color]) {
+    let len = chars.len();
+    for (i, (_, format)) in chars.iter_mut().enumerate() {
+        format.color = Some(Color::gradient(colors, i, len));
+    }
+}
+
+/// Parses a '§'-formatted string. Strings with hex colors (`&#RRGGBB` or `§x§R§R§G§G§B§B`)
+/// or gradients (`<gradient:#ff0000:#0000ff>text</gradient>`) are turned into a component per
+/// differently colored piece of text, since clients only understand '§' codes for named colors
+pub fn parse(text: &str) -> TextComponent {
+    let mut chars: Vec<(char, Format)> = Vec::new();
+    let mut format = Format::default();
+    let mut extended = false;
+    // Where the open gradient starts in `chars`, and its colors
+    let mut gradient: Option<(usize, Vec<Color>)> = None;
+    let mut rest = text;
+    while let Some(c) = rest.chars().next() {
+        let after = &rest[c.len_utf8()..];
+        if c == '&' && after.starts_with('#') {
+            if let Some(color) = hex_color(after) {
+                format.set_color(color);
+                extended = true;
+                rest = &after[7..];
+                continue;
+            }
+        } else if c == '§' {
+            if let Some(after_x) = after.strip_prefix(['x', 'X'])
+                && let Some(color) = bukkit_hex_color(after_x)
+            {
+                format.set_color(color);
+                extended = true;
+                // Six '§' and hex digit pairs
+                rest = &after_x[6 * ('§'.len_utf8() + 1)..];
+                continue;
+            }
+            if let Some(code) = after.chars().next()
+                && format.apply(code)
+            {
+                rest = &after[code.len_utf8()..];
+                continue;
+            }
+        } else if c == '<' {
+            if let Some((colors, len)) = gradient_tag(rest) {
+                if let Some((start, colors)) = gradient.take() {
+                    apply_gradient(&mut chars[start..], &colors);
+                }
+                gradient = Some((chars.len(), colors));
+                extended = true;
+                rest = &rest[len..];
+                continue;
+            }
+            if let Some(r) = rest.strip_prefix(GRADIENT_END)
+                && let Some((start, colors)) = gradient.take()
+            {
+                apply_gradient(&mut chars[start..], &colors);
+                // Text after the gradient doesn't keep its last color
+                format.color = None;
+                rest = r;
+                continue;
+            }
+        }
+        chars.push((c, format));
+        rest = after;
+    }
+    if let Some((start, colors)) = gradient {
+        apply_gradient(&mut chars[start..], &colors);
+    }
+    if !extended {
+        return TextComponent::Text(text.to_string());
+    }
+    // The empty first component keeps the others from inheriting its style
+    let mut components = vec![TextComponent::Text(String::new())];
+    let mut chars = chars.into_iter().peekable();
+    while let Some((c, format)) = chars.next() {
+        let mut text = String::from(c);
+        while let Some((c, _)) = chars.next_if(|(_, f)| *f == format) {
+            text.push(c);
+        }
+        if format == Format::default() {
+            components.push(TextComponent::Text(text));
+            continue;
+        }
+        components.push(TextComponent::Component(Box::new(Component {
+            content: Content::Text(text),
+            style: format.style(),
+            extra: vec![],
+        })));
+    }
+    TextComponent::List(components)
+}
+
+/// A '§'-formatted string with hex colors and gradients replaced by the closest named colors
+pub fn downsample(text: &str) -> String {
+    parse(text).to_legacy()
+}
+
+/// Removes '§' color and formatting codes from a string
+pub fn strip_formatting(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    let mut chars = s.chars();
+    while let Some(c) = chars.next() {
+        if c == '§' {
+            chars.next();
+        } else {
+            out.push(c);
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn test_plain_string() {
+        assert_eq!(to_legacy(&json!("§d§lstatus server")), "§d§lstatus server");
+        assert_eq!(to_legacy(&json!(["a", "b"])), "ab");
+    }
+
+    #[test]
+    fn test_styles() {
+        let motd = json!({"text": "status server", "bold": true, "underlined": true, "color": "dark_green"});
+        assert_eq!(to_legacy(&motd), "§2§l§nstatus server");
+        let motd = json!({"text": "a", "color": "red", "extra": ["b", {"text": "c", "color": "blue"}, {"text": "d", "italic": true}]});
+        assert_eq!(to_legacy(&motd), "§cab§9c§c§od");
+    }
+
+    #[test]
+    fn test_array_inheritance() {
+        let motd = json!([{"text": "a", "color": "gold"}, "b", {"text": "c", "bold": true}]);
+        assert_eq!(to_legacy(&motd), "§6ab§6§lc");
+        let motd = json!(["", {"text": "a", "color": "gold"}, "b"]);
+        assert_eq!(to_legacy(&motd), "§6a§rb");
+    }
+
+    #[test]
+    fn test_translatable() {
+        let kick = json!([{ "text": "You have been killed by a ", "color": "red" }, { "type": "translatable", "translate": "item.minecraft.diamond_sword" }]);
+        assert_eq!(
+            to_legacy(&kick),
+            "§cYou have been killed by a item.minecraft.diamond_sword"
+        );
+        let fallback = json!({"translate": "a.b", "fallback": "%s and %2$s %%", "with": ["x", {"text": "y", "color": "red"}]});
+        assert_eq!(to_plain(&fallback), "x and y %");
+    }
+
+    #[test]
+    fn test_objects() {
+        let motd = json!([{"object": "atlas", "sprite": "item/nether_star"}, {"text":"status server","bold":true,"color":"dark_green"}, {"object": "atlas", "sprite": "item/nether_star"}]);
+        assert_eq!(to_legacy(&motd), "§2§lstatus server");
+    }
+
+    #[test]
+    fn test_hex_colors() {
+        assert_eq!(
+            parse("§cplain §lstring"),
+            TextComponent::Text("§cplain §lstring".into())
+        );
+        let text = parse("a&#FF0000b§x§0§0§0§0§F§F§lc§rd");
+        assert_eq!(
+            text.to_json(767),
+            json!(["", "a", {"text": "b", "color": "#FF0000"}, {"text": "c", "color": "#0000FF", "bold": true}, "d"])
+        );
+        assert_eq!(downsample("a&#FF5555b§x§5§5§5§5§F§F§lc§rd"), "a§cb§9§lc§rd");
+        // Broken hex colors are left as they are
+        assert_eq!(
+            parse("&#12345 §x§1"),
+            TextComponent::Text("&#12345 §x§1".into())
+        );
+    }
+
+    #[test]
+    fn test_gradient() {
+        let text = parse("<gradient:#ff0000:#0000ff>abc</gradient>§ld");
+        assert_eq!(
+            text.to_json(767),
+            json!(["",
+                {"text": "a", "color": "#FF0000"},
+                {"text": "b", "color": "#800080"},
+                {"text": "c", "color": "#0000FF"},
+                {"text": "d", "bold": true}])
+        );
+        assert_eq!(
+            downsample("<gradient:#ff0000:#0000ff>abc</gradient>"),
+            "§4a§5b§1c"
+        );
+        let text = parse("<gradient:red:yellow:green>§labc");
+        assert_eq!(
+            text.to_json(767),
+            json!(["",
+                {"text": "a", "color": "#FF5555", "bold": true},
+                {"text": "b", "color": "#FFFF55", "bold": true},
+                {"text": "c", "color": "#55FF55", "bold": true}])
+        );
+        assert_eq!(
+            parse("<gradient:red>a</gradient>"),
+            TextComponent::Text("<gradient:red>a</gradient>".into())
+        );
+    }
+
+    #[test]
+    fn test_strip() {
+        assert_eq!(strip_formatting("§d§lstatus §rserver"), "status server");
+        assert_eq!(strip_formatting("trailing §"), "trailing ");
+    }
+}
@@ -0,0 +1,11 @@
+pub mod color;
+pub mod compat;
+pub mod component;
+pub mod legacy;
+pub mod minimessage;
+pub mod width;
+
+pub use color::{Color, NamedColor};
+pub use compat::ObjectFallback;
+pub use component::{RawText, TextComponent, TextError, TextFormat};
+pub use legacy::{strip_formatting, to_legacy, to_plain};
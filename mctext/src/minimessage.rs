@@ -0,0 +1,537 @@
+use std::mem;
+
+use crate::{
+    color::Color,
+    component::{
+        ClickAction, ClickEvent, Component, Content, HoverEvent, ObjectContent, Style,
+        TextComponent,
+    },
+};
+
+/// Marks a string from the config as MiniMessage, whatever the config's format is
+pub const PREFIX: &str = "mm:";
+
+/// A tag that wasn't closed yet, and the text inside it so far
+struct Open {
+    /// The name that closes the tag, like `bold` for `<b>`
+    name: String,
+    style: Style,
+    gradient: Option<Vec<Color>>,
+    children: Vec<TextComponent>,
+}
+
+impl Open {
+    fn root() -> Self {
+        Open {
+            name: String::new(),
+            style: Style::default(),
+            gradient: None,
+            children: Vec::new(),
+        }
+    }
+
+    fn into_component(self) -> Option<TextComponent> {
+        let mut children = self.children;
+        if children.is_empty() {
+            return None;
+        }
+        if let Some(colors) = &self.gradient {
+            let len = children.iter().map(text_len).sum();
+            let mut index = 0;
+            for child in &mut children {
+                colorize(child, colors, &mut index, len);
+            }
+        }
+        let (content, extra) = match children.as_slice() {
+            [TextComponent::Text(text)] => (Content::Text(text.clone()), Vec::new()),
+            _ => (Content::Text(String::new()), children),
+        };
+        Some(TextComponent::Component(Box::new(Component {
+            content,
+            style: self.style,
+            extra,
+        })))
+    }
+}
+
+/// What an opening tag does
+enum Tag {
+    /// Styles the text until the tag is closed
+    Open {
+        name: String,
+        style: Style,
+        gradient: Option<Vec<Color>>,
+    },
+    /// Inserts a component, like `<newline>`
+    Insert(TextComponent),
+    /// Closes every tag
+    Reset,
+}
+
+/// The number of characters a gradient colors
+fn text_len(component: &TextComponent) -> usize {
+    match component {
+        TextComponent::Text(text) => text.chars().count(),
+        TextComponent::List(list) => list.iter().map(text_len).sum(),
+        TextComponent::Component(c) => {
+            let content = match &c.content {
+                Content::Text(text) => text.chars().count(),
+                _ => 0,
+            };
+            content + c.extra.iter().map(text_len).sum::<usize>()
+        }
+    }
+}
+
+fn gradient_chars(
+    text: &str,
+    colors: &[Color],
+    index: &mut usize,
+    len: usize,
+) -> Vec<TextComponent> {
+    text.chars()
+        .map(|c| {
+            let color = Color::gradient(colors, *index, len);
+            *index += 1;
+            TextComponent::Component(Box::new(Component {
+                content: Content::Text(c.to_string()),
+                style: Style {
+                    color: Some(color),
+                    ..Default::default()
+                },
+                extra: Vec::new(),
+            }))
+        })
+        .collect()
+}
+
+/// Splits text into a component per character, colored along the gradient
+fn colorize(component: &mut TextComponent, colors: &[Color], index: &mut usize, len: usize) {
+    match component {
+        TextComponent::Text(text) => {
+            let extra = gradient_chars(text, colors, index, len);
+            *component = TextComponent::Component(Box::new(Component {
+                content: Content::Text(String::new()),
+                style: Style::default(),
+                extra,
+            }));
+        }
+        TextComponent::List(list) => {
+            for c in list {
+                colorize(c, colors, index, len);
+            }
+        }
+        // Text with its own color keeps it
+        TextComponent::Component(c) if c.style.color.is_some() => *index += text_len(component),
+        TextComponent::Component(c) => {
+            let chars = match &mut c.content {
+                Content::Text(text) => gradient_chars(&mem::take(text), colors, index, len),
+                _ => Vec::new(),
+            };
+            for e in &mut c.extra {
+                colorize(e, colors, index, len);
+            }
+            c.extra.splice(0..0, chars);
+        }
+    }
+}
+
+/// The index of the `>` ending the tag at the start of `s`, skipping ones in quotes
+fn tag_end(s: &str) -> Option<usize> {
+    let mut quote = None;
+    let mut escaped = false;
+    for (i, c) in s.char_indices().skip(1) {
+        match (quote, c) {
+            _ if escaped => escaped = false,
+            (Some(_), '\\') => escaped = true,
+            (Some(q), c) if c == q => quote = None,
+            (Some(_), _) => {}
+            (None, '\'' | '"') => quote = Some(c),
+            (None, '>') => return Some(i),
+            (None, '<') => return None,
+            (None, _) => {}
+        }
+    }
+    None
+}
+
+/// Splits the inside of a tag on `:`, removing quotes
+fn split_args(tag: &str) -> Vec<String> {
+    let mut args = vec![String::new()];
+    let mut quote = None;
+    let mut chars = tag.chars();
+    while let Some(c) = chars.next() {
+        match (quote, c) {
+            (Some(_), '\\') => {
+                if let Some(next) = chars.next() {
+                    args.last_mut().unwrap().push(next);
+                }
+            }
+            (Some(q), c) if c == q => quote = None,
+            (None, '\'' | '"') => quote = Some(c),
+            (None, ':') => args.push(String::new()),
+            (_, c) => args.last_mut().unwrap().push(c),
+        }
+    }
+    args
+}
+
+/// The flag a decoration tag sets
+type Decoration = fn(&mut Style) -> &mut Option<bool>;
+
+/// The canonical name of a decoration tag and the flag it sets
+fn decoration(name: &str) -> Option<(&'static str, Decoration)> {
+    Some(match name {
+        "bold" | "b" => ("bold", |s| &mut s.bold),
+        "italic" | "i" | "em" => ("italic", |s| &mut s.italic),
+        "underlined" | "u" => ("underlined", |s| &mut s.underlined),
+        "strikethrough" | "st" => ("strikethrough", |s| &mut s.strikethrough),
+        "obfuscated" | "obf" => ("obfuscated", |s| &mut s.obfuscated),
+        _ => return None,
+    })
+}
+
+/// The name that closes a tag opened as `name`
+fn closing_name(name: &str) -> String {
+    let name = name.to_ascii_lowercase();
+    let name = name.strip_prefix('!').unwrap_or(&name).to_string();
+    match decoration(&name) {
+        Some((canonical, _)) => canonical.to_string(),
+        None => match name.as_str() {
+            "colour" | "c" => "color".to_string(),
+            _ => name,
+        },
+    }
+}
+
+fn open(name: &str, style: Style) -> Option<Tag> {
+    Some(Tag::Open {
+        name: name.to_string(),
+        style,
+        gradient: None,
+    })
+}
+
+fn insert(content: Content) -> Option<Tag> {
+    Some(Tag::Insert(TextComponent::Component(Box::new(Component {
+        content,
+        style: Style::default(),
+        extra: Vec::new(),
+    }))))
+}
+
+fn hover_event(args: &[String]) -> Option<HoverEvent> {
+    match args {
+        [action, text] if action == "show_text" => Some(HoverEvent::ShowText(parse(text))),
+        [action, id, rest @ ..] if action == "show_item" => Some(HoverEvent::ShowItem {
+            id: id.clone(),
+            count: match rest {
+                [count, ..] => Some(count.parse().ok()?),
+                [] => None,
+            },
+            components: None,
+        }),
+        [action, entity_type, uuid, rest @ ..] if action == "show_entity" => {
+            Some(HoverEvent::ShowEntity {
+                entity_type: entity_type.clone(),
+                uuid: uuid.clone(),
+                name: rest.first().map(|name| parse(name)),
+            })
+        }
+        _ => None,
+    }
+}
+
+/// Reads an opening tag, or `None` if it isn't one we know
+fn open_tag(args: &[String]) -> Option<Tag> {
+    let name = args[0].to_ascii_lowercase();
+    let (name, negated) = match name.strip_prefix('!') {
+        Some(name) => (name.to_string(), true),
+        None => (name, false),
+    };
+    if let Some((canonical, flag)) = decoration(&name) {
+        let mut style = Style::default();
+        *flag(&mut style) = Some(!negated);
+        return open(canonical, style);
+    }
+    if negated {
+        return None;
+    }
+    let color = |color: &str| {
+        Some(Style {
+            color: Some(Color::parse(&color.to_ascii_lowercase())?),
+            ..Default::default()
+        })
+    };
+    match (name.as_str(), &args[1..]) {
+        ("color" | "colour" | "c", [c]) => open("color", color(c)?),
+        (c, []) if Color::parse(c).is_some() => open(c, color(c)?),
+        ("gradient", colors) if colors.len() >= 2 => Some(Tag::Open {
+            name: "gradient".to_string(),
+            style: Style::default(),
+            gradient: Some(
+                colors
+                    .iter()
+                    .map(|c| Color::parse(&c.to_ascii_lowercase()))
+                    .collect::<Option<_>>()?,
+            ),
+        }),
+        ("reset", []) => Some(Tag::Reset),
+        ("newline" | "br", []) => Some(Tag::Insert(TextComponent::Text("\n".to_string()))),
+        ("lang" | "tr" | "translate", [key, with @ ..]) => insert(Content::Translatable {
+            key: key.clone(),
+            fallback: None,
+            with: with.iter().map(|arg| parse(arg)).collect(),
+        }),
+        ("lang_or" | "tr_or" | "translate_or", [key, fallback, with @ ..]) => {
+            insert(Content::Translatable {
+                key: key.clone(),
+                fallback: Some(fallback.clone()),
+                with: with.iter().map(|arg| parse(arg)).collect(),
+            })
+        }
+        ("key", [key]) => insert(Content::Keybind(key.clone())),
+        ("selector" | "sel", [selector]) => insert(Content::Selector {
+            selector: selector.clone(),
+            separator: None,
+        }),
+        ("score", [name, objective]) => insert(Content::Score {
+            name: name.clone(),
+            objective: objective.clone(),
+        }),
+        ("sprite", [sprite]) => insert(Content::Object(ObjectContent::Atlas {
+            atlas: None,
+            sprite: sprite.clone(),
+        })),
+        ("sprite", [atlas, sprite]) => insert(Content::Object(ObjectContent::Atlas {
+            atlas: Some(atlas.clone()),
+            sprite: sprite.clone(),
+        })),
+        ("head", [player, hat @ ..]) => insert(Content::Object(ObjectContent::Player {
+            player: player.clone().into(),
+            hat: match hat {
+                [hat] => Some(hat.parse().ok()?),
+                [] => None,
+                _ => return None,
+            },
+        })),
+        ("hover", args) => open(
+            "hover",
+            Style {
+                hover_event: Some(hover_event(args)?),
+                ..Default::default()
+            },
+        ),
+        ("click", [action, value]) => open(
+            "click",
+            Style {
+                click_event: Some(ClickEvent {
+                    action: ClickAction::from_name(action)?,
+                    value: value.clone(),
+                }),
+                ..Default::default()
+            },
+        ),
+        ("insert", [text]) => open(
+            "insert",
+            Style {
+                insertion: Some(text.clone()),
+                ..Default::default()
+            },
+        ),
+        ("font", [font]) => open(
+            "font",
+            Style {
+                font: Some(font.clone()),
+                ..Default::default()
+            },
+        ),
+        _ => None,
+    }
+}
+
+struct Parser {
+    stack: Vec<Open>,
+    text: String,
+}
+
+impl Parser {
+    fn flush(&mut self) {
+        if !self.text.is_empty() {
+            let text = TextComponent::Text(mem::take(&mut self.text));
+            self.stack.last_mut().unwrap().children.push(text);
+        }
+    }
+
+    /// Closes tags until only `len` are left open
+    fn close_to(&mut self, len: usize) {
+        self.flush();
+        while self.stack.len() > len.max(1) {
+            let open = self.stack.pop().unwrap();
+            if let Some(component) = open.into_component() {
+                self.stack.last_mut().unwrap().children.push(component);
+            }
+        }
+    }
+
+    /// Handles the inside of a tag, returning false if it should be left as text
+    fn tag(&mut self, tag: &str) -> bool {
+        if let Some(name) = tag.strip_prefix('/') {
+            let name = closing_name(&split_args(name)[0]);
+            return match self.stack.iter().rposition(|o| o.name == name) {
+                Some(pos) if pos > 0 => {
+                    self.close_to(pos);
+                    true
+                }
+                _ => false,
+            };
+        }
+        match open_tag(&split_args(tag)) {
+            Some(Tag::Open {
+                name,
+                style,
+                gradient,
+            }) => {
+                self.flush();
+                self.stack.push(Open {
+                    name,
+                    style,
+                    gradient,
+                    children: Vec::new(),
+                });
+            }
+            Some(Tag::Insert(component)) => {
+                self.flush();
+                self.stack.last_mut().unwrap().children.push(component);
+            }
+            Some(Tag::Reset) => self.close_to(1),
+            None => return false,
+        }
+        true
+    }
+}
+
+/// Parses MiniMessage text like `<red><bold>Maintenance</bold></red>`.
+/// Unknown tags are left as text, and `\<` is a literal `<`
+pub fn parse(input: &str) -> TextComponent {
+    let mut parser = Parser {
+        stack: vec![Open::root()],
+        text: String::new(),
+    };
+    let mut rest = input;
+    while let Some(c) = rest.chars().next() {
+        if c == '\\'
+            && let Some(next @ ('<' | '\\')) = rest[1..].chars().next()
+        {
+            parser.text.push(next);
+            rest = &rest[2..];
+            continue;
+        }
+        if c == '<'
+            && let Some(end) = tag_end(rest)
+            && parser.tag(&rest[1..end])
+        {
+            rest = &rest[end + 1..];
+            continue;
+        }
+        parser.text.push(c);
+        rest = &rest[c.len_utf8()..];
+    }
+    parser.close_to(1);
+    let mut children = parser.stack.pop().unwrap().children;
+    match children.len() {
+        0 => TextComponent::Text(String::new()),
+        1 => children.pop().unwrap(),
+        // The empty first component keeps the others from inheriting its style
+        _ => {
+            children.insert(0, TextComponent::Text(String::new()));
+            TextComponent::List(children)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    fn mm(input: &str) -> serde_json::Value {
+        parse(input).to_json(773)
+    }
+
+    #[test]
+    fn test_styles() {
+        assert_eq!(
+            mm("<red><bold>Maintenance</bold></red> <lang:item.minecraft.diamond_sword>"),
+            json!(["",
+                {"text": "", "color": "red", "extra": [{"text": "Maintenance", "bold": true}]},
+                " ",
+                {"translate": "item.minecraft.diamond_sword"}])
+        );
+        assert_eq!(
+            mm("<#ff8800>a<!i>b</!i></#ff8800>"),
+            json!({"text": "", "color": "#FF8800", "extra": ["a", {"text": "b", "italic": false}]})
+        );
+        assert_eq!(
+            mm("<color:gold><u>a<reset>b"),
+            json!(["", {"text": "", "color": "gold", "extra": [{"text": "a", "underlined": true}]}, "b"])
+        );
+        // Closing an outer tag closes the ones inside it
+        assert_eq!(
+            mm("<b>a<i>b</b>c"),
+            json!(["", {"text": "", "bold": true, "extra": ["a", {"text": "b", "italic": true}]}, "c"])
+        );
+    }
+
+    #[test]
+    fn test_literal() {
+        assert_eq!(mm("plain"), json!("plain"));
+        assert_eq!(
+            mm("<3 <unknown>a</red> \\<red>"),
+            json!("<3 <unknown>a</red> <red>")
+        );
+        assert_eq!(mm("a<newline>b"), json!(["", "a", "\n", "b"]));
+    }
+
+    #[test]
+    fn test_components() {
+        assert_eq!(
+            mm("<key:key.jump><lang:a.b:'<red>x':y><lang_or:a.c:'Fallback %s':z>"),
+            json!(["",
+                {"keybind": "key.jump"},
+                {"translate": "a.b", "with": [{"text": "x", "color": "red"}, "y"]},
+                {"translate": "a.c", "fallback": "Fallback %s", "with": ["z"]}])
+        );
+        assert_eq!(
+            mm("<sprite:item/diamond><sprite:'minecraft:blocks':block/stone><head:jeb_:false>"),
+            json!(["",
+                {"object": "atlas", "sprite": "item/diamond"},
+                {"object": "atlas", "atlas": "minecraft:blocks", "sprite": "block/stone"},
+                {"object": "player", "player": "jeb_", "hat": false}])
+        );
+    }
+
+    #[test]
+    fn test_events() {
+        assert_eq!(
+            mm("<hover:show_text:'<red>Hi: there'><click:open_url:'https://example.com'>link"),
+            json!({"text": "", "hover_event": {"action": "show_text", "value": {"text": "Hi: there", "color": "red"}},
+                "extra": [{"text": "link", "click_event": {"action": "open_url", "url": "https://example.com"}}]})
+        );
+        assert_eq!(mm("<click:open_door:x>a"), json!("<click:open_door:x>a"));
+    }
+
+    #[test]
+    fn test_gradient() {
+        assert_eq!(
+            mm("<gradient:#ff0000:#0000ff>a<b>b</b><blue>x</blue>c</gradient>"),
+            json!({"text": "", "extra": [
+                {"text": "", "extra": [{"text": "a", "color": "#FF0000"}]},
+                {"text": "", "bold": true, "extra": [{"text": "b", "color": "#AA0055"}]},
+                {"text": "x", "color": "blue"},
+                {"text": "", "extra": [{"text": "c", "color": "#0000FF"}]},
+            ]})
+        );
+    }
+}
@@ -0,0 +1,176 @@
+use serde::Deserialize;
+
+use crate::component::{Component, Content, Style, TextComponent};
+
+/// The width of a line of the server list's motd, in pixels
+pub const MOTD_WIDTH: u32 = 270;
+
+/// The server list shows at most this many motd lines
+pub const MOTD_LINES: usize = 2;
+
+/// Pixels the ASCII characters that aren't 5px wide take up in the default font,
+/// not counting the 1px gap after them
+const ASCII_WIDTHS: &[(char, u32)] = &[
+    (' ', 3),
+    ('!', 1),
+    ('"', 3),
+    ('\'', 1),
+    ('(', 3),
+    (')', 3),
+    ('*', 3),
+    (',', 1),
+    ('.', 1),
+    (':', 1),
+    (';', 1),
+    ('<', 4),
+    ('>', 4),
+    ('@', 6),
+    ('I', 3),
+    ('[', 3),
+    (']', 3),
+    ('`', 2),
+    ('f', 4),
+    ('i', 1),
+    ('k', 4),
+    ('l', 2),
+    ('t', 3),
+    ('{', 3),
+    ('|', 1),
+    ('}', 3),
+    ('~', 6),
+];
+
+/// Pixels a character takes up in the default font, including the 1px gap after it.
+/// Bold text is drawn twice, 1px apart, so it's 1px wider
+pub fn char_width(c: char, bold: bool) -> u32 {
+    let width = match ASCII_WIDTHS.iter().find(|(ch, _)| *ch == c) {
+        Some((_, width)) => *width,
+        None if c.is_control() => return 0,
+        // Wide characters come from the unifont
+        None if ('\u{2e80}'..='\u{9fff}').contains(&c)
+            || ('\u{ac00}'..='\u{d7af}').contains(&c) =>
+        {
+            8
+        }
+        None => 5,
+    };
+    width + 1 + bold as u32
+}
+
+/// The width in pixels of each line of a '§'-formatted string
+pub fn line_widths(text: &str) -> Vec<u32> {
+    let mut widths = vec![0];
+    let mut bold = false;
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '§' => match chars.next().map(|c| c.to_ascii_lowercase()) {
+                Some('l') => bold = true,
+                Some('k' | 'm' | 'n' | 'o') => {}
+                // Colors and §r reset the formatting
+                Some(_) => bold = false,
+                None => {}
+            },
+            '\n' => widths.push(0),
+            c => *widths.last_mut().unwrap() += char_width(c, bold),
+        }
+    }
+    widths
+}
+
+/// The width in pixels of each line of a text component
+pub fn component_widths(text: &TextComponent) -> Vec<u32> {
+    line_widths(&text.to_legacy())
+}
+
+#[derive(Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Align {
+    #[default]
+    Left,
+    Center,
+    Right,
+}
+
+/// Spaces taking up exactly `pixels`, when possible: spaces are 4px, and bold ones 5px
+pub fn padding(pixels: u32) -> TextComponent {
+    let mut bold = pixels % 4;
+    let mut normal = (pixels.saturating_sub(bold * 5)) / 4;
+    // Too narrow to be exact: get as close as possible with normal spaces
+    if bold * 5 > pixels {
+        bold = 0;
+        normal = (pixels + 2) / 4;
+    }
+    let mut parts = vec![TextComponent::Text(String::new())];
+    if normal > 0 {
+        parts.push(TextComponent::Text(" ".repeat(normal as usize)));
+    }
+    if bold > 0 {
+        parts.push(TextComponent::Component(Box::new(Component {
+            content: Content::Text(" ".repeat(bold as usize)),
+            style: Style {
+                bold: Some(true),
+                ..Default::default()
+            },
+            extra: Vec::new(),
+        })));
+    }
+    TextComponent::List(parts)
+}
+
+/// Pads a single line of text to align it in `width` pixels
+pub fn align_line(line: TextComponent, align: Align, width: u32) -> TextComponent {
+    let line_width = component_widths(&line).into_iter().max().unwrap_or(0);
+    let space = width.saturating_sub(line_width);
+    let pixels = match align {
+        Align::Left => return line,
+        Align::Center => space / 2,
+        Align::Right => space,
+    };
+    if pixels == 0 {
+        return line;
+    }
+    // The padding is its own component so the line doesn't inherit the bold spaces' style
+    TextComponent::List(vec![
+        TextComponent::Text(String::new()),
+        padding(pixels),
+        line,
+    ])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_widths() {
+        assert_eq!(line_widths("Hello"), vec![6 + 6 + 3 + 3 + 6]);
+        assert_eq!(line_widths("§lHi§r i"), vec![7 + 3 + 4 + 2]);
+        assert_eq!(line_widths("§c§lab§6c\nd"), vec![7 + 7 + 6, 6]);
+        assert_eq!(char_width('你', false), 9);
+    }
+
+    #[test]
+    fn test_padding() {
+        for pixels in [12, 13, 14, 15, 40, 101] {
+            let pad = padding(pixels);
+            assert_eq!(component_widths(&pad), vec![pixels], "{}", pixels);
+        }
+        assert_eq!(component_widths(&padding(7)), vec![8]);
+        assert_eq!(component_widths(&padding(1)), vec![0]);
+    }
+
+    #[test]
+    fn test_align() {
+        let line = TextComponent::from("§lstatus server");
+        let width = component_widths(&line)[0];
+        let centered = align_line(line.clone(), Align::Center, MOTD_WIDTH);
+        assert_eq!(
+            component_widths(&centered),
+            vec![width + (MOTD_WIDTH - width) / 2]
+        );
+        let right = align_line(line.clone(), Align::Right, MOTD_WIDTH);
+        assert_eq!(component_widths(&right), vec![MOTD_WIDTH]);
+        assert_eq!(align_line(line.clone(), Align::Left, MOTD_WIDTH), line);
+    }
+}
@@ -0,0 +1,68 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    hash::Hash,
+};
+
+/// A map that keeps at most `LIMIT` entries, dropping the oldest ones to make room for new ones
+#[derive(Debug)]
+pub struct BoundedCache<K, V, const LIMIT: usize> {
+    entries: HashMap<K, V>,
+    /// The keys from oldest to newest
+    order: VecDeque<K>,
+}
+
+impl<K, V, const LIMIT: usize> Default for BoundedCache<K, V, LIMIT> {
+    fn default() -> Self {
+        BoundedCache {
+            entries: HashMap::new(),
+            order: VecDeque::new(),
+        }
+    }
+}
+
+impl<K: Hash + Eq + Clone, V, const LIMIT: usize> BoundedCache<K, V, LIMIT> {
+    pub fn get(&self, key: &K) -> Option<&V> {
+        self.entries.get(key)
+    }
+
+    /// Keeps `value`, dropping the oldest entries if the cache is full
+    pub fn insert(&mut self, key: K, value: V) {
+        if self.entries.insert(key.clone(), value).is_none() {
+            self.order.push_back(key);
+        }
+        while self.order.len() > LIMIT {
+            if let Some(oldest) = self.order.pop_front() {
+                self.entries.remove(&oldest);
+            }
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_oldest_dropped() {
+        let mut cache = BoundedCache::<i32, &str, 2>::default();
+        assert!(cache.is_empty());
+        cache.insert(1, "a");
+        cache.insert(2, "b");
+        // Replacing a value doesn't make it newer
+        cache.insert(1, "c");
+        assert_eq!(cache.len(), 2);
+        cache.insert(3, "d");
+        assert_eq!(cache.len(), 2);
+        assert_eq!(cache.get(&1), None);
+        assert_eq!(cache.get(&2), Some(&"b"));
+        assert_eq!(cache.get(&3), Some(&"d"));
+    }
+}
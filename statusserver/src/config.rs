@@ -0,0 +1,435 @@
+use std::{
+    collections::{HashMap, HashSet},
+    fmt, fs, io,
+    path::{Path, PathBuf},
+};
+
+use log::warn;
+use mcproto::ProtocolRange;
+use serde::Deserialize;
+
+use crate::{
+    frames::Frames,
+    icon::{self, IconError},
+    hosts::{self, ConfigSet, HostHeader, VirtualHost},
+    overrides::{self, OverrideHeader, VersionOverride},
+    packets::ServerConfig,
+    placeholders::{self, PlaceholderError},
+    profiles::{self, Profile, ProfileHeader},
+    status::StatusCache,
+    status_file::{STATUS_FILE, StatusFile},
+};
+
+#[derive(Debug)]
+pub enum ConfigLoadingError {
+    IOError(io::Error),
+    ConfigError(toml::de::Error),
+    InvalidPlaceholder(PlaceholderError),
+    IconError(PathBuf, IconError),
+    Invalid(String),
+    /// An error in a `[[profiles]]`, `[[hosts]]` or `[[version_overrides]]` block
+    InSection(String, Box<ConfigLoadingError>),
+}
+
+impl fmt::Display for ConfigLoadingError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfigLoadingError::IOError(e) => write!(f, "{}", e),
+            ConfigLoadingError::ConfigError(e) => write!(f, "{}", e),
+            ConfigLoadingError::InvalidPlaceholder(e) => write!(f, "{}", e),
+            ConfigLoadingError::IconError(path, e) => {
+                write!(f, "couldn't read icon {}: {}", path.display(), e)
+            }
+            ConfigLoadingError::Invalid(message) => write!(f, "{}", message),
+            ConfigLoadingError::InSection(section, e) => write!(f, "in {}: {}", section, e),
+        }
+    }
+}
+
+impl From<io::Error> for ConfigLoadingError {
+    fn from(value: io::Error) -> Self {
+        ConfigLoadingError::IOError(value)
+    }
+}
+
+impl From<toml::de::Error> for ConfigLoadingError {
+    fn from(value: toml::de::Error) -> Self {
+        ConfigLoadingError::ConfigError(value)
+    }
+}
+
+impl From<PlaceholderError> for ConfigLoadingError {
+    fn from(value: PlaceholderError) -> Self {
+        ConfigLoadingError::InvalidPlaceholder(value)
+    }
+}
+
+impl From<String> for ConfigLoadingError {
+    fn from(value: String) -> Self {
+        ConfigLoadingError::Invalid(value)
+    }
+}
+
+/// Everything read from the config directory
+pub struct LoadedConfig {
+    pub default: ConfigSet,
+    pub hosts: Vec<VirtualHost>,
+    pub profiles: Vec<Profile>,
+    pub status_file: Option<StatusFile>,
+}
+
+/// A `[[profiles]]`, `[[hosts]]` or `[[version_overrides]]` block split into the keys that say when it's used
+/// and the fields it replaces
+fn split_section<T: for<'de> Deserialize<'de>>(
+    section: toml::Value,
+    keys: &[&str],
+) -> Result<(T, toml::Table), ConfigLoadingError> {
+    let toml::Value::Table(mut fields) = section else {
+        return Err(String::from("expected a table").into());
+    };
+    let mut header = toml::Table::new();
+    for key in keys {
+        if let Some(value) = fields.remove(*key) {
+            header.insert(key.to_string(), value);
+        }
+    }
+    let header = toml::Value::Table(header)
+        .try_into()
+        .map_err(|e: toml::de::Error| e.message().to_string())?;
+    Ok((header, fields))
+}
+
+/// The blocks of a list like `[[profiles]]`
+fn sections(table: &mut toml::Table, key: &str) -> Result<Vec<toml::Value>, ConfigLoadingError> {
+    match table.remove(key) {
+        None => Ok(vec![]),
+        Some(toml::Value::Array(list)) => Ok(list),
+        Some(_) => Err(format!("{} should be written as [[{}]]", key, key).into()),
+    }
+}
+
+/// Reads status.json if there is one, checking its placeholders if any config replaces them
+fn load_status_file(cfgdir: &Path, configs: &[&ServerConfig]) -> Result<Option<StatusFile>, ConfigLoadingError> {
+    let text = match fs::read_to_string(cfgdir.join(STATUS_FILE)) {
+        Ok(text) => text,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(ConfigLoadingError::InSection(STATUS_FILE.to_string(), Box::new(e.into()))),
+    };
+    let status_file = StatusFile::parse(&text)
+        .map_err(|e| ConfigLoadingError::InSection(STATUS_FILE.to_string(), Box::new(e.into())))?;
+    if configs.iter().any(|c| c.status_json.placeholders) {
+        placeholders::check_json(status_file.value(), false, STATUS_FILE)?;
+    }
+    Ok(Some(status_file))
+}
+
+/// Builds configs from the base config's table with the fields of some blocks replaced
+struct Builder<'a> {
+    base: toml::Table,
+    cfgdir: &'a Path,
+    /// The fields of each `[[version_overrides]]` block, used on top of every config
+    overrides: Vec<(ProtocolRange, toml::Table)>,
+    /// Warnings already logged, so they aren't repeated for every block
+    warned: HashSet<String>,
+    /// Icons already read, so they aren't converted again for every block
+    icons: HashMap<PathBuf, String>,
+}
+
+impl Builder<'_> {
+    fn read_icon(&mut self, path: &Path) -> Result<String, ConfigLoadingError> {
+        let path = self.cfgdir.join(path);
+        if let Some(icon) = self.icons.get(&path) {
+            return Ok(icon.clone());
+        }
+        let icon = icon::load(&path).map_err(|e| ConfigLoadingError::IconError(path.clone(), e))?;
+        self.icons.insert(path, icon.clone());
+        Ok(icon)
+    }
+
+    /// Reads the icons of a config, or the first of [`icon::DEFAULT_ICONS`] if it doesn't set any
+    fn load_icon(&mut self, config: &ServerConfig) -> Result<Option<Frames<String>>, ConfigLoadingError> {
+        match &config.icon {
+            Some(paths) => {
+                let mut icons = Vec::new();
+                for path in paths.iter() {
+                    let files = icon::files(self.cfgdir, path).map_err(|e| {
+                        ConfigLoadingError::IconError(self.cfgdir.join(path), e.into())
+                    })?;
+                    if files.is_empty() {
+                        let dir = self.cfgdir.join(path);
+                        return Err(format!("icon directory {} has no images", dir.display()).into());
+                    }
+                    for file in files {
+                        icons.push(self.read_icon(&file)?);
+                    }
+                }
+                Ok(Frames::new(icons, paths.rotation()))
+            }
+            None => {
+                let default = icon::DEFAULT_ICONS
+                    .iter()
+                    .map(Path::new)
+                    .find(|icon| self.cfgdir.join(icon).exists());
+                match default {
+                    Some(path) => self.read_icon(path).map(|icon| Some(Frames::from(icon))),
+                    None => Ok(None),
+                }
+            }
+        }
+    }
+
+    /// Decodes the icons the overlay is drawn on, when the config has one
+    fn load_overlay(config: &mut ServerConfig) -> Result<(), ConfigLoadingError> {
+        let Some(overlay) = &mut config.icon_overlay else {
+            return Ok(());
+        };
+        if let Some(icons) = &config.loaded_icon {
+            overlay.base = icons
+                .iter()
+                .map(|icon| icon::decode(icon))
+                .collect::<Result<_, _>>()
+                .map_err(|e| format!("couldn't read the icon for icon_overlay: {}", e))?;
+        }
+        Ok(())
+    }
+
+    fn warn(&mut self, section: Option<&str>, warnings: Vec<String>) {
+        for warning in warnings {
+            if !self.warned.insert(warning.clone()) {
+                continue;
+            }
+            match section {
+                Some(section) => warn!("In {}: {}", section, warning),
+                None => warn!("{}", warning),
+            }
+        }
+    }
+
+    /// A config with its version overrides
+    fn build(&mut self, layers: &[&toml::Table], section: &str) -> Result<ServerConfig, ConfigLoadingError> {
+        let mut config = self.build_one(layers, section)?;
+        config.version_overrides = self.build_overrides(layers, Some(section))?;
+        Ok(config)
+    }
+
+    /// The configs of the version overrides, on top of the config made from `layers`
+    fn build_overrides(
+        &mut self,
+        layers: &[&toml::Table],
+        section: Option<&str>,
+    ) -> Result<Vec<VersionOverride>, ConfigLoadingError> {
+        let mut built = Vec::with_capacity(self.overrides.len());
+        for (protocols, fields) in self.overrides.clone() {
+            let name = format!("version override '{}'", protocols);
+            // Errors are already in the config's section, but warnings aren't
+            let full_name = match section {
+                Some(section) => format!("{}, {}", section, name),
+                None => name.clone(),
+            };
+            let mut layers = layers.to_vec();
+            layers.push(&fields);
+            let config = self
+                .build_one(&layers, &full_name)
+                .map_err(|e| ConfigLoadingError::InSection(name, Box::new(e)))?;
+            built.push(VersionOverride { protocols, config });
+        }
+        Ok(built)
+    }
+
+    fn build_one(&mut self, layers: &[&toml::Table], section: &str) -> Result<ServerConfig, ConfigLoadingError> {
+        let mut table = self.base.clone();
+        for layer in layers {
+            // A block's motd replaces the config's motd_lines, and the other way around
+            let replaced = [
+                ("motd", "motd_lines"),
+                ("motd_lines", "motd"),
+                ("icon", "icons"),
+                ("icons", "icon"),
+            ];
+            for (key, other) in replaced {
+                if layer.contains_key(key) && !layer.contains_key(other) {
+                    table.remove(other);
+                }
+            }
+            table.extend(layer.iter().map(|(k, v)| (k.clone(), v.clone())));
+        }
+        let mut config: ServerConfig = toml::Value::Table(table)
+            .try_into()
+            .map_err(|e: toml::de::Error| e.message().to_string())?;
+        config.read_text()?;
+        config.check_placeholders()?;
+        config.loaded_icon = self.load_icon(&config)?;
+        Self::load_overlay(&mut config)?;
+        config.status_cache = StatusCache::new(config.status_is_static());
+        self.warn(Some(section), config.warnings());
+        Ok(config)
+    }
+
+    /// A config and its version for each profile
+    fn build_set(
+        &mut self,
+        base: ServerConfig,
+        layer: Option<(&toml::Table, &str)>,
+        profiles: &[(Profile, toml::Table)],
+    ) -> Result<ConfigSet, ConfigLoadingError> {
+        let mut configs = Vec::with_capacity(profiles.len());
+        for (profile, fields) in profiles {
+            let (layers, section) = match layer {
+                Some((host, name)) => (
+                    vec![host, fields],
+                    format!("{} with profile '{}'", name, profile.name),
+                ),
+                None => (vec![fields], format!("profile '{}'", profile.name)),
+            };
+            let config = self
+                .build(&layers, &section)
+                .map_err(|e| ConfigLoadingError::InSection(section, Box::new(e)))?;
+            configs.push(config);
+        }
+        Ok(ConfigSet {
+            base,
+            profiles: configs,
+        })
+    }
+}
+
+/// Reads the config with its profiles and hosts, and the icons they use
+pub fn load(config_path: &Path) -> Result<LoadedConfig, ConfigLoadingError> {
+    let text = &fs::read_to_string(config_path)?;
+    let cfgdir = config_path.parent().unwrap_or(Path::new("."));
+    // Read directly from the text first, so errors say where they are
+    let mut base: ServerConfig = toml::from_str(text)?;
+    base.read_text()?;
+    base.check_placeholders()?;
+
+    let mut table: toml::Table = toml::from_str(text)?;
+    let profile_sections = sections(&mut table, "profiles")?;
+    let host_sections = sections(&mut table, "hosts")?;
+    let override_sections = sections(&mut table, "version_overrides")?;
+
+    let mut version_overrides = Vec::new();
+    for (i, section) in override_sections.into_iter().enumerate() {
+        let in_section =
+            |e| ConfigLoadingError::InSection(format!("version_overrides[{}]", i), Box::new(e));
+        let (header, fields): (OverrideHeader, toml::Table) =
+            split_section(section, &overrides::OVERRIDE_KEYS).map_err(in_section)?;
+        if let Some(key) = fields.keys().find(|k| !overrides::OVERRIDE_FIELDS.contains(&k.as_str())) {
+            return Err(in_section(format!("{} can't be changed for some protocols", key).into()));
+        }
+        version_overrides.push((header.protocols, fields));
+    }
+
+    let mut builder = Builder {
+        base: table,
+        cfgdir,
+        overrides: version_overrides,
+        warned: HashSet::new(),
+        icons: HashMap::new(),
+    };
+    base.loaded_icon = builder.load_icon(&base)?;
+    Builder::load_overlay(&mut base)?;
+    base.status_cache = StatusCache::new(base.status_is_static());
+    builder.warn(None, base.warnings());
+    base.version_overrides = builder.build_overrides(&[], None)?;
+
+    let mut profiles: Vec<(Profile, toml::Table)> = Vec::new();
+    for (i, section) in profile_sections.into_iter().enumerate() {
+        let name = match section.get("name") {
+            Some(toml::Value::String(name)) => name.clone(),
+            _ => format!("#{}", i + 1),
+        };
+        let in_section = |e| ConfigLoadingError::InSection(format!("profile '{}'", name), Box::new(e));
+        let (header, fields): (ProfileHeader, _) =
+            split_section(section, &profiles::SCHEDULE_KEYS).map_err(in_section)?;
+        if profiles.iter().any(|(p, _)| p.name == header.name) {
+            return Err(in_section(String::from("another profile has the same name").into()));
+        }
+        let schedule = header.schedule().map_err(|e| in_section(e.into()))?;
+        profiles.push((
+            Profile {
+                name: header.name,
+                schedule,
+            },
+            fields,
+        ));
+    }
+
+    let mut virtual_hosts = Vec::new();
+    for (i, section) in host_sections.into_iter().enumerate() {
+        let (header, fields): (HostHeader, _) = split_section(section, &hosts::HOST_KEYS)
+            .map_err(|e| ConfigLoadingError::InSection(format!("hosts[{}]", i), Box::new(e)))?;
+        if header.hostnames.is_empty() {
+            let e = String::from("hostnames can't be empty").into();
+            return Err(ConfigLoadingError::InSection(format!("hosts[{}]", i), Box::new(e)));
+        }
+        let names: Vec<String> = header.hostnames.iter().map(|h| h.to_string()).collect();
+        let name = format!("host '{}'", names.join("', '"));
+        let config = builder
+            .build(&[&fields], &name)
+            .map_err(|e| ConfigLoadingError::InSection(name.clone(), Box::new(e)))?;
+        let configs = builder.build_set(config, Some((&fields, &name)), &profiles)?;
+        virtual_hosts.push(VirtualHost {
+            hostnames: header.hostnames,
+            configs,
+        });
+    }
+
+    let default = builder.build_set(base, None, &profiles)?;
+    let configs: Vec<&ServerConfig> = default
+        .iter()
+        .chain(virtual_hosts.iter().flat_map(|h| h.configs.iter()))
+        .collect();
+    let status_file = load_status_file(cfgdir, &configs)?;
+    if let Some(status_file) = &status_file {
+        for config in &configs {
+            builder.warn(None, status_file.warnings(config.status_json));
+        }
+    }
+    Ok(LoadedConfig {
+        default,
+        hosts: virtual_hosts,
+        profiles: profiles.into_iter().map(|(p, _)| p).collect(),
+        status_file,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BASE: &str = "version = '1.21'\nonline_players = 0\nmax_players = 20\nplayer_list = []\nmotd = ''\nkick_message = ''\n";
+
+    /// Loads a config with `extra` after the required fields, from a directory with `dirs` in it
+    fn load_with(name: &str, extra: &str, dirs: &[&str]) -> Result<LoadedConfig, ConfigLoadingError> {
+        let cfgdir = std::env::temp_dir().join(format!("statusserver-{}-{}", name, std::process::id()));
+        for dir in dirs {
+            fs::create_dir_all(cfgdir.join(dir)).unwrap();
+        }
+        fs::create_dir_all(&cfgdir).unwrap();
+        fs::write(cfgdir.join("config.toml"), format!("{}{}", BASE, extra)).unwrap();
+        let loaded = load(&cfgdir.join("config.toml"));
+        fs::remove_dir_all(&cfgdir).unwrap();
+        loaded
+    }
+
+    #[test]
+    fn test_empty_icon_directory() {
+        let Err(e) = load_with("empty-icons", "icon = 'icons'\n", &["icons"]) else {
+            panic!("loaded a config with an empty icon directory");
+        };
+        assert!(e.to_string().ends_with("has no images"), "{}", e);
+    }
+
+    #[test]
+    fn test_override_fields() {
+        let motd = "[[version_overrides]]\nprotocols = '..=1.20.1'\nmotd = 'old'\n";
+        let loaded = load_with("override-motd", motd, &[]).unwrap();
+        assert_eq!(loaded.default.base.version_overrides.len(), 1);
+
+        let players = "[[version_overrides]]\nprotocols = '..=1.20.1'\nonline_players = 5\n";
+        let Err(e) = load_with("override-players", players, &[]) else {
+            panic!("loaded a version override with online_players");
+        };
+        assert_eq!(e.to_string(), "in version_overrides[0]: online_players can't be changed for some protocols");
+    }
+}
@@ -0,0 +1,356 @@
+use std::collections::HashMap;
+
+use mcproto::{McWrite, ProtocolError, ProtocolVersion, VarInt};
+use serde::{Deserialize, Serialize};
+
+/// The last protocol of each Forge network version: 1.12.2 for FML, 1.17.1 for FML2
+const FML1_LAST: ProtocolVersion = ProtocolVersion(340);
+const FML2_LAST: ProtocolVersion = ProtocolVersion(756);
+
+/// How Forge's mod list is written in the status response
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum FmlVersion {
+    /// 1.7 to 1.12: `modinfo` with the mod list
+    Fml1,
+    /// 1.13 to 1.17: `forgeData` with the mods and channels
+    Fml2,
+    /// 1.18+: `forgeData` with the mods and channels in a compressed binary string
+    Fml3,
+}
+
+impl FmlVersion {
+    /// The version for a client, from the marker Forge adds to the hostname like `\0FML2\0`,
+    /// or from the client's protocol for clients that don't add one
+    pub fn for_client(hostname: Option<&str>, protocol: ProtocolVersion) -> Self {
+        let marker = hostname.and_then(|h| h.split('\0').nth(1));
+        match marker {
+            Some("FML") => FmlVersion::Fml1,
+            Some("FML2") => FmlVersion::Fml2,
+            Some("FML3") => FmlVersion::Fml3,
+            _ if protocol <= FML1_LAST => FmlVersion::Fml1,
+            _ if protocol <= FML2_LAST => FmlVersion::Fml2,
+            _ => FmlVersion::Fml3,
+        }
+    }
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct ForgeMod {
+    pub id: String,
+    pub version: String,
+}
+
+/// A network channel, like `forge:handshake`
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct ForgeChannel {
+    pub name: String,
+    pub version: String,
+    #[serde(default)]
+    pub required: bool,
+}
+
+impl ForgeChannel {
+    /// The channel's namespace and path, with `minecraft` as the namespace when it has none
+    fn split(&self) -> (&str, &str) {
+        self.name
+            .split_once(':')
+            .unwrap_or(("minecraft", &self.name))
+    }
+}
+
+/// How `[forge]` is written in the config
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ForgeConfig {
+    #[serde(default)]
+    mods: Vec<ForgeMod>,
+    #[serde(default)]
+    channels: Vec<ForgeChannel>,
+}
+
+/// The mods and channels shown to Forge clients, so they can tell whether they have them
+#[derive(Deserialize, Debug, Clone)]
+#[serde(try_from = "ForgeConfig")]
+pub struct ForgeData {
+    pub mods: Vec<ForgeMod>,
+    pub channels: Vec<ForgeChannel>,
+    /// The FML3 binary string, which is the same for every client
+    encoded: String,
+}
+
+impl TryFrom<ForgeConfig> for ForgeData {
+    type Error = String;
+
+    fn try_from(value: ForgeConfig) -> Result<Self, Self::Error> {
+        let encoded = encode(&value.mods, &value.channels).map_err(|e| e.to_string())?;
+        Ok(ForgeData {
+            mods: value.mods,
+            channels: value.channels,
+            encoded,
+        })
+    }
+}
+
+impl ForgeData {
+    /// The field of the status response with the mods
+    pub fn status(&self, version: FmlVersion) -> ForgeStatus<'_> {
+        match version {
+            FmlVersion::Fml1 => ForgeStatus::ModInfo {
+                kind: "FML",
+                mod_list: self
+                    .mods
+                    .iter()
+                    .map(|m| ModInfoEntry {
+                        modid: &m.id,
+                        version: &m.version,
+                    })
+                    .collect(),
+            },
+            FmlVersion::Fml2 => ForgeStatus::ForgeData {
+                channels: self
+                    .channels
+                    .iter()
+                    .map(|c| ChannelEntry {
+                        res: &c.name,
+                        version: &c.version,
+                        required: c.required,
+                    })
+                    .collect(),
+                mods: self
+                    .mods
+                    .iter()
+                    .map(|m| ModEntry {
+                        mod_id: &m.id,
+                        modmarker: &m.version,
+                    })
+                    .collect(),
+                truncated: None,
+                fml_network_version: 2,
+                d: None,
+            },
+            FmlVersion::Fml3 => ForgeStatus::ForgeData {
+                channels: vec![],
+                mods: vec![],
+                truncated: Some(false),
+                fml_network_version: 3,
+                d: Some(&self.encoded),
+            },
+        }
+    }
+}
+
+/// Forge's field of the status response, named after the variant
+#[derive(Serialize, Debug)]
+pub enum ForgeStatus<'a> {
+    #[serde(rename = "modinfo")]
+    ModInfo {
+        #[serde(rename = "type")]
+        kind: &'static str,
+        #[serde(rename = "modList")]
+        mod_list: Vec<ModInfoEntry<'a>>,
+    },
+    #[serde(rename = "forgeData", rename_all = "camelCase")]
+    ForgeData {
+        channels: Vec<ChannelEntry<'a>>,
+        mods: Vec<ModEntry<'a>>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        truncated: Option<bool>,
+        fml_network_version: u8,
+        /// FML3's mods and channels, in place of the lists
+        #[serde(skip_serializing_if = "Option::is_none")]
+        d: Option<&'a str>,
+    },
+}
+
+#[derive(Serialize, Debug)]
+pub struct ModInfoEntry<'a> {
+    modid: &'a str,
+    version: &'a str,
+}
+
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct ModEntry<'a> {
+    mod_id: &'a str,
+    modmarker: &'a str,
+}
+
+#[derive(Serialize, Debug)]
+pub struct ChannelEntry<'a> {
+    res: &'a str,
+    version: &'a str,
+    required: bool,
+}
+
+/// FML3's binary mod list: each mod with the channels in its namespace, then the other channels
+fn encode(mods: &[ForgeMod], channels: &[ForgeChannel]) -> Result<String, ProtocolError> {
+    let mut by_mod: HashMap<&str, Vec<&ForgeChannel>> = HashMap::new();
+    let mut other = Vec::new();
+    for channel in channels {
+        let (namespace, _) = channel.split();
+        match mods.iter().find(|m| m.id == namespace) {
+            Some(m) => by_mod.entry(m.id.as_str()).or_default().push(channel),
+            None => other.push(channel),
+        }
+    }
+    let mut buf = Vec::new();
+    // Whether the list was truncated
+    false.write_to(&mut buf)?;
+    (mods.len() as u16).write_to(&mut buf)?;
+    for m in mods {
+        let channels = by_mod.remove(m.id.as_str()).unwrap_or_default();
+        // The lowest bit would mean the mod has no version
+        VarInt((channels.len() as i32) << 1).write_to(&mut buf)?;
+        m.id.write_to(&mut buf)?;
+        m.version.write_to(&mut buf)?;
+        for channel in channels {
+            channel.split().1.write_to(&mut buf)?;
+            channel.version.write_to(&mut buf)?;
+            channel.required.write_to(&mut buf)?;
+        }
+    }
+    VarInt(other.len() as i32).write_to(&mut buf)?;
+    for channel in other {
+        channel.name.write_to(&mut buf)?;
+        channel.version.write_to(&mut buf)?;
+        channel.required.write_to(&mut buf)?;
+    }
+    Ok(pack_string(&buf))
+}
+
+/// Packs bytes into a string with 15 bits in each char, after 2 chars with the length.
+/// Every char is below 0x8000, so none of them are surrogates
+fn pack_string(bytes: &[u8]) -> String {
+    let to_char = |bits: u32| char::from_u32(bits & 0x7fff).unwrap_or_default();
+    let len = bytes.len() as u32;
+    let mut packed = String::new();
+    packed.push(to_char(len));
+    packed.push(to_char(len >> 15));
+    let mut buffer = 0u32;
+    let mut bits = 0;
+    for b in bytes {
+        buffer |= (*b as u32) << bits;
+        bits += 8;
+        // Flushed right away, so the last char never has more than 15 bits left for it
+        if bits >= 15 {
+            packed.push(to_char(buffer));
+            buffer >>= 15;
+            bits -= 15;
+        }
+    }
+    if bits > 0 {
+        packed.push(to_char(buffer));
+    }
+    packed
+}
+
+#[cfg(test)]
+mod tests {
+    use mcproto::McRead;
+
+    use super::*;
+
+    /// Forge's `ServerStatusPing.decodeOptimized`, to check the packing against
+    fn unpack_string(s: &str) -> Vec<u8> {
+        let chars: Vec<u32> = s.chars().map(u32::from).collect();
+        let size = (chars[0] | chars[1] << 15) as usize;
+        let mut bytes = Vec::with_capacity(size);
+        let mut buffer = 0u32;
+        let mut bits = 0;
+        for c in &chars[2..] {
+            while bits >= 8 {
+                bytes.push(buffer as u8);
+                buffer >>= 8;
+                bits -= 8;
+            }
+            buffer |= (c & 0x7fff) << bits;
+            bits += 15;
+        }
+        while bytes.len() < size {
+            bytes.push(buffer as u8);
+            buffer >>= 8;
+        }
+        bytes
+    }
+
+    fn channel(name: &str, required: bool) -> ForgeChannel {
+        ForgeChannel {
+            name: name.to_string(),
+            version: String::from("1"),
+            required,
+        }
+    }
+
+    fn forge_mod(id: &str, version: &str) -> ForgeMod {
+        ForgeMod {
+            id: id.to_string(),
+            version: version.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_pack_string() {
+        assert_eq!(pack_string(&[1, 2, 3]), "\u{3}\u{0}\u{201}\u{6}");
+        assert_eq!(pack_string(&[]), "\u{0}\u{0}");
+        for len in 0..64 {
+            let bytes: Vec<u8> = (0..len).map(|i| (i * 97 + 13) as u8 | 0x80).collect();
+            let packed = pack_string(&bytes);
+            assert!(packed.chars().all(|c| (c as u32) < 0x8000));
+            assert_eq!(unpack_string(&packed), bytes);
+        }
+    }
+
+    #[test]
+    fn test_encode() {
+        let mods = [forge_mod("forge", "47.2.0"), forge_mod("jei", "15.2.0")];
+        let channels = [
+            channel("forge:handshake", true),
+            channel("jei:channel", false),
+            channel("forge:tier_sorting", false),
+            channel("minecraft:register", false),
+        ];
+        let bytes = unpack_string(&encode(&mods, &channels).unwrap());
+        let reader = &mut bytes.as_slice();
+        let string = |reader: &mut &[u8]| String::read_from(reader).unwrap();
+        let channel = |reader: &mut &[u8]| {
+            (
+                string(reader),
+                string(reader),
+                bool::read_from(reader).unwrap(),
+            )
+        };
+        assert!(!bool::read_from(reader).unwrap());
+        assert_eq!(u16::read_from(reader).unwrap(), 2);
+        assert_eq!(VarInt::read_from(reader).unwrap(), VarInt(2 << 1));
+        assert_eq!(string(reader), "forge");
+        assert_eq!(string(reader), "47.2.0");
+        assert_eq!(channel(reader), ("handshake".into(), "1".into(), true));
+        assert_eq!(channel(reader), ("tier_sorting".into(), "1".into(), false));
+        assert_eq!(VarInt::read_from(reader).unwrap(), VarInt(1 << 1));
+        assert_eq!(string(reader), "jei");
+        assert_eq!(string(reader), "15.2.0");
+        assert_eq!(channel(reader), ("channel".into(), "1".into(), false));
+        // Channels without a mod keep their namespace
+        assert_eq!(VarInt::read_from(reader).unwrap(), VarInt(1));
+        assert_eq!(
+            channel(reader),
+            ("minecraft:register".into(), "1".into(), false)
+        );
+        assert!(reader.is_empty());
+    }
+
+    #[test]
+    fn test_for_client() {
+        let version =
+            |hostname, protocol| FmlVersion::for_client(hostname, ProtocolVersion(protocol));
+        assert_eq!(version(Some("localhost\0FML\0"), 767), FmlVersion::Fml1);
+        assert_eq!(version(Some("localhost\0FML2\0"), 767), FmlVersion::Fml2);
+        assert_eq!(version(Some("localhost\0FML3\0"), 340), FmlVersion::Fml3);
+        assert_eq!(version(Some("localhost"), 340), FmlVersion::Fml1);
+        assert_eq!(version(None, 341), FmlVersion::Fml2);
+        assert_eq!(version(None, 756), FmlVersion::Fml2);
+        assert_eq!(version(None, 757), FmlVersion::Fml3);
+    }
+}
@@ -0,0 +1,257 @@
+use std::{
+    hash::{DefaultHasher, Hash, Hasher},
+    net::IpAddr,
+    sync::atomic::{AtomicUsize, Ordering},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use serde::{
+    Deserialize, Deserializer,
+    de::{self, DeserializeOwned},
+};
+use serde_json::Value;
+
+/// How a frame is chosen for each request
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Rotation {
+    /// The next frame on every request
+    Sequential,
+    Random,
+    /// The same frame for every request from an IP
+    Sticky,
+    /// The next frame every this many seconds
+    Interval(u64),
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum RotationName {
+    Sequential,
+    Random,
+    Sticky,
+    Interval,
+}
+
+/// How frames are written in the config
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct FrameSet<T> {
+    frames: Vec<T>,
+    rotation: Option<RotationName>,
+    /// In seconds
+    interval: Option<u64>,
+}
+
+/// A value that can change between requests, like an animated motd.
+/// It's written as a single value or as `{ frames = [...], rotation = '...' }`
+#[derive(Debug)]
+pub struct Frames<T> {
+    frames: Vec<T>,
+    rotation: Rotation,
+    next: AtomicUsize,
+}
+
+impl<T> Frames<T> {
+    pub fn new(frames: Vec<T>, rotation: Rotation) -> Option<Self> {
+        if frames.is_empty() {
+            return None;
+        }
+        Some(Frames {
+            frames,
+            rotation,
+            next: AtomicUsize::new(0),
+        })
+    }
+
+    pub fn rotation(&self) -> Rotation {
+        self.rotation
+    }
+
+    pub fn iter(&self) -> std::slice::Iter<'_, T> {
+        self.frames.iter()
+    }
+
+    /// Each frame with where it is in the config, like `motd` or `motd.frames[1]`
+    pub fn labeled<'a>(&'a self, name: &'a str) -> impl Iterator<Item = (String, &'a T)> {
+        let single = self.frames.len() == 1;
+        self.frames
+            .iter()
+            .enumerate()
+            .map(move |(i, frame)| match single {
+                true => (name.to_string(), frame),
+                false => (format!("{}.frames[{}]", name, i), frame),
+            })
+    }
+
+    /// The same frames with another type, keeping the rotation
+    pub fn try_map<U, E>(&self, f: impl FnMut(&T) -> Result<U, E>) -> Result<Frames<U>, E> {
+        let frames = self.frames.iter().map(f).collect::<Result<_, _>>()?;
+        Ok(Frames {
+            frames,
+            rotation: self.rotation,
+            next: AtomicUsize::new(0),
+        })
+    }
+
+    /// The frame for a request from `ip`
+    pub fn pick(&self, ip: IpAddr) -> &T {
+        &self.frames[self.pick_index(ip)]
+    }
+
+    /// The frame at an index from [`pick_index`](Self::pick_index)
+    pub fn get(&self, i: usize) -> &T {
+        &self.frames[i]
+    }
+
+    /// The index of the frame for a request from `ip`
+    pub fn pick_index(&self, ip: IpAddr) -> usize {
+        let len = self.frames.len();
+        if len == 1 {
+            return 0;
+        }
+        let i = match self.rotation {
+            Rotation::Sequential => self.next.fetch_add(1, Ordering::Relaxed),
+            Rotation::Random => rand::random_range(0..len),
+            Rotation::Sticky => {
+                let mut hasher = DefaultHasher::new();
+                ip.hash(&mut hasher);
+                hasher.finish() as usize
+            }
+            Rotation::Interval(seconds) => {
+                let now = SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .unwrap_or_default()
+                    .as_secs();
+                (now / seconds) as usize
+            }
+        };
+        i % len
+    }
+}
+
+impl<T: Default> Default for Frames<T> {
+    fn default() -> Self {
+        Frames::from(T::default())
+    }
+}
+
+impl<T> From<T> for Frames<T> {
+    fn from(value: T) -> Self {
+        Frames {
+            frames: vec![value],
+            rotation: Rotation::Sequential,
+            next: AtomicUsize::new(0),
+        }
+    }
+}
+
+impl<'de, T: DeserializeOwned> Deserialize<'de> for Frames<T> {
+    fn deserialize<D: Deserializer<'de>>(d: D) -> Result<Self, D::Error> {
+        let value = Value::deserialize(d)?;
+        let is_frame_set = matches!(&value, Value::Object(map) if map.contains_key("frames"));
+        if !is_frame_set {
+            return T::deserialize(value)
+                .map(Frames::from)
+                .map_err(de::Error::custom);
+        }
+        let set = FrameSet::<T>::deserialize(value).map_err(de::Error::custom)?;
+        let rotation = match (set.rotation, set.interval) {
+            (Some(RotationName::Interval) | None, Some(0)) => {
+                return Err(de::Error::custom("interval must be at least 1 second"));
+            }
+            (Some(RotationName::Interval) | None, Some(seconds)) => Rotation::Interval(seconds),
+            (Some(RotationName::Interval), None) => {
+                return Err(de::Error::custom(
+                    "rotation 'interval' needs an interval in seconds",
+                ));
+            }
+            (Some(_), Some(_)) => {
+                return Err(de::Error::custom(
+                    "interval is only used by rotation 'interval'",
+                ));
+            }
+            (Some(RotationName::Sequential) | None, None) => Rotation::Sequential,
+            (Some(RotationName::Random), None) => Rotation::Random,
+            (Some(RotationName::Sticky), None) => Rotation::Sticky,
+        };
+        Frames::new(set.frames, rotation).ok_or_else(|| de::Error::custom("frames can't be empty"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::Ipv4Addr;
+
+    use serde_json::json;
+
+    use super::*;
+
+    fn frames(value: Value) -> Result<Frames<String>, String> {
+        Frames::deserialize(value).map_err(|e| e.to_string())
+    }
+
+    fn ip(last: u8) -> IpAddr {
+        IpAddr::V4(Ipv4Addr::new(10, 0, 0, last))
+    }
+
+    #[test]
+    fn test_single_value() {
+        let single = frames(json!("a")).unwrap();
+        assert_eq!(single.iter().collect::<Vec<_>>(), ["a"]);
+        assert_eq!(single.rotation(), Rotation::Sequential);
+        let set = frames(json!({"frames": ["a", "b"], "rotation": "sticky"})).unwrap();
+        assert_eq!(set.rotation(), Rotation::Sticky);
+        let set = frames(json!({"frames": ["a", "b"], "interval": 5})).unwrap();
+        assert_eq!(set.rotation(), Rotation::Interval(5));
+    }
+
+    #[test]
+    fn test_invalid() {
+        assert_eq!(
+            frames(json!({"frames": ["a"], "interval": 0})).unwrap_err(),
+            "interval must be at least 1 second"
+        );
+        assert_eq!(
+            frames(json!({"frames": ["a"], "rotation": "interval"})).unwrap_err(),
+            "rotation 'interval' needs an interval in seconds"
+        );
+        assert_eq!(
+            frames(json!({"frames": ["a"], "rotation": "random", "interval": 3})).unwrap_err(),
+            "interval is only used by rotation 'interval'"
+        );
+        assert_eq!(
+            frames(json!({"frames": []})).unwrap_err(),
+            "frames can't be empty"
+        );
+        assert!(frames(json!({"frames": ["a"], "speed": 1})).is_err());
+    }
+
+    #[test]
+    fn test_sequential() {
+        let frames = Frames::new(vec!["a", "b", "c"], Rotation::Sequential).unwrap();
+        let picked: Vec<usize> = (0..7).map(|_| frames.pick_index(ip(1))).collect();
+        assert_eq!(picked, [0, 1, 2, 0, 1, 2, 0]);
+    }
+
+    #[test]
+    fn test_sticky() {
+        let frames = Frames::new((0..16).collect(), Rotation::Sticky).unwrap();
+        let first = frames.pick_index(ip(1));
+        assert!((0..10).all(|_| frames.pick_index(ip(1)) == first));
+        // Other IPs don't all get the same frame
+        assert!((2..50).any(|i| frames.pick_index(ip(i)) != first));
+    }
+
+    #[test]
+    fn test_single_frame() {
+        for rotation in [
+            Rotation::Sequential,
+            Rotation::Random,
+            Rotation::Sticky,
+            Rotation::Interval(1),
+        ] {
+            let frames = Frames::new(vec!["a"], rotation).unwrap();
+            assert!((0..5).all(|i| frames.pick_index(ip(i)) == 0));
+        }
+    }
+}
@@ -0,0 +1,217 @@
+use std::fmt;
+
+use serde::{Deserialize, Deserializer, de};
+
+use crate::packets::ServerConfig;
+
+/// The keys of a host block that say which hostnames it's for; the rest replace fields of the config
+pub const HOST_KEYS: [&str; 1] = ["hostnames"];
+
+/// The hostname a client connected to, as it's matched: lowercase, without a trailing dot
+/// and without what Forge and proxies add after a `\0`, like `\0FML\0` or `\0FML3\0`
+pub fn normalize(hostname: &str) -> String {
+    let hostname = hostname.split('\0').next().unwrap_or_default();
+    hostname.trim_end_matches('.').to_ascii_lowercase()
+}
+
+/// Whether `text` matches `pattern`, where `*` matches any characters
+fn glob(pattern: &str, text: &str) -> bool {
+    match pattern.split_once('*') {
+        None => pattern == text,
+        Some((prefix, rest)) => {
+            let Some(text) = text.strip_prefix(prefix) else {
+                return false;
+            };
+            (0..=text.len())
+                .filter(|i| text.is_char_boundary(*i))
+                .any(|i| glob(rest, &text[i..]))
+        }
+    }
+}
+
+/// A hostname a host block is used for
+#[derive(Debug, Clone, PartialEq)]
+pub enum HostPattern {
+    /// Just this hostname
+    Exact(String),
+    /// `.example.com`: example.com and all of its subdomains
+    Suffix(String),
+    /// `*.example.com` or `play*.example.com`: `*` matches anything
+    Wildcard(String),
+}
+
+impl HostPattern {
+    pub fn parse(pattern: &str) -> Result<Self, String> {
+        let pattern = normalize(pattern);
+        if pattern.is_empty() || pattern == "." {
+            return Err(String::from("empty hostname"));
+        }
+        Ok(if let Some(domain) = pattern.strip_prefix('.') {
+            HostPattern::Suffix(domain.to_string())
+        } else if pattern.contains('*') {
+            HostPattern::Wildcard(pattern)
+        } else {
+            HostPattern::Exact(pattern)
+        })
+    }
+
+    /// Whether a [normalized](normalize) hostname matches
+    pub fn matches(&self, hostname: &str) -> bool {
+        match self {
+            HostPattern::Exact(host) => hostname == host,
+            HostPattern::Suffix(domain) => {
+                hostname == domain
+                    || hostname
+                        .strip_suffix(domain.as_str())
+                        .is_some_and(|sub| sub.ends_with('.'))
+            }
+            HostPattern::Wildcard(pattern) => glob(pattern, hostname),
+        }
+    }
+}
+
+impl fmt::Display for HostPattern {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            HostPattern::Exact(host) | HostPattern::Wildcard(host) => write!(f, "{}", host),
+            HostPattern::Suffix(domain) => write!(f, ".{}", domain),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for HostPattern {
+    fn deserialize<D: Deserializer<'de>>(d: D) -> Result<Self, D::Error> {
+        let pattern = String::deserialize(d)?;
+        HostPattern::parse(&pattern).map_err(de::Error::custom)
+    }
+}
+
+/// The keys of a host block in [`HOST_KEYS`]
+#[derive(Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct HostHeader {
+    pub hostnames: Vec<HostPattern>,
+}
+
+/// A config, and the config for each profile while it's active
+#[derive(Debug)]
+pub struct ConfigSet {
+    pub base: ServerConfig,
+    /// In the same order as the profiles
+    pub profiles: Vec<ServerConfig>,
+}
+
+impl ConfigSet {
+    /// The config in use while the profile at index `active` is active
+    pub fn get(&self, active: Option<usize>) -> &ServerConfig {
+        match active.and_then(|i| self.profiles.get(i)) {
+            Some(config) => config,
+            None => &self.base,
+        }
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &ServerConfig> {
+        std::iter::once(&self.base).chain(&self.profiles)
+    }
+}
+
+/// Configs used for clients connecting to some hostnames
+#[derive(Debug)]
+pub struct VirtualHost {
+    pub hostnames: Vec<HostPattern>,
+    pub configs: ConfigSet,
+}
+
+impl VirtualHost {
+    pub fn matches(&self, hostname: &str) -> bool {
+        self.hostnames.iter().any(|p| p.matches(hostname))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::packets::ServerInfo;
+
+    use super::*;
+
+    fn pattern(pattern: &str) -> HostPattern {
+        HostPattern::parse(pattern).unwrap()
+    }
+
+    fn config(motd: &str) -> ConfigSet {
+        ConfigSet {
+            base: ServerConfig::for_test(motd),
+            profiles: vec![],
+        }
+    }
+
+    #[test]
+    fn test_normalize() {
+        assert_eq!(normalize("Play.Example.COM."), "play.example.com");
+        assert_eq!(normalize("play.example.com\0FML\0"), "play.example.com");
+        assert_eq!(normalize("play.example.com\0FML3\0"), "play.example.com");
+        assert_eq!(normalize("play.example.com.\0FML2\0"), "play.example.com");
+    }
+
+    #[test]
+    fn test_parse() {
+        assert_eq!(
+            pattern("Example.com."),
+            HostPattern::Exact("example.com".into())
+        );
+        assert_eq!(
+            pattern(".example.com"),
+            HostPattern::Suffix("example.com".into())
+        );
+        assert_eq!(
+            pattern("*.example.com"),
+            HostPattern::Wildcard("*.example.com".into())
+        );
+        assert!(HostPattern::parse("").is_err());
+        assert!(HostPattern::parse(".").is_err());
+    }
+
+    #[test]
+    fn test_exact() {
+        let exact = pattern("play.example.com");
+        assert!(exact.matches(&normalize("PLAY.example.com.")));
+        assert!(!exact.matches("example.com"));
+        assert!(!exact.matches("a.play.example.com"));
+    }
+
+    #[test]
+    fn test_suffix() {
+        let suffix = pattern(".example.com");
+        assert!(suffix.matches("example.com"));
+        assert!(suffix.matches("play.example.com"));
+        assert!(suffix.matches("a.b.example.com"));
+        assert!(!suffix.matches("badexample.com"));
+        assert!(!suffix.matches("example.com.evil"));
+    }
+
+    #[test]
+    fn test_wildcard() {
+        let wildcard = pattern("*.example.com");
+        assert!(wildcard.matches("play.example.com"));
+        assert!(!wildcard.matches("example.com"));
+        let wildcard = pattern("play*.example.com");
+        assert!(wildcard.matches("play.example.com"));
+        assert!(wildcard.matches("play2.example.com"));
+        assert!(!wildcard.matches("lobby.example.com"));
+        assert!(glob("*", ""));
+        assert!(glob("a*é*", "abcé"));
+    }
+
+    #[test]
+    fn test_fallback() {
+        let hosts = vec![VirtualHost {
+            hostnames: vec![pattern(".example.com")],
+            configs: config("example"),
+        }];
+        let info = ServerInfo::new(config("default"), hosts, vec![], None);
+        let motd = |hostname| info.config(hostname).motd.get(0).to_string();
+        assert_eq!(motd(Some("Play.Example.com.\0FML3\0")), "example");
+        assert_eq!(motd(Some("other.net")), "default");
+        assert_eq!(motd(None), "default");
+    }
+}
@@ -0,0 +1,233 @@
+use std::{
+    fmt, fs,
+    io::{self, Cursor},
+    path::{Path, PathBuf},
+};
+
+use base64::{Engine, engine::general_purpose::STANDARD};
+use image::{ImageError, ImageFormat, RgbaImage, imageops::FilterType};
+
+/// The width and height of server icons
+pub const ICON_SIZE: u32 = 64;
+
+/// The icons looked for in the config directory when the config doesn't set `icon`, in order
+pub const DEFAULT_ICONS: [&str; 7] = [
+    "icon.png",
+    "icon.jpg",
+    "icon.jpeg",
+    "icon.gif",
+    "icon.webp",
+    "icon.bmp",
+    "icon.b64",
+];
+
+/// The extensions of files read as icons, like the images in an icon directory
+pub const EXTENSIONS: [&str; 7] = ["png", "jpg", "jpeg", "gif", "webp", "bmp", "b64"];
+
+#[derive(Debug)]
+pub enum IconError {
+    IOError(io::Error),
+    Base64Error(base64::DecodeError),
+    ImageError(ImageError),
+}
+
+impl fmt::Display for IconError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            IconError::IOError(e) => write!(f, "{}", e),
+            IconError::Base64Error(e) => write!(f, "invalid base64: {}", e),
+            IconError::ImageError(e) => write!(f, "invalid image: {}", e),
+        }
+    }
+}
+
+impl From<io::Error> for IconError {
+    fn from(value: io::Error) -> Self {
+        IconError::IOError(value)
+    }
+}
+
+impl From<base64::DecodeError> for IconError {
+    fn from(value: base64::DecodeError) -> Self {
+        IconError::Base64Error(value)
+    }
+}
+
+impl From<ImageError> for IconError {
+    fn from(value: ImageError) -> Self {
+        IconError::ImageError(value)
+    }
+}
+
+/// An image as a 64x64 png. Pngs are re-encoded even when they're already 64x64,
+/// so metadata chunks can't make the status response too long
+pub fn to_png(data: &[u8]) -> Result<Vec<u8>, IconError> {
+    let mut image = image::load_from_memory(data)?;
+    if image.width() != ICON_SIZE || image.height() != ICON_SIZE {
+        image = image.resize_exact(ICON_SIZE, ICON_SIZE, FilterType::Lanczos3);
+    }
+    let mut png = Vec::new();
+    image.write_to(&mut Cursor::new(&mut png), ImageFormat::Png)?;
+    Ok(png)
+}
+
+/// Reads an icon as a 64x64 png, from an image file or a `.b64` file with a base64 image
+pub fn read(path: &Path) -> Result<Vec<u8>, IconError> {
+    let data = fs::read(path)?;
+    if path.extension().is_some_and(|e| e == "b64") {
+        // `base64` splits its output into lines, which can't be in the data URL
+        let text: Vec<u8> = data.into_iter().filter(|b| !b.is_ascii_whitespace()).collect();
+        return to_png(&STANDARD.decode(text)?);
+    }
+    to_png(&data)
+}
+
+/// A png as base64, like it's sent to clients
+pub fn encode(png: &[u8]) -> String {
+    STANDARD.encode(png)
+}
+
+/// A base64 icon as an image to draw on
+pub fn decode(icon: &str) -> Result<RgbaImage, IconError> {
+    Ok(image::load_from_memory_with_format(&STANDARD.decode(icon)?, ImageFormat::Png)?.into_rgba8())
+}
+
+/// An image as a base64 png
+pub fn encode_image(image: &RgbaImage) -> Result<String, IconError> {
+    let mut png = Vec::new();
+    image.write_to(&mut Cursor::new(&mut png), ImageFormat::Png)?;
+    Ok(encode(&png))
+}
+
+/// Reads an icon as base64
+pub fn load(path: &Path) -> Result<String, IconError> {
+    Ok(encode(&read(path)?))
+}
+
+/// Whether a file has one of the icon [`EXTENSIONS`]
+pub fn is_icon_file(path: &Path) -> bool {
+    path.extension()
+        .and_then(|e| e.to_str())
+        .is_some_and(|e| EXTENSIONS.contains(&e.to_ascii_lowercase().as_str()))
+}
+
+/// The icons in a directory in name order, or just `path` if it isn't a directory.
+/// `path` is relative to `cfgdir`, and so are the icons
+pub fn files(cfgdir: &Path, path: &Path) -> io::Result<Vec<PathBuf>> {
+    let dir = cfgdir.join(path);
+    if !dir.is_dir() {
+        return Ok(vec![path.to_path_buf()]);
+    }
+    let mut files = Vec::new();
+    for entry in fs::read_dir(&dir)? {
+        let name = entry?.file_name();
+        let file = path.join(&name);
+        if is_icon_file(&file) && cfgdir.join(&file).is_file() {
+            files.push(file);
+        }
+    }
+    files.sort();
+    Ok(files)
+}
+
+/// Whether a file in the config directory is an icon used when the config doesn't set one
+pub fn is_default(path: &Path) -> bool {
+    DEFAULT_ICONS.iter().any(|icon| path.ends_with(icon))
+}
+
+#[cfg(test)]
+mod tests {
+    use image::{GenericImageView, Rgba};
+
+    use super::*;
+
+    fn png(width: u32, height: u32) -> Vec<u8> {
+        let image = RgbaImage::from_pixel(width, height, Rgba([255, 0, 0, 255]));
+        let mut png = Vec::new();
+        image.write_to(&mut Cursor::new(&mut png), ImageFormat::Png).unwrap();
+        png
+    }
+
+    /// A png with a tEXt chunk of `len` bytes before its IEND chunk
+    fn with_text_chunk(png: &[u8], len: usize) -> Vec<u8> {
+        let (image, iend) = png.split_at(png.len() - 12);
+        let mut data = b"tEXt".to_vec();
+        data.extend(b"Comment\0");
+        data.resize(len + 4, b'a');
+        let mut chunk = ((data.len() - 4) as u32).to_be_bytes().to_vec();
+        chunk.extend(&data);
+        // The CRC isn't checked when the image is decoded
+        chunk.extend(0u32.to_be_bytes());
+        [image, &chunk, iend].concat()
+    }
+
+    fn size(png: &[u8]) -> (u32, u32) {
+        image::load_from_memory(png).unwrap().dimensions()
+    }
+
+    #[test]
+    fn test_resize() {
+        let resized = to_png(&png(128, 32)).unwrap();
+        assert_eq!(size(&resized), (ICON_SIZE, ICON_SIZE));
+    }
+
+    #[test]
+    fn test_metadata_dropped() {
+        let icon = with_text_chunk(&png(64, 64), 40_000);
+        let png = to_png(&icon).unwrap();
+        assert_eq!(size(&png), (ICON_SIZE, ICON_SIZE));
+        assert!(png.len() < 1000);
+    }
+
+    #[test]
+    fn test_invalid() {
+        assert!(matches!(to_png(b"not an image"), Err(IconError::ImageError(_))));
+        let mut truncated = png(64, 64);
+        truncated.truncate(40);
+        assert!(matches!(to_png(&truncated), Err(IconError::ImageError(_))));
+    }
+
+    #[test]
+    fn test_read_b64() {
+        let base64 = encode(&png(32, 32));
+        // Wrapped at 76 columns, like `base64` does
+        let lines: Vec<&str> = base64
+            .as_bytes()
+            .chunks(76)
+            .map(|line| std::str::from_utf8(line).unwrap())
+            .collect();
+        let path = std::env::temp_dir().join(format!("statusserver-icon-{}.b64", std::process::id()));
+        fs::write(&path, lines.join("\n") + "\n").unwrap();
+        let png = read(&path);
+        fs::remove_file(&path).unwrap();
+        assert_eq!(size(&png.unwrap()), (ICON_SIZE, ICON_SIZE));
+    }
+
+    #[test]
+    fn test_is_icon_file() {
+        assert!(is_icon_file(Path::new("icons/a.png")));
+        assert!(is_icon_file(Path::new("icons/B.JPEG")));
+        assert!(is_icon_file(Path::new("icon.b64")));
+        assert!(!is_icon_file(Path::new("icons/notes.txt")));
+        assert!(!is_icon_file(Path::new("icons/png")));
+    }
+
+    #[test]
+    fn test_files() {
+        let cfgdir = std::env::temp_dir().join(format!("statusserver-icons-{}", std::process::id()));
+        let dir = cfgdir.join("icons");
+        fs::create_dir_all(dir.join("old.png")).unwrap();
+        fs::create_dir_all(cfgdir.join("empty")).unwrap();
+        for name in ["c.webp", "a.png", "notes.txt", "b.b64", "README"] {
+            fs::write(dir.join(name), "").unwrap();
+        }
+        let icons = files(&cfgdir, Path::new("icons"));
+        let single = files(&cfgdir, Path::new("icon.png"));
+        let empty = files(&cfgdir, Path::new("empty"));
+        fs::remove_dir_all(&cfgdir).unwrap();
+        let expected: Vec<PathBuf> = ["icons/a.png", "icons/b.b64", "icons/c.webp"].map(PathBuf::from).into();
+        assert_eq!(icons.unwrap(), expected);
+        assert_eq!(single.unwrap(), vec![PathBuf::from("icon.png")]);
+        assert!(empty.unwrap().is_empty());
+    }
+}
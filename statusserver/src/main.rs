@@ -1,4 +1,4 @@
-use clap::Parser;
+use clap::{Parser, Subcommand};
 use env_logger::Env;
 use log::{debug, error, info, warn};
 
@@ -6,38 +6,74 @@ use std::{
     fs, io,
     net::{TcpListener, TcpStream},
     path::{Path, PathBuf},
-    sync::{PoisonError, RwLock, RwLockReadGuard, mpsc::{self, Receiver}},
+    sync::{Arc, PoisonError, RwLock, RwLockReadGuard, mpsc::{self, Receiver}},
     thread,
     time::Duration,
 };
 
+use chrono::Utc;
 use lazy_static::lazy_static;
+use mcproto::ProtocolVersion;
+use mctext::{TextComponent, TextFormat};
 use notify::{
     Event, EventKind, INotifyWatcher, RecursiveMode, Watcher, event::{AccessKind, AccessMode}
 };
 
 use crate::{
+    config::ConfigLoadingError,
+    frames::Frames,
+    hosts::ConfigSet,
     packets::{PacketError, ServerConfig, ServerInfo},
     player::Player,
 };
 
+pub mod cache;
+pub mod config;
+pub mod forge;
+pub mod frames;
+pub mod hosts;
+pub mod icon;
+pub mod overlay;
+pub mod overrides;
 pub mod packets;
+pub mod placeholders;
 pub mod player;
+pub mod profiles;
+pub mod status;
+pub mod status_file;
 
 lazy_static! {
-    static ref server_info: RwLock<ServerInfo> = ServerInfo {
-        config: ServerConfig {
-            version: String::from("custom"),
-            protocol: Some(127),
+    /// Connections keep the info they started with, so reloading doesn't wait for them
+    static ref server_info: RwLock<Arc<ServerInfo>> = {
+        let config = ServerConfig {
+            format: TextFormat::Legacy,
+            version: Frames::from(String::from("custom")),
+            protocol: Some(ProtocolVersion(127)),
             online_players: 0,
             max_players: 0,
+            raw_text: Default::default(),
             player_list: vec![],
-            motd: String::from("A status server"),
-            kick_message: String::from("Just a status server")
-        },
-        icon: None,
-    }
-    .into();
+            motd: Frames::from(TextComponent::from("A status server")),
+            motd_lines: None,
+            kick_message: TextComponent::from("Just a status server"),
+            object_fallback: Default::default(),
+            icon: None,
+            loaded_icon: None,
+            icon_overlay: None,
+            enforces_secure_chat: false,
+            prevents_chat_reports: false,
+            previews_chat: false,
+            forge: None,
+            status_json: Default::default(),
+            version_overrides: vec![],
+            status_cache: Default::default(),
+        };
+        let default = ConfigSet {
+            base: config,
+            profiles: vec![],
+        };
+        RwLock::new(Arc::new(ServerInfo::new(default, vec![], vec![], None)))
+    };
 }
 
 enum ClientError {
@@ -68,8 +104,8 @@ impl From<io::Error> for ClientError {
     }
 }
 
-impl From<PoisonError<RwLockReadGuard<'_, ServerInfo>>> for ClientError {
-    fn from(_: PoisonError<RwLockReadGuard<'_, ServerInfo>>) -> Self {
+impl From<PoisonError<RwLockReadGuard<'_, Arc<ServerInfo>>>> for ClientError {
+    fn from(_: PoisonError<RwLockReadGuard<'_, Arc<ServerInfo>>>) -> Self {
         ClientError::InfoUnlock
     }
 }
@@ -79,7 +115,7 @@ fn handle_client(stream: TcpStream) -> Result<(), ClientError> {
     stream.set_write_timeout(Some(Duration::from_secs(5)))?;
     let mut player = Player::new(stream);
     info!("Player {} connected!", player.addr);
-    let info = &server_info.read()?;
+    let info = server_info.read()?.clone();
     loop {
         let state = player.receive_packet(&info);
         match state {
@@ -98,50 +134,45 @@ fn handle_client(stream: TcpStream) -> Result<(), ClientError> {
     }
 }
 
-fn load_icon(icon_path: &Path) -> io::Result<()>{
-    let icon: Option<String> = Some(fs::read_to_string(icon_path)?);
-    {
-        let mut cfg = server_info.write().unwrap();
-        cfg.icon = icon;
-    }
-    Ok(())
-}
-
-#[derive(Debug)]
-enum ConfigLoadingError {
-    IOError(io::Error),
-    ConfigError(toml::de::Error)
-}
+/// How often the server checks whether a profile started or ended
+const PROFILE_CHECK_INTERVAL: Duration = Duration::from_secs(1);
 
-impl std::fmt::Display for ConfigLoadingError {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        match &self {
-            &ConfigLoadingError::IOError(e) => write!(f, "{}", e),
-            &ConfigLoadingError::ConfigError(e) => write!(f, "{}", e)
-        }
-    }
+/// Whether a changed file is one of the icons, or in one of the icon directories
+fn is_icon(info: &ServerInfo, path: &Path) -> bool {
+    info.configs().any(|config| match &config.icon {
+        Some(paths) => paths.iter().any(|p| {
+            path.ends_with(p) || path.parent().is_some_and(|dir| dir.ends_with(p) && icon::is_icon_file(path))
+        }),
+        None => icon::is_default(path),
+    })
 }
 
-impl From<io::Error> for ConfigLoadingError {
-    fn from(value: io::Error) -> Self {
-        ConfigLoadingError::IOError(value)
-    }
+fn load_config(config_path: &Path) -> Result<(), ConfigLoadingError> {
+    let loaded = config::load(config_path)?;
+    let info = ServerInfo::new(loaded.default, loaded.hosts, loaded.profiles, loaded.status_file);
+    let mut current = server_info.write().unwrap();
+    update_profile(&info, current.active_name());
+    *current = Arc::new(info);
+    Ok(())
 }
 
-impl From<toml::de::Error> for ConfigLoadingError {
-    fn from(value: toml::de::Error) -> Self {
-        ConfigLoadingError::ConfigError(value)
+/// Uses the profile that's active now, logging if it isn't the `previous` one
+fn update_profile(info: &ServerInfo, previous: Option<&str>) {
+    info.set_active(profiles::active(&info.profiles, Utc::now()));
+    match (previous, info.active_name()) {
+        (None, Some(new)) => info!("Profile '{}' is now active", new),
+        (Some(old), None) => info!("Profile '{}' ended, using the base config", old),
+        (Some(old), Some(new)) if old != new => {
+            info!("Profile '{}' ended, profile '{}' is now active", old, new)
+        }
+        _ => {}
     }
 }
 
-fn load_config(config_path: &Path) -> Result<(), ConfigLoadingError> {
-    let text = &fs::read_to_string(config_path)?;
-    let new_cfg = toml::from_str::<ServerConfig>(text)?;
-    {
-        let mut cfg = server_info.write().unwrap();
-        cfg.config = new_cfg;
-    }
-    Ok(())
+/// Swaps in the profile that's active now if it changed
+fn check_profiles() {
+    let info = server_info.read().unwrap().clone();
+    update_profile(&info, info.active_name());
 }
 
 #[derive(Parser)]
@@ -152,6 +183,37 @@ struct CommandArgs {
     ip: String,
     #[arg(short, long, default_value = "./config")]
     cfgdir: PathBuf,
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Server icon tools
+    Icon {
+        #[command(subcommand)]
+        command: IconCommand,
+    },
+}
+
+#[derive(Subcommand)]
+enum IconCommand {
+    /// Converts an image to a 64x64 png, written as base64 unless the output ends in .png
+    Convert {
+        input: PathBuf,
+        #[arg(default_value = "icon.b64")]
+        output: PathBuf,
+    },
+}
+
+fn convert_icon(input: &Path, output: &Path) -> Result<(), icon::IconError> {
+    let png = icon::read(input)?;
+    if output.extension().is_some_and(|e| e == "png") {
+        fs::write(output, png)?;
+    } else {
+        fs::write(output, icon::encode(&png))?;
+    }
+    Ok(())
 }
 
 fn main() {
@@ -159,6 +221,13 @@ fn main() {
     env_logger::Builder::from_env(env).init();
 
     let args = CommandArgs::parse();
+    if let Some(Command::Icon { command: IconCommand::Convert { input, output } }) = &args.command {
+        match convert_icon(input, output) {
+            Ok(_) => info!("Wrote {} as a 64x64 icon to {}", input.display(), output.display()),
+            Err(e) => error!("Couldn't convert {}: {}", input.display(), e),
+        }
+        return;
+    }
     let c = args.cfgdir.display();
     info!("Using '{c}' as config dir");
     let config_path = {
@@ -166,46 +235,56 @@ fn main() {
         c.push("config.toml");
         c
     };
-    let icon_path = {
-        let mut c = args.cfgdir.clone();
-        c.push("icon.b64");
-        c
-    };
     match load_config(&config_path) {
         Ok(_) => { info!("Loaded config {}", config_path.display()); },
         Err(e) => { error!("Error loading config! {}", e); return; }
     }
-    match load_icon(&icon_path) {
-        Ok(_) => { info!("Loaded icon {}", icon_path.display()); },
-        Err(e) => { debug!("Error loading icon! {}", e); }
-    }
     {
         let info = server_info.read();
         match info {
             Ok(info) => {
                 info!("Config has been loaded:");
+                let config = info.config(None);
                 info!(
                     "Players: {}/{}",
-                    info.config.online_players, info.config.max_players
+                    config.online_players, config.max_players
                 );
-                for i in &info.config.player_list {
+                for i in &config.player_list {
                     info!("- {}", i.name);
                 }
-                info!(
-                    "Version {}, Protocol {}",
-                    info.config.version,
-                    match info.config.protocol {
-                        Some(p) => &p.to_string(),
-                        None => "same as player",
-                    }
-                );
-                info!("Motd: '{}'", info.config.motd);
-                info!("Kick message: '{}'", info.config.kick_message);
-                if let Some(_) = info.icon {
-                    info!("Icon was loaded");
+                let protocol = match config.protocol {
+                    Some(p) => p.describe(),
+                    None => String::from("same as player"),
+                };
+                for (label, version) in config.version.labeled("version") {
+                    info!("{}: {}", label, version);
+                }
+                info!("Protocol {}", protocol);
+                for (label, motd) in config.motd_frames(None) {
+                    info!("{}: '{}'", label, motd);
+                }
+                info!("Kick message: '{}'", config.kick_message);
+                for version_override in &config.version_overrides {
+                    info!("Version override for protocols {}", version_override.protocols);
+                }
+                for host in &info.hosts {
+                    let hostnames: Vec<String> = host.hostnames.iter().map(|h| h.to_string()).collect();
+                    info!("Host {}", hostnames.join(", "));
+                }
+                for profile in &info.profiles {
+                    info!("Profile '{}'", profile.name);
+                }
+                if let Some(icon) = &config.loaded_icon {
+                    info!("{} icon(s) loaded", icon.iter().count());
                 } else {
                     info!("No icon loaded");
                 }
+                if config.icon_overlay.is_some() {
+                    info!("Drawing an overlay on the icon");
+                }
+                if info.status_file.is_some() {
+                    info!("Sending {} as the status response", status_file::STATUS_FILE);
+                }
             }
             Err(_) => {
                 error!("Couldn't unlock server info for reading");
@@ -247,6 +326,12 @@ fn main() {
     info!("Listening on {}", args.ip);
 
     thread::scope(move |s| {
+        s.spawn(move || {
+            loop {
+                thread::sleep(PROFILE_CHECK_INTERVAL);
+                check_profiles();
+            }
+        });
         s.spawn(move || {
             for client in listener.incoming() {
                 match client {
@@ -281,12 +366,31 @@ fn main() {
                                             Err(e) => { error!("Couldn't reload config! {}", e); }
                                         }
                                         break;
-                                    } else if i.ends_with("icon.b64") {
-                                        match load_icon(&i) {
+                                    } else if is_icon(&server_info.read().unwrap(), &i) {
+                                        // Icons are read with the config
+                                        match load_config(&config_path) {
                                             Ok(_) => { info!("Reloaded icon"); }
                                             Err(e) => { error!("Couldn't reload icon! {}", e); }
                                         }
                                         break;
+                                    } else if i.ends_with(status_file::STATUS_FILE) {
+                                        match load_config(&config_path) {
+                                            Ok(_) => { info!("Reloaded {}", status_file::STATUS_FILE); }
+                                            Err(e) => { error!("Couldn't reload {}! {}", status_file::STATUS_FILE, e); }
+                                        }
+                                        break;
+                                    }
+                                }
+                            } else if let EventKind::Remove(_) = event.kind {
+                                // Removing status.json goes back to the config's response,
+                                // and removing an icon from a directory takes it out of the rotation
+                                let info = server_info.read().unwrap();
+                                let changed = event.paths.iter().any(|i| i.ends_with(status_file::STATUS_FILE) || is_icon(&info, i));
+                                drop(info);
+                                if changed {
+                                    match load_config(&config_path) {
+                                        Ok(_) => { info!("Reloaded config"); }
+                                        Err(e) => { error!("Couldn't reload config! {}", e); }
                                     }
                                 }
                             }
@@ -303,3 +407,36 @@ fn main() {
         w.unwatch(&args.cfgdir).ok();
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::{frames::Rotation, hosts::VirtualHost};
+
+    use super::*;
+
+    fn configs(config: ServerConfig) -> ConfigSet {
+        ConfigSet {
+            base: config,
+            profiles: vec![],
+        }
+    }
+
+    #[test]
+    fn test_is_icon() {
+        let mut config = ServerConfig::for_test("§aicons");
+        config.icon = Frames::new(vec![PathBuf::from("icons"), PathBuf::from("logo.png")], Rotation::Sequential);
+        let mut info = ServerInfo::new(configs(config), vec![], vec![], None);
+        assert!(is_icon(&info, Path::new("/srv/config/logo.png")));
+        assert!(is_icon(&info, Path::new("/srv/config/icons/a.png")));
+        assert!(!is_icon(&info, Path::new("/srv/config/icons/notes.txt")));
+        assert!(!is_icon(&info, Path::new("/srv/config/icon.png")));
+
+        // A host without `icon` uses the default icons
+        info.hosts.push(VirtualHost {
+            hostnames: vec![],
+            configs: configs(ServerConfig::for_test("§adefault")),
+        });
+        assert!(is_icon(&info, Path::new("/srv/config/icon.png")));
+        assert!(!is_icon(&info, Path::new("/srv/config/other.png")));
+    }
+}
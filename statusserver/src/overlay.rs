@@ -0,0 +1,399 @@
+use std::sync::RwLock;
+
+use image::{Rgba, RgbaImage};
+use serde::{Deserialize, Deserializer, de};
+
+use crate::{
+    cache::BoundedCache,
+    icon::{self, ICON_SIZE, IconError},
+};
+
+/// How many rendered icons an overlay keeps, so text like `{time}` doesn't fill the memory.
+/// The oldest ones are dropped to make room for new ones
+const CACHE_LIMIT: usize = 64;
+
+const GLYPH_WIDTH: u32 = 5;
+const GLYPH_HEIGHT: u32 = 7;
+/// The space around the text inside its badge
+const BADGE_PADDING: u32 = 1;
+const DOT_RADIUS: i32 = 5;
+
+/// The most characters that fit in a badge across the icon
+pub const MAX_TEXT_LEN: usize = ((ICON_SIZE - 2 * BADGE_PADDING + 1) / (GLYPH_WIDTH + 1)) as usize;
+
+/// A 5x7 font, with the leftmost pixel of each row in the 5th bit
+const FONT: &[(char, [u8; 7])] = &[
+    (' ', [0b00000, 0b00000, 0b00000, 0b00000, 0b00000, 0b00000, 0b00000]),
+    ('0', [0b01110, 0b10001, 0b10011, 0b10101, 0b11001, 0b10001, 0b01110]),
+    ('1', [0b00100, 0b01100, 0b00100, 0b00100, 0b00100, 0b00100, 0b01110]),
+    ('2', [0b01110, 0b10001, 0b00001, 0b00010, 0b00100, 0b01000, 0b11111]),
+    ('3', [0b11111, 0b00010, 0b00100, 0b00010, 0b00001, 0b10001, 0b01110]),
+    ('4', [0b00010, 0b00110, 0b01010, 0b10010, 0b11111, 0b00010, 0b00010]),
+    ('5', [0b11111, 0b10000, 0b11110, 0b00001, 0b00001, 0b10001, 0b01110]),
+    ('6', [0b00110, 0b01000, 0b10000, 0b11110, 0b10001, 0b10001, 0b01110]),
+    ('7', [0b11111, 0b00001, 0b00010, 0b00100, 0b01000, 0b01000, 0b01000]),
+    ('8', [0b01110, 0b10001, 0b10001, 0b01110, 0b10001, 0b10001, 0b01110]),
+    ('9', [0b01110, 0b10001, 0b10001, 0b01111, 0b00001, 0b00010, 0b01100]),
+    ('A', [0b01110, 0b10001, 0b10001, 0b11111, 0b10001, 0b10001, 0b10001]),
+    ('B', [0b11110, 0b10001, 0b10001, 0b11110, 0b10001, 0b10001, 0b11110]),
+    ('C', [0b01110, 0b10001, 0b10000, 0b10000, 0b10000, 0b10001, 0b01110]),
+    ('D', [0b11100, 0b10010, 0b10001, 0b10001, 0b10001, 0b10010, 0b11100]),
+    ('E', [0b11111, 0b10000, 0b10000, 0b11110, 0b10000, 0b10000, 0b11111]),
+    ('F', [0b11111, 0b10000, 0b10000, 0b11110, 0b10000, 0b10000, 0b10000]),
+    ('G', [0b01110, 0b10001, 0b10000, 0b10111, 0b10001, 0b10001, 0b01111]),
+    ('H', [0b10001, 0b10001, 0b10001, 0b11111, 0b10001, 0b10001, 0b10001]),
+    ('I', [0b01110, 0b00100, 0b00100, 0b00100, 0b00100, 0b00100, 0b01110]),
+    ('J', [0b00111, 0b00010, 0b00010, 0b00010, 0b00010, 0b10010, 0b01100]),
+    ('K', [0b10001, 0b10010, 0b10100, 0b11000, 0b10100, 0b10010, 0b10001]),
+    ('L', [0b10000, 0b10000, 0b10000, 0b10000, 0b10000, 0b10000, 0b11111]),
+    ('M', [0b10001, 0b11011, 0b10101, 0b10101, 0b10001, 0b10001, 0b10001]),
+    ('N', [0b10001, 0b10001, 0b11001, 0b10101, 0b10011, 0b10001, 0b10001]),
+    ('O', [0b01110, 0b10001, 0b10001, 0b10001, 0b10001, 0b10001, 0b01110]),
+    ('P', [0b11110, 0b10001, 0b10001, 0b11110, 0b10000, 0b10000, 0b10000]),
+    ('Q', [0b01110, 0b10001, 0b10001, 0b10001, 0b10101, 0b10010, 0b01101]),
+    ('R', [0b11110, 0b10001, 0b10001, 0b11110, 0b10100, 0b10010, 0b10001]),
+    ('S', [0b01111, 0b10000, 0b10000, 0b01110, 0b00001, 0b00001, 0b11110]),
+    ('T', [0b11111, 0b00100, 0b00100, 0b00100, 0b00100, 0b00100, 0b00100]),
+    ('U', [0b10001, 0b10001, 0b10001, 0b10001, 0b10001, 0b10001, 0b01110]),
+    ('V', [0b10001, 0b10001, 0b10001, 0b10001, 0b10001, 0b01010, 0b00100]),
+    ('W', [0b10001, 0b10001, 0b10001, 0b10101, 0b10101, 0b10101, 0b01010]),
+    ('X', [0b10001, 0b10001, 0b01010, 0b00100, 0b01010, 0b10001, 0b10001]),
+    ('Y', [0b10001, 0b10001, 0b10001, 0b01010, 0b00100, 0b00100, 0b00100]),
+    ('Z', [0b11111, 0b00001, 0b00010, 0b00100, 0b01000, 0b10000, 0b11111]),
+    ('/', [0b00000, 0b00001, 0b00010, 0b00100, 0b01000, 0b10000, 0b00000]),
+    (':', [0b00000, 0b01100, 0b01100, 0b00000, 0b01100, 0b01100, 0b00000]),
+    ('.', [0b00000, 0b00000, 0b00000, 0b00000, 0b00000, 0b01100, 0b01100]),
+    (',', [0b00000, 0b00000, 0b00000, 0b00000, 0b01100, 0b00100, 0b01000]),
+    ('-', [0b00000, 0b00000, 0b00000, 0b11111, 0b00000, 0b00000, 0b00000]),
+    ('+', [0b00000, 0b00100, 0b00100, 0b11111, 0b00100, 0b00100, 0b00000]),
+    ('=', [0b00000, 0b00000, 0b11111, 0b00000, 0b11111, 0b00000, 0b00000]),
+    ('!', [0b00100, 0b00100, 0b00100, 0b00100, 0b00100, 0b00000, 0b00100]),
+    ('?', [0b01110, 0b10001, 0b00001, 0b00010, 0b00100, 0b00000, 0b00100]),
+    ('%', [0b11000, 0b11001, 0b00010, 0b00100, 0b01000, 0b10011, 0b00011]),
+    ('#', [0b01010, 0b01010, 0b11111, 0b01010, 0b11111, 0b01010, 0b01010]),
+    ('*', [0b00000, 0b00100, 0b10101, 0b01110, 0b10101, 0b00100, 0b00000]),
+    ('(', [0b00010, 0b00100, 0b01000, 0b01000, 0b01000, 0b00100, 0b00010]),
+    (')', [0b01000, 0b00100, 0b00010, 0b00010, 0b00010, 0b00100, 0b01000]),
+    ('<', [0b00010, 0b00100, 0b01000, 0b10000, 0b01000, 0b00100, 0b00010]),
+    ('>', [0b01000, 0b00100, 0b00010, 0b00001, 0b00010, 0b00100, 0b01000]),
+    ('\'', [0b01100, 0b00100, 0b01000, 0b00000, 0b00000, 0b00000, 0b00000]),
+];
+
+/// The rows of a character, with lowercase letters drawn as uppercase and unknown ones as `?`
+fn glyph(c: char) -> &'static [u8; 7] {
+    let c = c.to_ascii_uppercase();
+    let find = |c| FONT.iter().find(|(g, _)| *g == c).map(|(_, rows)| rows);
+    find(c).or_else(|| find('?')).expect("the font has '?'")
+}
+
+/// A color written as `#rrggbb` or `#rrggbbaa`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Color(pub Rgba<u8>);
+
+impl Color {
+    pub fn parse(text: &str) -> Option<Self> {
+        let hex = text.strip_prefix('#')?;
+        if !matches!(hex.len(), 6 | 8) || !hex.is_ascii() {
+            return None;
+        }
+        let byte = |i: usize| u8::from_str_radix(&hex[i..i + 2], 16).ok();
+        let alpha = if hex.len() == 8 { byte(6)? } else { 255 };
+        Some(Color(Rgba([byte(0)?, byte(2)?, byte(4)?, alpha])))
+    }
+}
+
+impl<'de> Deserialize<'de> for Color {
+    fn deserialize<D: Deserializer<'de>>(d: D) -> Result<Self, D::Error> {
+        let text = String::deserialize(d)?;
+        Color::parse(&text)
+            .ok_or_else(|| de::Error::custom(format!("invalid color '{}', expected '#rrggbb' or '#rrggbbaa'", text)))
+    }
+}
+
+/// A corner of the icon
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+pub enum Corner {
+    TopLeft,
+    TopRight,
+    BottomLeft,
+    BottomRight,
+}
+
+impl Corner {
+    /// Where a `width`x`height` box goes in this corner
+    fn place(self, width: u32, height: u32) -> (i32, i32) {
+        let right = ICON_SIZE.saturating_sub(width) as i32;
+        let bottom = ICON_SIZE.saturating_sub(height) as i32;
+        match self {
+            Corner::TopLeft => (0, 0),
+            Corner::TopRight => (right, 0),
+            Corner::BottomLeft => (0, bottom),
+            Corner::BottomRight => (right, bottom),
+        }
+    }
+}
+
+/// The color of the status dot
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[serde(rename_all = "lowercase")]
+pub enum Dot {
+    Green,
+    Amber,
+    Red,
+    /// Green, amber when the server is at least 3/4 full and red when it's full
+    Auto,
+}
+
+impl Dot {
+    /// The color for the player counts, with [`Dot::Auto`] replaced
+    pub fn resolve(self, online: i32, max: i32) -> Dot {
+        match self {
+            Dot::Auto if online >= max => Dot::Red,
+            // Widened, since fake player counts can be close to i32::MAX
+            Dot::Auto if online as i64 * 4 >= max as i64 * 3 => Dot::Amber,
+            Dot::Auto => Dot::Green,
+            dot => dot,
+        }
+    }
+
+    fn color(self) -> Rgba<u8> {
+        match self {
+            Dot::Green => Rgba([0x3c, 0xd0, 0x3c, 0xff]),
+            Dot::Amber | Dot::Auto => Rgba([0xff, 0xb0, 0x00, 0xff]),
+            Dot::Red => Rgba([0xe0, 0x30, 0x30, 0xff]),
+        }
+    }
+}
+
+fn default_text_position() -> Corner {
+    Corner::BottomRight
+}
+
+fn default_dot_position() -> Corner {
+    Corner::TopRight
+}
+
+fn default_text_color() -> Color {
+    Color(Rgba([0xff, 0xff, 0xff, 0xff]))
+}
+
+fn default_background() -> Color {
+    Color(Rgba([0x00, 0x00, 0x00, 0xb0]))
+}
+
+/// Everything a rendered icon depends on
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct OverlayKey {
+    /// The index of the icon frame
+    frame: usize,
+    text: String,
+    dot: Option<Dot>,
+}
+
+/// Text and a status dot drawn over the icon for each request
+#[derive(Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct IconOverlay {
+    /// Drawn in a badge, with placeholders
+    pub text: Option<String>,
+    #[serde(default = "default_text_position")]
+    pub text_position: Corner,
+    #[serde(default = "default_text_color")]
+    pub text_color: Color,
+    /// The badge's color
+    #[serde(default = "default_background")]
+    pub background: Color,
+    pub dot: Option<Dot>,
+    #[serde(default = "default_dot_position")]
+    pub dot_position: Corner,
+    /// The frames of the icon it's drawn on
+    #[serde(skip)]
+    pub base: Vec<RgbaImage>,
+    /// The icons already rendered
+    #[serde(skip)]
+    cache: RwLock<BoundedCache<OverlayKey, String, CACHE_LIMIT>>,
+}
+
+/// Draws `color` over a pixel, blending by its alpha
+fn blend(image: &mut RgbaImage, x: i32, y: i32, color: Rgba<u8>) {
+    if x < 0 || y < 0 || x >= image.width() as i32 || y >= image.height() as i32 {
+        return;
+    }
+    let pixel = image.get_pixel_mut(x as u32, y as u32);
+    let alpha = color.0[3] as u32;
+    let dst_alpha = pixel.0[3] as u32 * (255 - alpha) / 255;
+    let out_alpha = alpha + dst_alpha;
+    if out_alpha == 0 {
+        return;
+    }
+    for i in 0..3 {
+        let c = (color.0[i] as u32 * alpha + pixel.0[i] as u32 * dst_alpha) / out_alpha;
+        pixel.0[i] = c as u8;
+    }
+    pixel.0[3] = out_alpha as u8;
+}
+
+impl IconOverlay {
+    /// The icon frame at index `frame` with the overlay, as base64
+    pub fn render(&self, frame: usize, text: Option<&str>, dot: Option<Dot>) -> Result<String, IconError> {
+        let text: String = text
+            .map(|t| mctext::strip_formatting(t).chars().take(MAX_TEXT_LEN).collect())
+            .unwrap_or_default();
+        let key = OverlayKey { frame, text, dot };
+        if let Some(icon) = self.cache.read().unwrap().get(&key) {
+            return Ok(icon.clone());
+        }
+        let mut image = match self.base.get(frame) {
+            Some(base) => base.clone(),
+            None => RgbaImage::new(ICON_SIZE, ICON_SIZE),
+        };
+        if !key.text.is_empty() {
+            self.draw_badge(&mut image, &key.text);
+        }
+        if let Some(dot) = dot {
+            self.draw_dot(&mut image, dot);
+        }
+        let icon = icon::encode_image(&image)?;
+        self.cache.write().unwrap().insert(key, icon.clone());
+        Ok(icon)
+    }
+
+    fn draw_badge(&self, image: &mut RgbaImage, text: &str) {
+        let len = text.chars().count() as u32;
+        let width = len * (GLYPH_WIDTH + 1) - 1 + 2 * BADGE_PADDING;
+        let height = GLYPH_HEIGHT + 2 * BADGE_PADDING;
+        let (left, top) = self.text_position.place(width, height);
+        for y in 0..height as i32 {
+            for x in 0..width as i32 {
+                blend(image, left + x, top + y, self.background.0);
+            }
+        }
+        let top = top + BADGE_PADDING as i32;
+        for (i, c) in text.chars().enumerate() {
+            let left = left + BADGE_PADDING as i32 + (i as u32 * (GLYPH_WIDTH + 1)) as i32;
+            for (y, row) in glyph(c).iter().enumerate() {
+                for x in 0..GLYPH_WIDTH {
+                    if row & (1 << (GLYPH_WIDTH - 1 - x)) != 0 {
+                        blend(image, left + x as i32, top + y as i32, self.text_color.0);
+                    }
+                }
+            }
+        }
+    }
+
+    fn draw_dot(&self, image: &mut RgbaImage, dot: Dot) {
+        let size = (DOT_RADIUS * 2 + 1) as u32;
+        let (left, top) = self.dot_position.place(size, size);
+        let outline = Rgba([0x00, 0x00, 0x00, 0xc0]);
+        for y in -DOT_RADIUS..=DOT_RADIUS {
+            for x in -DOT_RADIUS..=DOT_RADIUS {
+                let distance = x * x + y * y;
+                let color = if distance <= (DOT_RADIUS - 1) * (DOT_RADIUS - 1) {
+                    dot.color()
+                } else if distance <= DOT_RADIUS * DOT_RADIUS {
+                    outline
+                } else {
+                    continue;
+                };
+                blend(image, left + DOT_RADIUS + x, top + DOT_RADIUS + y, color);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const GRAY: Rgba<u8> = Rgba([0x80, 0x80, 0x80, 0xff]);
+
+    fn overlay(text: &str) -> IconOverlay {
+        let mut overlay: IconOverlay = toml::from_str(text).unwrap();
+        overlay.base = vec![RgbaImage::from_pixel(ICON_SIZE, ICON_SIZE, GRAY)];
+        overlay
+    }
+
+    fn render(overlay: &IconOverlay, text: Option<&str>, dot: Option<Dot>) -> RgbaImage {
+        icon::decode(&overlay.render(0, text, dot).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn test_color() {
+        assert_eq!(Color::parse("#ff8000"), Some(Color(Rgba([0xff, 0x80, 0x00, 0xff]))));
+        assert_eq!(Color::parse("#FF800040"), Some(Color(Rgba([0xff, 0x80, 0x00, 0x40]))));
+        assert_eq!(Color::parse("ff8000"), None);
+        assert_eq!(Color::parse("#ff80"), None);
+        assert_eq!(Color::parse("#ff80000"), None);
+        assert_eq!(Color::parse("#gg8000"), None);
+        assert_eq!(Color::parse("#ééé"), None);
+        assert_eq!(Color::parse("#éééé"), None);
+    }
+
+    #[test]
+    fn test_dot() {
+        assert_eq!(Dot::Auto.resolve(0, 20), Dot::Green);
+        assert_eq!(Dot::Auto.resolve(14, 20), Dot::Green);
+        assert_eq!(Dot::Auto.resolve(15, 20), Dot::Amber);
+        assert_eq!(Dot::Auto.resolve(20, 20), Dot::Red);
+        assert_eq!(Dot::Auto.resolve(25, 20), Dot::Red);
+        assert_eq!(Dot::Auto.resolve(0, 0), Dot::Red);
+        assert_eq!(Dot::Auto.resolve(1_000_000_000, 2_000_000_000), Dot::Green);
+        assert_eq!(Dot::Auto.resolve(i32::MAX - 1, i32::MAX), Dot::Amber);
+        assert_eq!(Dot::Green.resolve(20, 20), Dot::Green);
+    }
+
+    #[test]
+    fn test_badge() {
+        let overlay = overlay("text = '{online}'\ntext_position = 'top-left'");
+        let image = render(&overlay, Some("42"), None);
+        let changed = |x0: u32, y0: u32| {
+            (y0..y0 + 16).any(|y| (x0..x0 + 16).any(|x| *image.get_pixel(x, y) != GRAY))
+        };
+        assert!(changed(0, 0));
+        assert!(!changed(ICON_SIZE - 16, 0));
+        assert!(!changed(0, ICON_SIZE - 16));
+        assert!(!changed(ICON_SIZE - 16, ICON_SIZE - 16));
+    }
+
+    #[test]
+    fn test_dot_position() {
+        let overlay = overlay("dot = 'auto'\ndot_position = 'bottom-left'");
+        let image = render(&overlay, None, Some(Dot::Red));
+        let center = ICON_SIZE - 1 - DOT_RADIUS as u32;
+        assert_eq!(*image.get_pixel(DOT_RADIUS as u32, center), Dot::Red.color());
+        assert_eq!(*image.get_pixel(center, DOT_RADIUS as u32), GRAY);
+    }
+
+    #[test]
+    fn test_cache() {
+        let overlay = overlay("text = '{online}'");
+        let first = overlay.render(0, Some("1"), None).unwrap();
+        assert_eq!(overlay.cache.read().unwrap().len(), 1);
+        assert_eq!(overlay.render(0, Some("1"), None).unwrap(), first);
+        assert_eq!(overlay.cache.read().unwrap().len(), 1);
+        assert_ne!(overlay.render(0, Some("2"), None).unwrap(), first);
+        overlay.render(0, Some("1"), Some(Dot::Green)).unwrap();
+        assert_eq!(overlay.cache.read().unwrap().len(), 3);
+        // Cached icons aren't drawn again
+        let key = OverlayKey {
+            frame: 0,
+            text: String::from("7"),
+            dot: None,
+        };
+        overlay.cache.write().unwrap().insert(key, String::from("cached"));
+        assert_eq!(overlay.render(0, Some("§a7"), None).unwrap(), "cached");
+        // The oldest icons are dropped when it's full
+        for i in 0..CACHE_LIMIT {
+            overlay.render(0, Some(&(i + 100).to_string()), None).unwrap();
+        }
+        assert_eq!(overlay.cache.read().unwrap().len(), CACHE_LIMIT);
+        assert_ne!(overlay.render(0, Some("7"), None).unwrap(), "cached");
+        let newest = OverlayKey {
+            frame: 0,
+            text: (CACHE_LIMIT + 99).to_string(),
+            dot: None,
+        };
+        assert!(overlay.cache.read().unwrap().get(&newest).is_some());
+    }
+}
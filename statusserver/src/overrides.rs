@@ -0,0 +1,74 @@
+use mcproto::{ProtocolRange, ProtocolVersion};
+use serde::Deserialize;
+
+use crate::packets::ServerConfig;
+
+/// The keys of a version override that say which protocols it's for
+pub const OVERRIDE_KEYS: [&str; 1] = ["protocols"];
+
+/// The fields a version override can replace
+pub const OVERRIDE_FIELDS: [&str; 5] = ["version", "protocol", "motd", "motd_lines", "kick_message"];
+
+/// The keys of a version override in [`OVERRIDE_KEYS`]
+#[derive(Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct OverrideHeader {
+    pub protocols: ProtocolRange,
+}
+
+/// A config used instead of another one for clients with some protocols
+#[derive(Debug)]
+pub struct VersionOverride {
+    pub protocols: ProtocolRange,
+    pub config: ServerConfig,
+}
+
+impl VersionOverride {
+    pub fn matches(&self, protocol: ProtocolVersion) -> bool {
+        self.protocols.contains(protocol)
+    }
+
+    /// Clients from before 1.7 are older than every protocol, so they match ranges without a start
+    pub fn matches_legacy(&self) -> bool {
+        self.protocols.start == std::ops::Bound::Unbounded
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(overrides: &[(&str, &str)]) -> ServerConfig {
+        let mut config = ServerConfig::for_test("§abase");
+        config.version_overrides = overrides
+            .iter()
+            .map(|(protocols, motd)| VersionOverride {
+                protocols: ProtocolRange::parse(protocols).unwrap(),
+                config: ServerConfig::for_test(motd),
+            })
+            .collect();
+        config
+    }
+
+    fn motd(config: &ServerConfig) -> String {
+        config.motd.get(0).to_string()
+    }
+
+    #[test]
+    fn test_first_match() {
+        let config = config(&[("..=700", "§aold"), ("600..=800", "§amid"), ("0..=1000", "§aall")]);
+        let motd = |protocol| motd(config.for_protocol(ProtocolVersion(protocol)));
+        assert_eq!(motd(650), "§aold");
+        assert_eq!(motd(750), "§amid");
+        assert_eq!(motd(900), "§aall");
+        assert_eq!(motd(2000), "§abase");
+    }
+
+    #[test]
+    fn test_legacy() {
+        let unbounded = config(&[("600..=800", "§amid"), ("0..", "§aall"), ("..=700", "§aold"), ("..=500", "§aolder")]);
+        assert_eq!(motd(unbounded.for_legacy()), "§aold");
+        let bounded = config(&[("600..=800", "§amid"), ("0..", "§aall")]);
+        assert_eq!(motd(bounded.for_legacy()), "§abase");
+    }
+}
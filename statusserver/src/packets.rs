@@ -1,15 +1,39 @@
-use byteorder::{BigEndian, ReadBytesExt};
-use json::{object, JsonValue};
 use log::{debug, error, info};
-use serde::Deserialize;
+use mcproto::{McString, Packet, Packets, ProtocolError, VarInt};
+use mcproto::{ProtocolVersion, versions};
+use mctext::{
+    ObjectFallback, RawText, TextComponent, TextFormat, compat,
+    width::{self, Align, MOTD_LINES, MOTD_WIDTH},
+};
+use serde::{Deserialize, Deserializer, de};
+use serde_json::Value;
 use std::{
-    io::{Error, Read, Write},
+    borrow::Cow,
+    io::{Error, Write},
+    net::IpAddr,
+    path::PathBuf,
     str::Utf8Error,
+    sync::atomic::{AtomicUsize, Ordering},
     string::{FromUtf16Error, FromUtf8Error},
 };
 use uuid::Uuid;
+use varint::VarIntError;
+
+use crate::{
+    forge::{FmlVersion, ForgeData},
+    frames::{Frames, Rotation},
+    placeholders::{self, Context, Login, PlaceholderError},
+    hosts::{self, ConfigSet, VirtualHost},
+    overlay::{self, IconOverlay},
+    overrides::VersionOverride,
+    profiles::Profile,
+    player::{ConnectionState, HandshakeInfo, Player},
+    status::{Favicon, Players, Sample, Status, StatusCache, StatusKey, Version},
+    status_file::{StatusFile, StatusFileOptions},
+};
 
-use crate::player::{ConnectionState, HandshakeInfo, Player};
+/// The protocol sent to clients that didn't send theirs, when none is configured
+pub const FALLBACK_PROTOCOL: ProtocolVersion = ProtocolVersion(127);
 
 const DEFAULT_UUID: Uuid = *uuid::Builder::from_bytes([0u8; 16]).as_uuid();
 
@@ -19,6 +43,8 @@ pub enum PacketError {
     FromUtf8Error(FromUtf8Error),
     Utf8Error(Utf8Error),
     FromUtf16Error(FromUtf16Error),
+    VarIntError(VarIntError),
+    ProtocolError(ProtocolError),
     DataError(Vec<u8>),
     ClosedError,
 }
@@ -30,6 +56,8 @@ impl std::fmt::Display for PacketError {
             Self::FromUtf8Error(e) => write!(f, "Invalid string sent: {}", e),
             Self::Utf8Error(e) => write!(f, "Invalid string sent: {}", e),
             Self::FromUtf16Error(e) => write!(f, "Invalid legacy string sent: {}", e),
+            Self::VarIntError(e) => write!(f, "Invalid varint sent: {}", e),
+            Self::ProtocolError(e) => write!(f, "Invalid data sent: {}", e),
             Self::DataError(e) => write!(f, "Player sent invalid data: {:?}", e),
             Self::ClosedError => write!(f, "Connection closed")
         }
@@ -54,18 +82,55 @@ impl From<Utf8Error> for PacketError {
     }
 }
 
+impl From<VarIntError> for PacketError {
+    fn from(value: VarIntError) -> Self {
+        match value {
+            VarIntError::Io(e) => PacketError::IOError(e),
+            e => PacketError::VarIntError(e),
+        }
+    }
+}
+
+impl From<ProtocolError> for PacketError {
+    fn from(value: ProtocolError) -> Self {
+        match value {
+            ProtocolError::Io(e) => PacketError::IOError(e),
+            e => PacketError::ProtocolError(e),
+        }
+    }
+}
+
 impl From<FromUtf16Error> for PacketError {
     fn from(value: FromUtf16Error) -> Self {
         PacketError::FromUtf16Error(value)
     }
 }
 
-#[derive(Deserialize, Debug, Clone)]
+/// Icons can also be a list of files, shown one after the other
+fn icon_frames<'de, D: Deserializer<'de>>(d: D) -> Result<Option<Frames<PathBuf>>, D::Error> {
+    let value = Value::deserialize(d)?;
+    if value.is_array() {
+        let paths = Vec::<PathBuf>::deserialize(value).map_err(de::Error::custom)?;
+        return Frames::new(paths, Rotation::Sequential)
+            .map(Some)
+            .ok_or_else(|| de::Error::custom("icons can't be empty"));
+    }
+    Frames::deserialize(value).map(Some).map_err(de::Error::custom)
+}
+
+#[derive(Debug, Clone)]
 pub struct PlayerListEntry {
     pub name: String,
     pub uuid: Option<Uuid>,
 }
 
+/// A [`PlayerListEntry`] as it's written in the config
+#[derive(Deserialize, Debug, Clone)]
+struct RawPlayerListEntry {
+    name: RawText,
+    uuid: Option<Uuid>,
+}
+
 impl From<(&str, Option<Uuid>)> for PlayerListEntry {
     fn from(value: (&str, Option<Uuid>)) -> Self {
         PlayerListEntry {
@@ -75,74 +140,567 @@ impl From<(&str, Option<Uuid>)> for PlayerListEntry {
     }
 }
 
-impl From<PlayerListEntry> for JsonValue {
-    fn from(value: PlayerListEntry) -> Self {
-        object! {
-            name: value.name.as_str(),
-            id: value.uuid.unwrap_or(DEFAULT_UUID).to_string()
-        }
+/// A line of the motd, either just its text or a table with an `align` and the text's component
+#[derive(Debug, Clone)]
+pub struct MotdLine {
+    pub text: TextComponent,
+    pub align: Align,
+}
+
+/// A [`MotdLine`] as it's written in the config
+#[derive(Debug, Clone)]
+struct RawMotdLine {
+    text: RawText,
+    align: Align,
+}
+
+impl<'de> Deserialize<'de> for RawMotdLine {
+    fn deserialize<D: Deserializer<'de>>(d: D) -> Result<Self, D::Error> {
+        let mut table = match Value::deserialize(d)? {
+            Value::Object(table) => table,
+            text => {
+                return Ok(RawMotdLine {
+                    text: RawText(text),
+                    align: Align::Left,
+                });
+            }
+        };
+        let align = match table.remove("align") {
+            Some(align) => Align::deserialize(align).map_err(de::Error::custom)?,
+            None => Align::Left,
+        };
+        // `{ text = '...' }` is read like any other text, the rest like a component
+        let text = match table.remove("text") {
+            Some(text) if table.is_empty() => text,
+            Some(text) => {
+                table.insert("text".to_string(), text);
+                Value::Object(table)
+            }
+            None => Value::Object(table),
+        };
+        Ok(RawMotdLine {
+            text: RawText(text),
+            align,
+        })
     }
 }
 
+/// The text fields of the config as they're written, which are read once `format` is known
+#[derive(Deserialize, Debug, Default)]
+pub struct RawTextFields {
+    player_list: Vec<RawPlayerListEntry>,
+    #[serde(default)]
+    motd: Frames<RawText>,
+    #[serde(default)]
+    motd_lines: Option<Frames<Vec<RawMotdLine>>>,
+    kick_message: RawText,
+}
+
 #[derive(Deserialize, Debug)]
 pub struct ServerConfig {
-    pub version: String,
-    pub protocol: Option<u16>,
+    /// How text that isn't JSON is read
+    #[serde(default)]
+    pub format: TextFormat,
+    /// The version name, or "auto" to use the name of the protocol's release
+    pub version: Frames<String>,
+    pub protocol: Option<ProtocolVersion>,
     pub online_players: i32,
     pub max_players: i32,
+    /// Read into the fields below by [`ServerConfig::read_text`]
+    #[serde(flatten)]
+    pub raw_text: RawTextFields,
+    #[serde(skip)]
     pub player_list: Vec<PlayerListEntry>,
-    pub motd: String,
-    pub kick_message: String,
+    #[serde(skip)]
+    pub motd: Frames<TextComponent>,
+    /// Replaces `motd` with lines that can be aligned
+    #[serde(skip)]
+    pub motd_lines: Option<Frames<Vec<MotdLine>>>,
+    #[serde(skip)]
+    pub kick_message: TextComponent,
+    /// What replaces `object` components for clients older than 1.21.9
+    #[serde(default)]
+    pub object_fallback: ObjectFallback,
+    /// Image or base64 icon files or directories of them in the config directory,
+    /// instead of icon.png or icon.b64
+    #[serde(default, alias = "icons", deserialize_with = "icon_frames")]
+    pub icon: Option<Frames<PathBuf>>,
+    /// The icons read from `icon` or the default icon, as base64 64x64 pngs
+    #[serde(skip)]
+    pub loaded_icon: Option<Frames<String>>,
+    /// Text and a status dot drawn over the icon for each request
+    pub icon_overlay: Option<IconOverlay>,
+    /// Whether clients should only allow signed chat messages
+    #[serde(default)]
+    pub enforces_secure_chat: bool,
+    /// Tells clients with the No Chat Reports mod that chat messages can't be reported
+    #[serde(default)]
+    pub prevents_chat_reports: bool,
+    /// Whether 1.19 clients should show a preview of chat messages
+    #[serde(default)]
+    pub previews_chat: bool,
+    /// The mods and channels shown to Forge clients
+    pub forge: Option<ForgeData>,
+    /// What's changed in status.json before it's sent, when there is one
+    #[serde(default)]
+    pub status_json: StatusFileOptions,
+    /// Configs used instead of this one for some protocols, read from `[[version_overrides]]`
+    #[serde(skip)]
+    pub version_overrides: Vec<VersionOverride>,
+    /// The status responses already sent
+    #[serde(skip)]
+    pub status_cache: StatusCache,
 }
 
-#[derive(Deserialize, Debug)]
-pub struct ServerInfo {
-    pub config: ServerConfig,
-    pub icon: Option<String>,
-}
+impl ServerConfig {
+    pub const AUTO_VERSION: &str = "auto";
 
-pub fn handle_status_login<T: Read>(
-    packet: &mut T,
-    client: &mut Player,
-    info: &ServerInfo,
-) -> Result<(), PacketError> {
-    debug!("Received status/login packet from {}", client.addr);
-    let state = &client.state;
-    match state {
-        ConnectionState::HANDSHAKING => {
-            handle_handshake(packet, client)?;
+    /// The config for a client with `protocol`, from the first version override that matches it
+    pub fn for_protocol(&self, protocol: ProtocolVersion) -> &ServerConfig {
+        match self.version_overrides.iter().find(|o| o.matches(protocol)) {
+            Some(o) => &o.config,
+            None => self,
         }
-        ConnectionState::STATUS => {
-            handle_status(packet, client, info)?;
+    }
+
+    /// The config for a client from before 1.7
+    pub fn for_legacy(&self) -> &ServerConfig {
+        match self.version_overrides.iter().find(|o| o.matches_legacy()) {
+            Some(o) => &o.config,
+            None => self,
         }
-        ConnectionState::LOGIN => {
-            handle_login(packet, client, info)?;
+    }
+
+    /// The name shown for a frame of `version` to a client with a different protocol
+    pub fn version_name(version: &str, protocol: ProtocolVersion) -> Cow<'_, str> {
+        if version != Self::AUTO_VERSION {
+            return Cow::Borrowed(version);
         }
-        s => {
-            error!(
-                "Invalid request: packet 0 in state {} by {}",
-                s, client.addr
-            );
+        match protocol.name() {
+            Some(name) => Cow::Owned(name),
+            None => Cow::Owned(protocol.to_string()),
         }
-    };
-    Ok(())
+    }
+
+    /// Aligns and joins a frame of `motd_lines`.
+    /// Placeholders are replaced before aligning when there's a `context`
+    fn join_lines(lines: &[MotdLine], context: Option<&Context>) -> TextComponent {
+        let mut parts = vec![TextComponent::default()];
+        for (i, line) in lines.iter().enumerate() {
+            if i > 0 {
+                parts.push(TextComponent::from("\n"));
+            }
+            let text = match context {
+                Some(context) => placeholders::replace_component(&line.text, context),
+                None => line.text.clone(),
+            };
+            parts.push(width::align_line(text, line.align, MOTD_WIDTH));
+        }
+        TextComponent::List(parts)
+    }
+
+    /// The motd frame for a request from `ip`, with placeholders replaced when there's a `context`
+    pub fn motd(&self, ip: IpAddr, context: Option<&Context>) -> Cow<'_, TextComponent> {
+        self.motd_frame(self.motd_index(ip), context)
+    }
+
+    /// The index of the motd frame for a request from `ip`
+    pub fn motd_index(&self, ip: IpAddr) -> usize {
+        match &self.motd_lines {
+            Some(lines) => lines.pick_index(ip),
+            None => self.motd.pick_index(ip),
+        }
+    }
+
+    /// The motd frame at index `i`, with placeholders replaced when there's a `context`
+    pub fn motd_frame(&self, i: usize, context: Option<&Context>) -> Cow<'_, TextComponent> {
+        if let Some(lines) = &self.motd_lines {
+            return Cow::Owned(Self::join_lines(lines.get(i), context));
+        }
+        let motd = self.motd.get(i);
+        match context {
+            Some(context) => Cow::Owned(placeholders::replace_component(motd, context)),
+            None => Cow::Borrowed(motd),
+        }
+    }
+
+    /// Every frame of the motd, with `motd_lines` joined and placeholders replaced when there's a `context`
+    pub fn motd_frames(&self, context: Option<&Context>) -> Vec<(String, Cow<'_, TextComponent>)> {
+        match &self.motd_lines {
+            Some(lines) => lines
+                .labeled("motd_lines")
+                .map(|(label, lines)| (label, Cow::Owned(Self::join_lines(lines, context))))
+                .collect(),
+            None => self
+                .motd
+                .labeled("motd")
+                .map(|(label, motd)| match context {
+                    Some(context) => (label, Cow::Owned(placeholders::replace_component(motd, context))),
+                    None => (label, Cow::Borrowed(motd)),
+                })
+                .collect(),
+        }
+    }
+
+    /// Placeholder values like a client's, to see how wide the motd would be
+    fn preview_context(&self) -> Context {
+        Context {
+            online: self.online_players,
+            max: self.max_players,
+            client_version: versions::latest().minecraft_version.clone(),
+            hostname: String::from("localhost"),
+            port: 25565,
+            login: None,
+        }
+    }
+
+    /// Reads the text fields in the config's `format`
+    pub fn read_text(&mut self) -> Result<(), String> {
+        let raw = std::mem::take(&mut self.raw_text);
+        let format = self.format;
+        let read = |text: &RawText, field: &str| text.parse(format).map_err(|e| format!("in {}: {}", field, e));
+        self.player_list = raw
+            .player_list
+            .iter()
+            .enumerate()
+            .map(|(i, entry)| {
+                Ok(PlayerListEntry {
+                    // Sample names can only have '§' codes, so hex colors and gradients use the closest named colors
+                    name: read(&entry.name, &format!("player_list[{}]", i))?.to_legacy(),
+                    uuid: entry.uuid,
+                })
+            })
+            .collect::<Result<_, String>>()?;
+        self.motd = raw.motd.try_map(|motd| read(motd, "motd"))?;
+        self.motd_lines = match &raw.motd_lines {
+            Some(frames) => Some(frames.try_map(|lines| {
+                lines
+                    .iter()
+                    .map(|line| {
+                        Ok(MotdLine {
+                            text: read(&line.text, "motd_lines")?,
+                            align: line.align,
+                        })
+                    })
+                    .collect::<Result<Vec<_>, String>>()
+            })?),
+            None => None,
+        };
+        self.kick_message = read(&raw.kick_message, "kick_message")?;
+        Ok(())
+    }
+
+    /// Checks the placeholders of every field that has them
+    pub fn check_placeholders(&self) -> Result<(), PlaceholderError> {
+        for (label, version) in self.version.labeled("version") {
+            placeholders::check(version, false, &label)?;
+        }
+        for (i, entry) in self.player_list.iter().enumerate() {
+            placeholders::check(&entry.name, false, &format!("player_list[{}]", i))?;
+        }
+        for (label, motd) in self.motd.labeled("motd") {
+            placeholders::check_component(motd, false, &label)?;
+        }
+        for (label, lines) in self.motd_lines.iter().flat_map(|l| l.labeled("motd_lines")) {
+            for (i, line) in lines.iter().enumerate() {
+                placeholders::check_component(&line.text, false, &format!("{}[{}]", label, i))?;
+            }
+        }
+        if let Some(text) = self.icon_overlay.as_ref().and_then(|o| o.text.as_ref()) {
+            placeholders::check(text, false, "icon_overlay.text")?;
+        }
+        placeholders::check_component(&self.kick_message, true, "kick_message")
+    }
+
+    /// Whether the status response is the same for every client with the same protocol,
+    /// so it can be cached
+    pub fn status_is_static(&self) -> bool {
+        self.version.iter().all(|v| placeholders::is_static(v))
+            && self.player_list.iter().all(|e| placeholders::is_static(&e.name))
+            && self.motd.iter().all(placeholders::is_static_component)
+            && self
+                .motd_lines
+                .iter()
+                .flat_map(|l| l.iter().flatten())
+                .all(|line| placeholders::is_static_component(&line.text))
+            && self
+                .icon_overlay
+                .as_ref()
+                .and_then(|o| o.text.as_ref())
+                .is_none_or(|text| placeholders::is_static(text))
+    }
+
+    /// What placeholders are replaced with for a client, using the address it connected
+    /// to when it didn't send one
+    pub fn context(&self, client: &Player) -> Context {
+        let (client_version, hostname, port) = match &client.handshake_info {
+            Some(info) => (
+                info.protocol.name().unwrap_or_else(|| info.protocol.to_string()),
+                hosts::normalize(&info.server_addr),
+                info.server_port,
+            ),
+            None => {
+                let local = client.connection.local_addr().ok();
+                (
+                    String::new(),
+                    local.map(|a| a.ip().to_string()).unwrap_or_default(),
+                    local.map(|a| a.port()).unwrap_or_default(),
+                )
+            }
+        };
+        Context {
+            online: self.online_players,
+            max: self.max_players,
+            client_version,
+            hostname,
+            port,
+            login: None,
+        }
+    }
+
+    /// Problems with the config that don't stop it from loading, like motd lines that are cut off
+    pub fn warnings(&self) -> Vec<String> {
+        let mut warnings = Vec::new();
+        let motd_set = self.motd.iter().any(|m| *m != TextComponent::default());
+        if self.motd_lines.is_some() && motd_set {
+            warnings.push("motd is replaced by motd_lines".to_string());
+        }
+        for (label, motd) in self.motd_frames(Some(&self.preview_context())) {
+            let widths = width::component_widths(&motd);
+            if widths.len() > MOTD_LINES {
+                warnings.push(format!(
+                    "{} has {} lines, only {} are shown",
+                    label,
+                    widths.len(),
+                    MOTD_LINES
+                ));
+            }
+            for (i, w) in widths.iter().enumerate().take(MOTD_LINES) {
+                if *w > MOTD_WIDTH {
+                    warnings.push(format!(
+                        "Line {} of {} is {}px wide and will be cut off at {}px",
+                        i + 1,
+                        label,
+                        w,
+                        MOTD_WIDTH
+                    ));
+                }
+            }
+        }
+        if let Some(text) = self.icon_overlay.as_ref().and_then(|o| o.text.as_ref()) {
+            let text = placeholders::replace(text, &self.preview_context());
+            let len = mctext::strip_formatting(&text).chars().count();
+            if len > overlay::MAX_TEXT_LEN {
+                warnings.push(format!(
+                    "icon_overlay.text is {} characters long and will be cut off at {}",
+                    len,
+                    overlay::MAX_TEXT_LEN
+                ));
+            }
+        }
+        warnings
+    }
+
+    /// The icon frame at index `i` with the overlay drawn over it, as base64
+    fn icon(&self, i: usize, context: &Context) -> Option<Cow<'_, str>> {
+        let icon = self.loaded_icon.as_ref().map(|icon| Cow::Borrowed(icon.get(i).as_str()));
+        let Some(overlay) = &self.icon_overlay else {
+            return icon;
+        };
+        let text = overlay.text.as_ref().map(|text| placeholders::replace(text, context));
+        let dot = overlay.dot.map(|dot| dot.resolve(context.online, context.max));
+        match overlay.render(i, text.as_deref(), dot) {
+            Ok(rendered) => Some(Cow::Owned(rendered)),
+            Err(e) => {
+                error!("Couldn't draw the icon overlay: {}", e);
+                icon
+            }
+        }
+    }
+
+    /// The status response for a client, with the frames in `key`
+    pub fn status(&self, client: &Player, key: &StatusKey) -> Status<'_> {
+        let protocol = self.protocol.unwrap_or(key.protocol);
+        let context = self.context(client);
+        let name = ServerConfig::version_name(self.version.get(key.version), protocol);
+        let sample = self
+            .player_list
+            .iter()
+            .map(|entry| Sample {
+                name: placeholders::replace(&entry.name, &context),
+                id: entry.uuid.unwrap_or(DEFAULT_UUID),
+            })
+            .collect();
+        let motd = self.motd_frame(key.motd, Some(&context));
+        Status {
+            version: Version {
+                name: Cow::Owned(placeholders::replace(&name, &context).into_owned()),
+                protocol: protocol.0,
+            },
+            players: Players {
+                max: self.max_players,
+                online: self.online_players,
+                sample,
+            },
+            description: self.text_json(&motd, key.protocol),
+            favicon: self.icon(key.icon, &context).map(Favicon),
+            enforces_secure_chat: self.enforces_secure_chat,
+            prevents_chat_reports: self.prevents_chat_reports,
+            previews_chat: self.previews_chat,
+            forge: self.forge.as_ref().zip(key.fml).map(|(forge, fml)| forge.status(fml)),
+        }
+    }
+
+    /// The JSON of a text component for a client with `protocol`, with the components it can't show replaced
+    pub fn text_json(&self, text: &TextComponent, protocol: ProtocolVersion) -> Value {
+        let mut value = text.to_json(protocol.0);
+        if !compat::supports_objects(protocol.0) {
+            compat::replace_objects(&mut value, &self.object_fallback);
+        }
+        value
+    }
+
+    /// A minimal config with `motd`, for tests
+    #[cfg(test)]
+    pub fn for_test(motd: &str) -> ServerConfig {
+        let text = format!(
+            "version = '1.21'\nonline_players = 0\nmax_players = 20\nplayer_list = []\nmotd = '{}'\nkick_message = ''",
+            motd
+        );
+        let mut config: ServerConfig = toml::from_str(&text).unwrap();
+        config.read_text().unwrap();
+        config
+    }
+}
+
+#[derive(Debug)]
+pub struct ServerInfo {
+    /// The configs used when the hostname doesn't match any of `hosts`
+    pub default: ConfigSet,
+    pub hosts: Vec<VirtualHost>,
+    pub profiles: Vec<Profile>,
+    /// Sent instead of the status response made from the config
+    pub status_file: Option<StatusFile>,
+    /// One more than the index of the active profile in `profiles`, or 0 for none.
+    /// It's atomic so the profile can change while connections are using the info
+    active: AtomicUsize,
+}
+
+impl ServerInfo {
+    /// Server info with no active profile
+    pub fn new(
+        default: ConfigSet,
+        hosts: Vec<VirtualHost>,
+        profiles: Vec<Profile>,
+        status_file: Option<StatusFile>,
+    ) -> Self {
+        ServerInfo {
+            default,
+            hosts,
+            profiles,
+            status_file,
+            active: AtomicUsize::new(0),
+        }
+    }
+
+    /// The index of the active profile in `profiles`
+    pub fn active(&self) -> Option<usize> {
+        self.active.load(Ordering::Relaxed).checked_sub(1)
+    }
+
+    pub fn set_active(&self, active: Option<usize>) {
+        self.active.store(active.map_or(0, |i| i + 1), Ordering::Relaxed);
+    }
+
+    /// The config for a client that connected to `hostname`, with the active profile
+    pub fn config(&self, hostname: Option<&str>) -> &ServerConfig {
+        let hostname = hostname.map(hosts::normalize);
+        let host = hostname.and_then(|h| self.hosts.iter().find(|host| host.matches(&h)));
+        match host {
+            Some(host) => host.configs.get(self.active()),
+            None => self.default.get(self.active()),
+        }
+    }
+
+    /// The config of every host and profile
+    pub fn configs(&self) -> impl Iterator<Item = &ServerConfig> {
+        self.default
+            .iter()
+            .chain(self.hosts.iter().flat_map(|h| h.configs.iter()))
+    }
+
+    /// The name of the active profile
+    pub fn active_name(&self) -> Option<&str> {
+        Some(self.profiles.get(self.active()?)?.name.as_str())
+    }
+}
+
+#[derive(Debug, Packet)]
+#[packet(id = 0x00, state = Handshaking, direction = Serverbound, name = "set_protocol")]
+pub struct Handshake {
+    pub protocol: ProtocolVersion,
+    pub host: McString<255>,
+    pub port: u16,
+    pub intent: VarInt,
+}
+
+#[derive(Debug, Packet)]
+#[packet(id = 0x00, state = Status, direction = Serverbound, name = "ping_start")]
+pub struct StatusRequest;
+
+#[derive(Debug, Packet)]
+#[packet(id = 0x01, state = Status, direction = Serverbound, name = "ping")]
+pub struct PingRequest {
+    pub payload: u64,
+}
+
+#[derive(Debug, Packet)]
+#[packet(id = 0x00, state = Login, direction = Serverbound, name = "login_start")]
+pub struct LoginStart {
+    pub name: McString<16>,
+    pub uuid: Uuid,
+}
+
+#[derive(Debug, Packets)]
+pub enum ServerboundPacket {
+    Handshake(Handshake),
+    StatusRequest(StatusRequest),
+    PingRequest(PingRequest),
+    LoginStart(LoginStart),
+}
+
+#[derive(Debug, Packet)]
+#[packet(id = 0x00, state = Status, direction = Clientbound, name = "server_info")]
+pub struct StatusResponse {
+    pub response: String,
+}
+
+#[derive(Debug, Packet)]
+#[packet(id = 0x01, state = Status, direction = Clientbound, name = "ping")]
+pub struct PongResponse {
+    pub payload: u64,
 }
 
-fn handle_handshake<T: Read>(packet: &mut T, client: &mut Player) -> Result<(), PacketError> {
+#[derive(Debug, Packet)]
+#[packet(id = 0x00, state = Login, direction = Clientbound, name = "disconnect")]
+pub struct LoginDisconnect {
+    pub reason: McString<262144>,
+}
+
+pub fn handle_handshake(packet: Handshake, client: &mut Player) -> Result<(), PacketError> {
     debug!("Received handshake packet from {}", client.addr);
-    let stream = packet;
-    let protocol_version = varint::decode_stream(stream)? as u16;
-    let strlen = varint::decode_stream(stream)? as usize;
-    let mut strbuf = vec![0u8; strlen];
-    stream.read_exact(&mut strbuf)?;
-    let host = String::from_utf8(strbuf)?;
-    let port = stream.read_u16::<BigEndian>()?;
-    let intent = varint::decode_stream(stream)?;
+    let protocol_version = packet.protocol;
+    let host = packet.host.into_inner();
+    let port = packet.port;
+    let intent = packet.intent.0;
     let intent = ConnectionState::try_from(intent as u8)
-        .or_else(|_| Err(PacketError::DataError(vec![intent as u8])))?;
+        .map_err(|_| PacketError::DataError(vec![intent as u8]))?;
     info!(
         "{}:{} connected with protocol {} intent {}",
-        host, port, protocol_version, intent
+        host,
+        port,
+        protocol_version.describe(),
+        intent
     );
     let info = HandshakeInfo {
         protocol: protocol_version,
@@ -154,110 +712,126 @@ fn handle_handshake<T: Read>(packet: &mut T, client: &mut Player) -> Result<(),
     Ok(())
 }
 
-fn make_status_response(
-    version: &str,
-    protocol: u16,
-    maxplr: i32,
-    players: i32,
-    playerlist: &Vec<PlayerListEntry>,
-    motd: &str,
-    secure: bool,
-    icon: Option<&str>,
-) -> String {
-    let motd = json::parse(motd).unwrap_or(JsonValue::String(motd.to_string()));
-    let icon = match icon {
-        Some(i) => Some(format!("data:image/png;base64,{i}")),
-        None => None,
-    };
-    let obj = object! {
-        version: {
-            name: version,
-            protocol: protocol
-        },
-        players: {
-            max: maxplr,
-            online: players,
-            sample: playerlist.clone() // FIXME way of doing this without clone?
-        },
-        description: motd,
-        favicon: icon,
-        enforcesSecureChat: secure,
-    };
-    obj.to_string()
-}
-
-fn send_packet(packet_id: i32, data: &[u8], client: &mut Player) -> Result<(), PacketError> {
-    let mut packet_id = varint::encode(packet_id);
-    let mut total_packet = varint::encode((packet_id.len() + data.len()) as i32);
-    total_packet.append(&mut packet_id);
-    total_packet.append(&mut data.to_vec());
-    if let Err(e) = client.connection.write(total_packet.as_slice()) {
-        return Err(PacketError::IOError(e));
-    }
+fn send_packet<P: Packet>(packet: &P, client: &mut Player) -> Result<(), PacketError> {
+    packet.write_packet_for(client.protocol().0, &mut client.connection)?;
     Ok(())
 }
 
-pub fn handle_ping<T: Read>(data: &mut T, client: &mut Player) -> Result<(), PacketError> {
+pub fn handle_ping(packet: PingRequest, client: &mut Player) -> Result<(), PacketError> {
     debug!("{}: Ping packet", client.addr);
-    let pong = data.read_u64::<BigEndian>()?;
-    send_packet(0x01, &pong.to_be_bytes(), client)?;
+    send_packet(&PongResponse { payload: packet.payload }, client)?;
     Ok(())
 }
 
-fn handle_status<T: Read>(
-    _: &mut T,
-    client: &mut Player,
-    info: &ServerInfo,
-) -> Result<(), PacketError> {
+pub fn handle_status(client: &mut Player, info: &ServerInfo) -> Result<(), PacketError> {
     debug!("Received status packet from {}", client.addr);
-    let protocol: u16 = match info.config.protocol {
-        Some(p) => p,
-        None => match &client.handshake_info {
-            Some(p) => p.protocol,
-            None => 127,
-        },
+    let config = info.config(client.hostname()).for_protocol(client.protocol());
+    if let Some(status_file) = &info.status_file {
+        let context = config.context(client);
+        let response = status_file.response(config.status_json, &context, client.protocol());
+        send_packet(&StatusResponse { response }, client)?;
+        return Ok(());
+    }
+    let ip = client.addr.ip();
+    // Clients that didn't send a handshake get the fallback protocol
+    let protocol = match &client.handshake_info {
+        Some(info) => info.protocol,
+        None => FALLBACK_PROTOCOL,
     };
-    let response = make_status_response(
-        &info.config.version,
+    let key = StatusKey {
         protocol,
-        info.config.max_players,
-        info.config.online_players,
-        &info.config.player_list,
-        &info.config.motd,
-        false,
-        info.icon.as_deref(),
-    );
-    let response = response.as_bytes();
-    let mut full_data = varint::encode(response.len() as i32);
-    full_data.extend(response);
-    send_packet(0x00, full_data.as_slice(), client)?;
+        fml: config
+            .forge
+            .as_ref()
+            .map(|_| FmlVersion::for_client(client.hostname(), protocol)),
+        motd: config.motd_index(ip),
+        version: config.version.pick_index(ip),
+        icon: config.loaded_icon.as_ref().map_or(0, |icon| icon.pick_index(ip)),
+    };
+    let packet = config.status_cache.get_or_render(key, || {
+        debug!("Rendering the status response for {:?}", key);
+        // The model only has string keys, so it always serializes
+        let response = serde_json::to_string(&config.status(client, &key))
+            .expect("status response should serialize");
+        let mut packet = Vec::new();
+        StatusResponse { response }.write_packet_for(protocol.0, &mut packet)?;
+        Ok::<_, PacketError>(packet)
+    })?;
+    client.connection.write_all(&packet)?;
     Ok(())
 }
 
-fn handle_login<T: Read>(
-    packet: &mut T,
+pub fn handle_login(
+    packet: LoginStart,
     client: &mut Player,
     info: &ServerInfo,
 ) -> Result<(), PacketError> {
     debug!("Received login packet from {}", client.addr);
-    let name_len = varint::decode_stream(packet)?;
-    if name_len <= 0 || name_len > 16 {
-        error!("Invalid name length {}", name_len);
+    let name = packet.name;
+    if name.is_empty() {
+        error!("Invalid name sent by {}", client.addr);
         client.connection.shutdown(std::net::Shutdown::Both)?;
-        return Err(PacketError::DataError(name_len.to_be_bytes().to_vec()));
+        return Err(PacketError::DataError(vec![]));
     }
-    let mut namebuf = [0u8; 16];
-    let (namebuf, _) = namebuf.split_at_mut(name_len as usize);
-    packet.read_exact(namebuf)?;
-    let name = str::from_utf8(namebuf)?;
-    let uuid = packet.read_u128::<BigEndian>()?;
+    let uuid = packet.uuid;
     info!("Player login: {} {}", name, uuid);
-    let kick_message = match json::parse(&info.config.kick_message) {
-        Ok(v) => v.to_string(),
-        Err(_) => info.config.kick_message.to_string()
-    };
-    let mut total_data = varint::encode(kick_message.len() as i32);
-    total_data.extend(kick_message.as_bytes());
-    send_packet(0x00, total_data.as_slice(), client)?;
+    let config = info.config(client.hostname()).for_protocol(client.protocol());
+    let mut context = config.context(client);
+    context.login = Some(Login {
+        name: name.into_inner(),
+        uuid,
+        ip: client.addr.ip(),
+    });
+    let kick_message = placeholders::replace_component(&config.kick_message, &context);
+    let kick_message = config.text_json(&kick_message, client.protocol());
+    let reason = McString::new(kick_message.to_string())?;
+    send_packet(&LoginDisconnect { reason }, client)?;
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn icons(text: &str) -> Result<Option<Frames<PathBuf>>, toml::de::Error> {
+        let mut table: toml::Table = toml::from_str(text).unwrap();
+        icon_frames(table.remove("icons").unwrap())
+    }
+
+    fn paths(frames: &Frames<PathBuf>) -> Vec<&str> {
+        frames.iter().map(|p| p.to_str().unwrap()).collect()
+    }
+
+    #[test]
+    fn test_icon_frames() {
+        let list = icons("icons = ['icons', 'logo.png']").unwrap().unwrap();
+        assert_eq!(paths(&list), ["icons", "logo.png"]);
+        assert_eq!(list.rotation(), Rotation::Sequential);
+
+        let table = icons("icons = { frames = ['a.png', 'b.png'], rotation = 'random' }").unwrap().unwrap();
+        assert_eq!(paths(&table), ["a.png", "b.png"]);
+        assert_eq!(table.rotation(), Rotation::Random);
+
+        let single = icons("icons = 'icon.webp'").unwrap().unwrap();
+        assert_eq!(paths(&single), ["icon.webp"]);
+
+        let empty = icons("icons = []").unwrap_err();
+        assert!(empty.to_string().contains("icons can't be empty"));
+    }
+
+    #[test]
+    fn test_active() {
+        let default = ConfigSet {
+            base: ServerConfig::for_test("§abase"),
+            profiles: vec![],
+        };
+        let info = ServerInfo::new(default, vec![], vec![], None);
+        assert_eq!(info.active(), None);
+        info.set_active(Some(0));
+        assert_eq!(info.active(), Some(0));
+        // A profile the config doesn't have uses the base config
+        assert_eq!(info.config(None).motd.get(0).to_string(), "§abase");
+        info.set_active(None);
+        assert_eq!(info.active(), None);
+    }
+}
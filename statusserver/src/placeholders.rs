@@ -0,0 +1,414 @@
+use std::{borrow::Cow, fmt, net::IpAddr};
+
+use chrono::{
+    DateTime, Utc,
+    format::{Item, StrftimeItems},
+};
+use log::debug;
+use mctext::TextComponent;
+use serde_json::Value;
+use uuid::Uuid;
+
+use crate::profiles;
+
+/// The format of `{time}` when none is given
+const DEFAULT_TIME_FORMAT: &str = "%H:%M";
+
+/// A `{name}` or `{name:argument}` in configured text, replaced for each request
+#[derive(Debug, Clone, PartialEq)]
+pub enum Placeholder {
+    Online,
+    Max,
+    ClientVersion,
+    Hostname,
+    Port,
+    Time(String),
+    Countdown(DateTime<Utc>),
+    Name,
+    Uuid,
+    Ip,
+}
+
+impl Placeholder {
+    fn parse(name: &str, argument: Option<&str>) -> Result<Self, String> {
+        let placeholder = match name {
+            "online" => Self::Online,
+            "max" => Self::Max,
+            "client_version" => Self::ClientVersion,
+            "hostname" => Self::Hostname,
+            "port" => Self::Port,
+            "time" => {
+                let format = argument.unwrap_or(DEFAULT_TIME_FORMAT);
+                if StrftimeItems::new(format).any(|i| matches!(i, Item::Error)) {
+                    return Err(format!("invalid time format '{}'", format));
+                }
+                return Ok(Self::Time(format.to_string()));
+            }
+            "countdown" => {
+                let time = argument.ok_or("{countdown} needs a time, like {countdown:2026-12-01T18:00Z}")?;
+                return profiles::parse_time(time)
+                    .map(Self::Countdown)
+                    .ok_or_else(|| format!("invalid time '{}'", time));
+            }
+            "name" => Self::Name,
+            "uuid" => Self::Uuid,
+            "ip" => Self::Ip,
+            _ => return Err(format!("unknown placeholder {{{}}}", name)),
+        };
+        match argument {
+            Some(_) => Err(format!("{{{}}} doesn't take an argument", name)),
+            None => Ok(placeholder),
+        }
+    }
+
+    /// Whether the placeholder needs the player that tried to join
+    fn needs_login(&self) -> bool {
+        matches!(self, Self::Name | Self::Uuid | Self::Ip)
+    }
+
+    /// Whether the placeholder is the same for every client with the same protocol
+    fn is_static(&self) -> bool {
+        matches!(self, Self::Online | Self::Max | Self::ClientVersion)
+    }
+}
+
+/// Placeholders that can't be used where they were written
+#[derive(Debug)]
+pub struct PlaceholderError {
+    pub field: String,
+    pub message: String,
+}
+
+impl fmt::Display for PlaceholderError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "in {}: {}", self.field, self.message)
+    }
+}
+
+impl std::error::Error for PlaceholderError {}
+
+/// The player that tried to join, for the kick message
+#[derive(Debug)]
+pub struct Login {
+    pub name: String,
+    pub uuid: Uuid,
+    pub ip: IpAddr,
+}
+
+/// What placeholders are replaced with for a request
+#[derive(Debug)]
+pub struct Context {
+    pub online: i32,
+    pub max: i32,
+    pub client_version: String,
+    pub hostname: String,
+    pub port: u16,
+    pub login: Option<Login>,
+}
+
+impl Context {
+    /// A request from `jeb_` for mc.example.com, for tests
+    #[cfg(test)]
+    pub fn for_test() -> Context {
+        Context {
+            online: 3,
+            max: 20,
+            client_version: String::from("1.21.4"),
+            hostname: String::from("mc.example.com"),
+            port: 25565,
+            login: Some(Login {
+                name: String::from("jeb_"),
+                uuid: Uuid::nil(),
+                ip: IpAddr::V4(std::net::Ipv4Addr::LOCALHOST),
+            }),
+        }
+    }
+
+    fn value(&self, placeholder: &Placeholder) -> Option<String> {
+        Some(match placeholder {
+            Placeholder::Online => self.online.to_string(),
+            Placeholder::Max => self.max.to_string(),
+            Placeholder::ClientVersion => self.client_version.clone(),
+            Placeholder::Hostname => self.hostname.clone(),
+            Placeholder::Port => self.port.to_string(),
+            Placeholder::Time(format) => chrono::Local::now().format(format).to_string(),
+            Placeholder::Countdown(time) => profiles::format_countdown(*time, Utc::now()),
+            Placeholder::Name => self.login.as_ref()?.name.clone(),
+            Placeholder::Uuid => self.login.as_ref()?.uuid.to_string(),
+            Placeholder::Ip => self.login.as_ref()?.ip.to_string(),
+        })
+    }
+}
+
+/// A piece of text split into literal text and placeholders
+enum Part<'a> {
+    Text(&'a str),
+    Placeholder(&'a str, Result<Placeholder, String>),
+}
+
+/// Splits text on its placeholders. `{{` is a literal `{`, and braces that don't hold
+/// a lowercase name like `{ "text": ... }` are left as they are
+fn parts(text: &str) -> Vec<Part<'_>> {
+    let mut parts = Vec::new();
+    let mut rest = text;
+    while let Some(start) = rest.find('{') {
+        if rest[start..].starts_with("{{") {
+            parts.push(Part::Text(&rest[..start + 1]));
+            rest = &rest[start + 2..];
+            continue;
+        }
+        let Some(len) = rest[start..].find('}') else {
+            break;
+        };
+        let inner = &rest[start + 1..start + len];
+        let (name, argument) = match inner.split_once(':') {
+            Some((name, argument)) => (name, Some(argument)),
+            None => (inner, None),
+        };
+        let is_name = !name.is_empty()
+            && name.starts_with(|c: char| c.is_ascii_lowercase())
+            && name.chars().all(|c| c.is_ascii_lowercase() || c == '_');
+        if !is_name {
+            parts.push(Part::Text(&rest[..start + 1]));
+            rest = &rest[start + 1..];
+            continue;
+        }
+        parts.push(Part::Text(&rest[..start]));
+        let whole = &rest[start..start + len + 1];
+        parts.push(Part::Placeholder(whole, Placeholder::parse(name, argument)));
+        rest = &rest[start + len + 1..];
+    }
+    parts.push(Part::Text(rest));
+    parts
+}
+
+/// Replaces the placeholders in a string. Ones that can't be replaced are left as they are
+pub fn replace<'a>(text: &'a str, context: &Context) -> Cow<'a, str> {
+    if !text.contains('{') {
+        return Cow::Borrowed(text);
+    }
+    let mut replaced = String::with_capacity(text.len());
+    for part in parts(text) {
+        match part {
+            Part::Text(t) => replaced.push_str(t),
+            Part::Placeholder(whole, placeholder) => {
+                match placeholder.ok().and_then(|p| context.value(&p)) {
+                    Some(value) => replaced.push_str(&value),
+                    None => replaced.push_str(whole),
+                }
+            }
+        }
+    }
+    Cow::Owned(replaced)
+}
+
+/// Replaces the placeholders in every string of a JSON value
+pub fn replace_json(value: &mut Value, context: &Context) {
+    match value {
+        Value::String(s) => {
+            if let Cow::Owned(replaced) = replace(s, context) {
+                *s = replaced;
+            }
+        }
+        Value::Array(values) => values.iter_mut().for_each(|v| replace_json(v, context)),
+        Value::Object(map) => map.values_mut().for_each(|v| replace_json(v, context)),
+        _ => {}
+    }
+}
+
+/// Replaces the placeholders in a text component's text, events and other strings
+pub fn replace_component(text: &TextComponent, context: &Context) -> TextComponent {
+    if let TextComponent::Text(t) = text {
+        return TextComponent::Text(replace(t, context).into_owned());
+    }
+    let mut value = text.to_json(i32::MAX);
+    replace_json(&mut value, context);
+    match TextComponent::from_value(&value) {
+        Ok(text) => text,
+        Err(e) => {
+            // Placeholders in fields like `color` can make the component invalid
+            debug!("Couldn't replace placeholders in {}: {}", text, e);
+            text.clone()
+        }
+    }
+}
+
+/// Whether a string is the same for every client with the same protocol, so it can be cached
+pub fn is_static(text: &str) -> bool {
+    parts(text).into_iter().all(|part| match part {
+        Part::Placeholder(_, Ok(p)) => p.is_static(),
+        _ => true,
+    })
+}
+
+/// [`is_static`] for every string of a text component
+pub fn is_static_component(text: &TextComponent) -> bool {
+    fn is_static_json(value: &Value) -> bool {
+        match value {
+            Value::String(s) => is_static(s),
+            Value::Array(values) => values.iter().all(is_static_json),
+            Value::Object(map) => map.values().all(is_static_json),
+            _ => true,
+        }
+    }
+    is_static_json(&text.to_json(i32::MAX))
+}
+
+/// Checks that every placeholder in a string is known, and that the ones for the
+/// player that tried to join are only used when `login` is set
+pub fn check(text: &str, login: bool, field: &str) -> Result<(), PlaceholderError> {
+    for part in parts(text) {
+        let Part::Placeholder(whole, placeholder) = part else {
+            continue;
+        };
+        let message = match placeholder {
+            Err(message) => message,
+            Ok(p) if p.needs_login() && !login => {
+                format!("{} can only be used in kick_message", whole)
+            }
+            Ok(_) => continue,
+        };
+        return Err(PlaceholderError {
+            field: field.to_string(),
+            message,
+        });
+    }
+    Ok(())
+}
+
+/// [`check`] for every string of a JSON value
+pub fn check_json(value: &Value, login: bool, field: &str) -> Result<(), PlaceholderError> {
+    match value {
+        Value::String(s) => check(s, login, field),
+        Value::Array(values) => values.iter().try_for_each(|v| check_json(v, login, field)),
+        Value::Object(map) => map.values().try_for_each(|v| check_json(v, login, field)),
+        _ => Ok(()),
+    }
+}
+
+/// [`check`] for every string of a text component
+pub fn check_component(
+    text: &TextComponent,
+    login: bool,
+    field: &str,
+) -> Result<(), PlaceholderError> {
+    check_json(&text.to_json(i32::MAX), login, field)
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    fn placeholders(text: &str) -> Vec<Result<Placeholder, String>> {
+        parts(text)
+            .into_iter()
+            .filter_map(|part| match part {
+                Part::Placeholder(_, p) => Some(p),
+                Part::Text(_) => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_replace() {
+        let context = Context::for_test();
+        assert_eq!(
+            replace("{online}/{max} on {hostname}:{port}", &context),
+            "3/20 on mc.example.com:25565"
+        );
+        assert_eq!(replace("{name} {ip}", &context), "jeb_ 127.0.0.1");
+        assert!(matches!(
+            replace("no placeholders", &context),
+            Cow::Borrowed(_)
+        ));
+    }
+
+    #[test]
+    fn test_escape() {
+        assert_eq!(
+            replace("{{online} is {online}", &Context::for_test()),
+            "{online} is 3"
+        );
+        assert!(placeholders("{{online}").is_empty());
+    }
+
+    #[test]
+    fn test_json_braces() {
+        let text = r#"{ "text": "a", "extra": [{"text": "{online}"}] }"#;
+        assert_eq!(
+            replace(text, &Context::for_test()),
+            r#"{ "text": "a", "extra": [{"text": "3"}] }"#
+        );
+        assert_eq!(placeholders(text).len(), 1);
+        assert!(check(text, false, "motd").is_ok());
+    }
+
+    #[test]
+    fn test_argument() {
+        assert_eq!(
+            placeholders("{time:%H:%M}"),
+            vec![Ok(Placeholder::Time(String::from("%H:%M")))]
+        );
+        assert_eq!(
+            placeholders("{time}"),
+            vec![Ok(Placeholder::Time(String::from(DEFAULT_TIME_FORMAT)))]
+        );
+        assert!(check("{time:%Q}", false, "motd").is_err());
+        assert!(check("{online:1}", false, "motd").is_err());
+    }
+
+    #[test]
+    fn test_check() {
+        let e = check("a {foo}", false, "motd").unwrap_err();
+        assert_eq!(e.to_string(), "in motd: unknown placeholder {foo}");
+        let e = check("bye {name}", false, "motd").unwrap_err();
+        assert_eq!(
+            e.to_string(),
+            "in motd: {name} can only be used in kick_message"
+        );
+        assert!(check("bye {name}", true, "kick_message").is_ok());
+        assert!(check("{countdown}", false, "motd").is_err());
+        let e = check_json(
+            &json!({"text": "", "extra": ["{uuid}"]}),
+            false,
+            "status.json",
+        );
+        assert!(e.is_err());
+    }
+
+    #[test]
+    fn test_replace_component() {
+        let text = TextComponent::from_value(&json!({"text": "{online} online", "color": "gold",
+            "hover_event": {"action": "show_text", "value": "on {hostname}"}}))
+        .unwrap();
+        let replaced = replace_component(&text, &Context::for_test()).to_json(i32::MAX);
+        assert_eq!(replaced["text"], "3 online");
+        assert_eq!(replaced["hover_event"]["value"], "on mc.example.com");
+        let text = TextComponent::from("{max} max");
+        assert_eq!(
+            replace_component(&text, &Context::for_test()),
+            TextComponent::from("20 max")
+        );
+    }
+
+    #[test]
+    fn test_invalid_color() {
+        // A placeholder in `color` never reaches replace_component, the text is rejected when it's read
+        let e =
+            TextComponent::from_value(&json!({"text": "a", "color": "{hostname}"})).unwrap_err();
+        assert_eq!(
+            e.to_string(),
+            "unknown color `{hostname}`, expected a color name or #RRGGBB at $.color"
+        );
+    }
+
+    #[test]
+    fn test_is_static() {
+        assert!(is_static("{online}/{max} {client_version}"));
+        assert!(!is_static("{hostname}"));
+        assert!(!is_static("{time}"));
+        assert!(is_static("{{hostname}"));
+    }
+}
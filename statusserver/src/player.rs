@@ -1,17 +1,35 @@
 use std::{
     fmt,
-    io::{Read, Write},
+    io::{ErrorKind, Read, Write},
     net::{SocketAddr, TcpStream},
+    time::Duration,
 };
 
 use byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
 use log::{debug, error, info, warn};
+use mcproto::{ProtocolError, ProtocolVersion, State, versions};
 
-use crate::packets::{self, PacketError, ServerInfo};
+use crate::{
+    packets::{self, PacketError, ServerConfig, ServerInfo, ServerboundPacket},
+    hosts, placeholders,
+};
+
+const LEGACY_PING_TIMEOUT: Duration = Duration::from_millis(100);
+
+/// The server list pings sent by clients from before the 1.7 protocol
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum LegacyPing {
+    /// Beta 1.8 to 1.3: a lone 0xfe
+    Beta,
+    /// 1.4 and 1.5: 0xfe 0x01
+    V1_4,
+    /// 1.6: 0xfe 0x01 followed by a MC|PingHost plugin message
+    V1_6,
+}
 
 #[derive(Debug)]
 pub struct HandshakeInfo {
-    pub protocol: u16,
+    pub protocol: ProtocolVersion,
     pub server_addr: String,
     pub server_port: u16,
 }
@@ -55,6 +73,17 @@ impl fmt::Display for ConnectionState {
     }
 }
 
+impl ConnectionState {
+    /// The protocol state packets are decoded in
+    pub fn protocol_state(&self) -> State {
+        match self {
+            Self::HANDSHAKING => State::Handshaking,
+            Self::STATUS => State::Status,
+            Self::LOGIN | Self::TRANSFER => State::Login,
+        }
+    }
+}
+
 impl std::convert::TryFrom<u8> for ConnectionState {
     type Error = ConnectionStateError;
 
@@ -75,6 +104,23 @@ pub struct Player {
     pub handshake_info: Option<HandshakeInfo>,
 }
 
+/// A legacy kick packet with the response. The length is counted in UTF-16 code units, not bytes,
+/// and responses too long for its 16 bit length are rejected instead of wrapping around
+fn legacy_response(response: &str) -> Result<Vec<u8>, PacketError> {
+    let response: Vec<u16> = response.encode_utf16().collect();
+    let len = u16::try_from(response.len()).map_err(|_| ProtocolError::StringTooLong {
+        len: response.len(),
+        max: u16::MAX as usize,
+    })?;
+    let mut data = Vec::with_capacity(3 + response.len() * 2);
+    data.write_u8(0xff)?;
+    data.write_u16::<BigEndian>(len)?;
+    for v in response {
+        data.write_u16::<BigEndian>(v)?;
+    }
+    Ok(data)
+}
+
 impl Player {
     pub fn new(connection: TcpStream) -> Self {
         let addr = connection.peer_addr().unwrap();
@@ -86,6 +132,19 @@ impl Player {
         }
     }
 
+    /// The hostname the player sent in its handshake, as it was sent
+    pub fn hostname(&self) -> Option<&str> {
+        self.handshake_info.as_ref().map(|info| info.server_addr.as_str())
+    }
+
+    /// The protocol the player sent in its handshake, or 0 before the handshake
+    pub fn protocol(&self) -> ProtocolVersion {
+        match &self.handshake_info {
+            Some(info) => info.protocol,
+            None => ProtocolVersion(0),
+        }
+    }
+
     fn handle_packet<T: Read>(
         &mut self,
         packet: &mut T,
@@ -93,15 +152,17 @@ impl Player {
     ) -> Result<(), PacketError> {
         let packet_id = varint::decode_stream(packet)?;
         debug!("Packet id {:?} by {}", packet_id, self.addr);
-        match packet_id {
-            0 => {
-                packets::handle_status_login(packet, self, server_info)?;
-            }
-            1 => {
-                packets::handle_ping(packet, self)?;
-            }
-            p => {
-                error!("Invalid packet {} sent by {}", p, self.addr);
+        let state = self.state.protocol_state();
+        match ServerboundPacket::decode_versioned(self.protocol().0, state, packet_id, packet)? {
+            Some(ServerboundPacket::Handshake(p)) => packets::handle_handshake(p, self)?,
+            Some(ServerboundPacket::StatusRequest(_)) => packets::handle_status(self, server_info)?,
+            Some(ServerboundPacket::PingRequest(p)) => packets::handle_ping(p, self)?,
+            Some(ServerboundPacket::LoginStart(p)) => packets::handle_login(p, self, server_info)?,
+            None => {
+                error!(
+                    "Invalid packet {} in state {} sent by {}",
+                    packet_id, self.state, self.addr
+                );
             }
         };
         Ok(())
@@ -116,56 +177,132 @@ impl Player {
         for _ in 0..strlen {
             pingstr.push(self.connection.read_u16::<BigEndian>()?);
         }
-        String::from_utf16(&pingstr).or_else(|e| Err(PacketError::FromUtf16Error(e)))
+        String::from_utf16(&pingstr).map_err(PacketError::FromUtf16Error)
+    }
+
+    /// Reads the next byte of a legacy ping, or `None` if the client didn't send one in time.
+    /// Older clients send fewer bytes and then wait for the response, so a short timeout tells them apart
+    fn read_legacy_byte(&mut self) -> Result<Option<u8>, PacketError> {
+        let timeout = self.connection.read_timeout()?;
+        self.connection.set_read_timeout(Some(LEGACY_PING_TIMEOUT))?;
+        let mut buf = [0u8; 1];
+        let res = self.connection.read(&mut buf);
+        self.connection.set_read_timeout(timeout)?;
+        match res {
+            Ok(0) => Err(PacketError::ClosedError),
+            Ok(_) => Ok(Some(buf[0])),
+            Err(e) if matches!(e.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut) => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    fn detect_legacy_ping(&mut self) -> Result<LegacyPing, PacketError> {
+        // The 0xfe packet id was already read
+        match self.read_legacy_byte()? {
+            None => Ok(LegacyPing::Beta),
+            Some(0x01) => match self.read_legacy_byte()? {
+                None => Ok(LegacyPing::V1_4),
+                Some(0xfa) => Ok(LegacyPing::V1_6),
+                Some(b) => Err(PacketError::DataError(vec![0xfe, 0x01, b])),
+            },
+            Some(b) => Err(PacketError::DataError(vec![0xfe, b])),
+        }
     }
 
     fn handle_legacy_ping(&mut self, server_info: &ServerInfo) -> Result<(), PacketError> {
-        let packet_identifier = self.connection.read_u8()?;
-        if packet_identifier != 0xfa {
-            error!(
-                "{}: Invalid legacy ping packet identifier {}",
-                self.addr, packet_identifier
+        let ping = self.detect_legacy_ping()?;
+        debug!("{}: {:?} legacy ping", self.addr, ping);
+        let mut client_protocol = None;
+        let mut host = None;
+        if ping == LegacyPing::V1_6 {
+            let pinghost = self.read_utf16_string()?;
+            if !pinghost.eq("MC|PingHost") {
+                warn!("{}: Unexpected ping string {}", self.addr, pinghost);
+            }
+            self.connection.read_u16::<BigEndian>()?;
+            let protocol = self.connection.read_u8()?;
+            let hostname = self.read_utf16_string()?;
+            let port = self.connection.read_u32::<BigEndian>()?;
+            info!(
+                "(legacy) {} connecting to {}:{} protocol version {}",
+                self.addr, hostname, port, protocol
             );
+            client_protocol = Some(protocol);
+            host = Some((hostname, port as u16));
+        } else {
+            info!("(legacy) {} pinged the server", self.addr);
         }
-        let pinghost = self.read_utf16_string()?;
-        if !pinghost.eq("MC|PingHost") {
-            warn!("{}: Unexpected ping string {}", self.addr, pinghost);
-        }
-        self.connection.read_u16::<BigEndian>()?;
-        let protocol = self.connection.read_u8()?;
-        let hostname = self.read_utf16_string()?;
-        let port = self.connection.read_u32::<BigEndian>()?;
-        info!(
-            "(legacy) {} connecting to {}:{} protocol version {}",
-            self.addr, hostname, port, protocol
-        );
-        // Send response
-        let header = [0x00, 0xa7, 0x00, 0x31, 0x00, 0x00];
-        let protocol = match server_info.config.protocol {
-            Some(p) => p,
-            None => protocol as u16,
+        let config = server_info
+            .config(host.as_ref().map(|(hostname, _)| hostname.as_str()))
+            .for_legacy();
+        let mut context = config.context(self);
+        context.client_version = match (ping, client_protocol) {
+            (LegacyPing::Beta, _) => String::from("Beta 1.8-1.3"),
+            (_, Some(protocol)) => versions::legacy_release_name(protocol as i32)
+                .unwrap_or_else(|| protocol.to_string()),
+            (_, None) => String::from("1.4/1.5"),
         };
-        let response = format!(
-            "{}\x00{}\x00{}\x00{}\x00{}\x00",
-            protocol,
-            server_info.config.version,
-            server_info.config.motd,
-            server_info.config.online_players,
-            server_info.config.max_players
-        );
-        self.connection.write_u8(0xff)?;
-        self.connection
-            .write_u16::<BigEndian>(response.len() as u16)?;
-        self.connection.write(&header)?;
-        let v: Vec<u16> = response.encode_utf16().collect();
-        for v in v {
-            self.connection.write_u16::<BigEndian>(v)?;
+        if let Some((hostname, port)) = host {
+            context.hostname = hosts::normalize(&hostname);
+            context.port = port;
         }
+        let ip = self.addr.ip();
+        let motd = config.motd(ip, Some(&context));
+        let motd = mctext::to_legacy(&config.text_json(&motd, self.protocol()));
+        let response = match ping {
+            // Beta 1.8 to 1.3 split the response on '§', so it can't have color codes
+            LegacyPing::Beta => format!(
+                "{}§{}§{}",
+                mctext::strip_formatting(&motd),
+                config.online_players,
+                config.max_players
+            ),
+            LegacyPing::V1_4 | LegacyPing::V1_6 => {
+                let version = config.version.pick(ip);
+                let (protocol, version) = match (config.protocol, client_protocol) {
+                    (Some(p), _) => (p, ServerConfig::version_name(version, p)),
+                    (None, Some(protocol)) => {
+                        let protocol = ProtocolVersion(protocol as i32);
+                        let version = match versions::legacy_release_name(protocol.0) {
+                            Some(name) if version == ServerConfig::AUTO_VERSION => name.into(),
+                            _ => ServerConfig::version_name(version, protocol),
+                        };
+                        (protocol, version)
+                    }
+                    (None, None) => (
+                        packets::FALLBACK_PROTOCOL,
+                        ServerConfig::version_name(version, packets::FALLBACK_PROTOCOL),
+                    ),
+                };
+                let version = placeholders::replace(&version, &context);
+                format!(
+                    "§1\x00{}\x00{}\x00{}\x00{}\x00{}",
+                    protocol.0, version, motd, config.online_players, config.max_players
+                )
+            }
+        };
+        self.connection.write_all(&legacy_response(&response)?)?;
         Ok(())
     }
 
     pub fn receive_packet(&mut self, server_info: &ServerInfo) -> Result<(), PacketError> {
-        let packet_size = varint::decode_stream(&mut self.connection)?;
+        if self.state == ConnectionState::HANDSHAKING {
+            // Pre-1.7 clients start with 0xfe, which is too big a length for a handshake
+            let mut first = [0u8; 1];
+            if self.connection.peek(&mut first)? == 0 {
+                return Err(PacketError::ClosedError);
+            }
+            if first[0] == 0xfe {
+                self.connection.read_exact(&mut first)?;
+                self.handle_legacy_ping(server_info)?;
+                return Err(PacketError::ClosedError);
+            }
+        }
+        let packet_size = match varint::decode_stream(&mut self.connection) {
+            Ok(size) => size,
+            Err(varint::VarIntError::UnexpectedEof) => return Err(PacketError::ClosedError),
+            Err(e) => return Err(e.into()),
+        };
         debug!("{} sent packet sized {}", self.addr, packet_size);
         if packet_size <= 0 {
             return Err(PacketError::ClosedError);
@@ -173,10 +310,6 @@ impl Player {
         if packet_size > 256 {
             return Err(PacketError::ClosedError);
         }
-        if packet_size == 254 && self.state == ConnectionState::HANDSHAKING {
-            self.handle_legacy_ping(server_info)?;
-            return Ok(());
-        }
         let packet_size = packet_size as usize;
         let mut buf = vec![0; packet_size];
         self.connection.read_exact(&mut buf)?;
@@ -184,3 +317,23 @@ impl Player {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_legacy_response() {
+        assert_eq!(
+            legacy_response("a§").unwrap(),
+            vec![0xff, 0x00, 0x02, 0x00, b'a', 0x00, 0xa7]
+        );
+        let longest = "a".repeat(u16::MAX as usize);
+        assert_eq!(legacy_response(&longest).unwrap()[1..3], [0xff, 0xff]);
+        let too_long = "a".repeat(u16::MAX as usize + 1);
+        assert!(matches!(
+            legacy_response(&too_long),
+            Err(PacketError::ProtocolError(ProtocolError::StringTooLong { .. }))
+        ));
+    }
+}
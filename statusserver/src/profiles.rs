@@ -0,0 +1,448 @@
+use chrono::{
+    DateTime, Datelike, Local, LocalResult, NaiveDate, NaiveDateTime, TimeDelta, TimeZone,
+    Timelike, Utc,
+};
+use serde::{Deserialize, Deserializer, de};
+
+/// The keys of a profile that say when it's active; the rest replace fields of the config
+pub const SCHEDULE_KEYS: [&str; 5] = ["name", "start", "end", "cron", "duration"];
+
+/// Reads a time like `2026-12-01T18:00Z`, `2026-12-01T18:00:00+01:00` or `2026-12-01`.
+/// Times without an offset are in the server's local time
+pub fn parse_time(text: &str) -> Option<DateTime<Utc>> {
+    let text = text.trim().replace(' ', "T");
+    let text = match text.strip_suffix(['Z', 'z']) {
+        Some(t) => format!("{}+00:00", t),
+        None => text,
+    };
+    if let Ok(time) = DateTime::parse_from_rfc3339(&text) {
+        return Some(time.to_utc());
+    }
+    if let Ok(time) = DateTime::parse_from_str(&text, "%Y-%m-%dT%H:%M%:z") {
+        return Some(time.to_utc());
+    }
+    let naive = ["%Y-%m-%dT%H:%M:%S%.f", "%Y-%m-%dT%H:%M"]
+        .iter()
+        .find_map(|f| NaiveDateTime::parse_from_str(&text, f).ok())
+        .or_else(|| {
+            NaiveDate::parse_from_str(&text, "%Y-%m-%d")
+                .ok()
+                .and_then(|d| d.and_hms_opt(0, 0, 0))
+        })?;
+    Some(Local.from_local_datetime(&naive).earliest()?.to_utc())
+}
+
+/// How long until `time`, in its 2 largest units like `3d 4h`
+pub fn format_countdown(time: DateTime<Utc>, now: DateTime<Utc>) -> String {
+    let secs = (time - now).num_seconds().max(0);
+    let (days, hours, minutes, secs) = (
+        secs / 86400,
+        secs % 86400 / 3600,
+        secs % 3600 / 60,
+        secs % 60,
+    );
+    if days > 0 {
+        format!("{}d {}h", days, hours)
+    } else if hours > 0 {
+        format!("{}h {}m", hours, minutes)
+    } else if minutes > 0 {
+        format!("{}m {}s", minutes, secs)
+    } else {
+        format!("{}s", secs)
+    }
+}
+
+/// Reads a duration like `90m`, `2h` or `1h30m`
+pub fn parse_duration(text: &str) -> Option<TimeDelta> {
+    let mut total = TimeDelta::zero();
+    let mut number = String::new();
+    for c in text.trim().chars() {
+        if c.is_ascii_digit() {
+            number.push(c);
+            continue;
+        }
+        let n: i64 = number.parse().ok()?;
+        number.clear();
+        total += match c {
+            's' => TimeDelta::try_seconds(n)?,
+            'm' => TimeDelta::try_minutes(n)?,
+            'h' => TimeDelta::try_hours(n)?,
+            'd' => TimeDelta::try_days(n)?,
+            _ => return None,
+        };
+    }
+    if !number.is_empty() || total.is_zero() {
+        return None;
+    }
+    Some(total)
+}
+
+const MONTHS: [&str; 12] = [
+    "jan", "feb", "mar", "apr", "may", "jun", "jul", "aug", "sep", "oct", "nov", "dec",
+];
+const WEEKDAYS: [&str; 7] = ["sun", "mon", "tue", "wed", "thu", "fri", "sat"];
+
+/// The values a cron field matches, as bits
+fn cron_field(field: &str, min: u32, max: u32, names: &[&str]) -> Result<u64, String> {
+    let value = |v: &str| -> Result<u32, String> {
+        let name = v.to_ascii_lowercase();
+        if let Some(i) = names.iter().position(|n| *n == name) {
+            return Ok(i as u32 + min);
+        }
+        match v.parse() {
+            Ok(n) if (min..=max).contains(&n) => Ok(n),
+            _ => Err(format!("'{}' isn't between {} and {}", v, min, max)),
+        }
+    };
+    let mut bits = 0;
+    for part in field.split(',') {
+        let (range, step) = match part.split_once('/') {
+            Some((range, step)) => match step.parse::<u32>() {
+                Ok(step) if step > 0 => (range, step),
+                _ => return Err(format!("invalid step '{}'", step)),
+            },
+            None => (part, 1),
+        };
+        let (start, end) = match range.split_once('-') {
+            _ if range == "*" => (min, max),
+            Some((start, end)) => (value(start)?, value(end)?),
+            // `5/15` means from 5 to the end, every 15
+            None if step > 1 => (value(range)?, max),
+            None => (value(range)?, value(range)?),
+        };
+        if start > end {
+            return Err(format!("invalid range '{}'", range));
+        }
+        for v in (start..=end).step_by(step as usize) {
+            bits |= 1 << v;
+        }
+    }
+    Ok(bits)
+}
+
+/// A cron expression: minute, hour, day of the month, month and day of the week
+#[derive(Debug, Clone, PartialEq)]
+pub struct Cron {
+    minutes: u64,
+    hours: u64,
+    days: u64,
+    months: u64,
+    weekdays: u64,
+    /// Like cron, when both days are restricted a time matches if either does
+    any_day: bool,
+    any_weekday: bool,
+}
+
+impl Cron {
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let fields: Vec<&str> = text.split_whitespace().collect();
+        let [minutes, hours, days, months, weekdays] = fields[..] else {
+            return Err(format!("'{}' should have 5 fields", text));
+        };
+        let mut weekday_bits = cron_field(weekdays, 0, 7, &WEEKDAYS)?;
+        // Both 0 and 7 are sunday
+        if weekday_bits & (1 << 7) != 0 {
+            weekday_bits |= 1;
+        }
+        Ok(Cron {
+            minutes: cron_field(minutes, 0, 59, &[])?,
+            hours: cron_field(hours, 0, 23, &[])?,
+            days: cron_field(days, 1, 31, &[])?,
+            months: cron_field(months, 1, 12, &MONTHS)?,
+            weekdays: weekday_bits,
+            any_day: days.starts_with('*'),
+            any_weekday: weekdays.starts_with('*'),
+        })
+    }
+
+    /// Whether `date` matches, ignoring the time
+    fn matches_day(&self, date: NaiveDate) -> bool {
+        let has = |bits: u64, v: u32| bits & (1 << v) != 0;
+        let day = has(self.days, date.day());
+        let weekday = has(self.weekdays, date.weekday().num_days_from_sunday());
+        let day = match (self.any_day, self.any_weekday) {
+            (false, false) => day || weekday,
+            _ => day && weekday,
+        };
+        day && has(self.months, date.month())
+    }
+
+    /// The last matching minute at or before `before`, in the server's local time.
+    /// Skips whole months and days that don't match, and gives up before `since`
+    fn last_match(&self, before: NaiveDateTime, since: NaiveDate) -> Option<NaiveDateTime> {
+        // The highest value in `bits` that's at most `max`
+        let last = |bits: u64, max: u32| {
+            let bits = bits & (u64::MAX >> (63 - max));
+            (bits != 0).then(|| 63 - bits.leading_zeros())
+        };
+        let mut date = before.date();
+        while date >= since {
+            if self.months & (1 << date.month()) == 0 {
+                date = date.with_day(1)?.pred_opt()?;
+                continue;
+            }
+            if self.matches_day(date) {
+                let today = date == before.date();
+                let mut hour = last(self.hours, if today { before.hour() } else { 23 });
+                while let Some(h) = hour {
+                    let max_minute = match today && h == before.hour() {
+                        true => before.minute(),
+                        false => 59,
+                    };
+                    if let Some(m) = last(self.minutes, max_minute) {
+                        return date.and_hms_opt(h, m, 0);
+                    }
+                    hour = h.checked_sub(1).and_then(|h| last(self.hours, h));
+                }
+            }
+            date = date.pred_opt()?;
+        }
+        None
+    }
+}
+
+/// When a profile is active: between `start` and `end`, and for `duration` after
+/// each time matching `cron`
+#[derive(Debug, Clone)]
+pub struct Schedule {
+    pub start: Option<DateTime<Utc>>,
+    pub end: Option<DateTime<Utc>>,
+    pub cron: Option<(Cron, TimeDelta)>,
+}
+
+impl Schedule {
+    pub fn is_active(&self, now: DateTime<Utc>) -> bool {
+        if self.start.is_some_and(|start| now < start) || self.end.is_some_and(|end| now >= end) {
+            return false;
+        }
+        let Some((cron, duration)) = &self.cron else {
+            return true;
+        };
+        // Only the last matching minute can still be going on
+        let local = now.with_timezone(&Local).naive_local();
+        let since = (now - *duration).with_timezone(&Local).date_naive();
+        let mut before = local;
+        while let Some(minute) = cron.last_match(before, since) {
+            let start = match Local.from_local_datetime(&minute) {
+                LocalResult::Single(t) => t,
+                // The hour repeated when the clocks go back
+                LocalResult::Ambiguous(_, second) if second <= now => second,
+                LocalResult::Ambiguous(first, _) => first,
+                // Skipped when the clocks go forward
+                LocalResult::None => {
+                    before = minute - TimeDelta::minutes(1);
+                    continue;
+                }
+            };
+            return start + *duration > now;
+        }
+        false
+    }
+}
+
+/// Reads a TOML datetime or a string with [`parse_time`]
+fn time<'de, D: Deserializer<'de>>(d: D) -> Result<Option<DateTime<Utc>>, D::Error> {
+    let text = match toml::Value::deserialize(d)? {
+        toml::Value::String(s) => s,
+        toml::Value::Datetime(dt) => dt.to_string(),
+        v => return Err(de::Error::custom(format!("expected a time, found {}", v.type_str()))),
+    };
+    parse_time(&text)
+        .map(Some)
+        .ok_or_else(|| de::Error::custom(format!("invalid time '{}'", text)))
+}
+
+/// The keys of a profile in [`SCHEDULE_KEYS`]
+#[derive(Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct ProfileHeader {
+    pub name: String,
+    #[serde(default, deserialize_with = "time")]
+    start: Option<DateTime<Utc>>,
+    #[serde(default, deserialize_with = "time")]
+    end: Option<DateTime<Utc>>,
+    cron: Option<String>,
+    duration: Option<String>,
+}
+
+impl ProfileHeader {
+    pub fn schedule(&self) -> Result<Schedule, String> {
+        let cron = match (&self.cron, &self.duration) {
+            (Some(cron), Some(duration)) => {
+                let duration = parse_duration(duration)
+                    .ok_or_else(|| format!("invalid duration '{}'", duration))?;
+                Some((Cron::parse(cron)?, duration))
+            }
+            (Some(_), None) => return Err("cron needs a duration, like '2h'".to_string()),
+            (None, Some(_)) => return Err("duration is only used with cron".to_string()),
+            (None, None) => None,
+        };
+        if cron.is_none() && self.start.is_none() && self.end.is_none() {
+            return Err("needs a start, an end or a cron schedule".to_string());
+        }
+        if let (Some(start), Some(end)) = (self.start, self.end)
+            && start >= end
+        {
+            return Err("ends before it starts".to_string());
+        }
+        Ok(Schedule {
+            start: self.start,
+            end: self.end,
+            cron,
+        })
+    }
+}
+
+/// A named set of config fields used while its schedule is active.
+/// Each host's config for it is in its [`ConfigSet`](crate::hosts::ConfigSet)
+#[derive(Debug)]
+pub struct Profile {
+    pub name: String,
+    pub schedule: Schedule,
+}
+
+/// The index of the first profile that's active at `now`
+pub fn active(profiles: &[Profile], now: DateTime<Utc>) -> Option<usize> {
+    profiles.iter().position(|p| p.schedule.is_active(now))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bits(values: &[u32]) -> u64 {
+        values.iter().fold(0, |bits, v| bits | 1 << v)
+    }
+
+    fn local(y: i32, mo: u32, d: u32, h: u32, mi: u32) -> DateTime<Utc> {
+        Local.with_ymd_and_hms(y, mo, d, h, mi, 0).unwrap().to_utc()
+    }
+
+    fn date(y: i32, m: u32, d: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(y, m, d).unwrap()
+    }
+
+    fn schedule(cron: &str, duration: &str) -> Schedule {
+        Schedule {
+            start: None,
+            end: None,
+            cron: Some((
+                Cron::parse(cron).unwrap(),
+                parse_duration(duration).unwrap(),
+            )),
+        }
+    }
+
+    #[test]
+    fn test_cron_field() {
+        assert_eq!(cron_field("*/15", 0, 59, &[]), Ok(bits(&[0, 15, 30, 45])));
+        assert_eq!(cron_field("5/15", 0, 59, &[]), Ok(bits(&[5, 20, 35, 50])));
+        assert_eq!(cron_field("1-3,10", 0, 59, &[]), Ok(bits(&[1, 2, 3, 10])));
+        assert_eq!(cron_field("jan-mar", 1, 12, &MONTHS), Ok(bits(&[1, 2, 3])));
+        assert_eq!(
+            cron_field("Mon-Fri", 0, 7, &WEEKDAYS),
+            Ok(bits(&[1, 2, 3, 4, 5]))
+        );
+        assert!(cron_field("60", 0, 59, &[]).is_err());
+        assert!(cron_field("*/0", 0, 59, &[]).is_err());
+        assert!(cron_field("5-1", 0, 59, &[]).is_err());
+        assert!(cron_field("foo", 1, 12, &MONTHS).is_err());
+    }
+
+    #[test]
+    fn test_cron_parse() {
+        let cron = Cron::parse("0 18 * * 7").unwrap();
+        assert_eq!(cron.weekdays, bits(&[0, 7]));
+        assert_eq!(Cron::parse("0 18 * * sun").unwrap().weekdays, bits(&[0]));
+        assert!(Cron::parse("0 18 * *").is_err());
+        assert!(Cron::parse("0 24 * * *").is_err());
+    }
+
+    #[test]
+    fn test_cron_days() {
+        // Friday the 13th and every friday, like cron
+        let cron = Cron::parse("0 0 13 * fri").unwrap();
+        assert!(cron.matches_day(date(2026, 11, 13)));
+        assert!(cron.matches_day(date(2026, 11, 6)));
+        assert!(cron.matches_day(date(2026, 12, 13)));
+        assert!(!cron.matches_day(date(2026, 11, 7)));
+        // With only one of them restricted, both have to match
+        let cron = Cron::parse("0 0 13 * *").unwrap();
+        assert!(!cron.matches_day(date(2026, 11, 6)));
+        let cron = Cron::parse("0 0 * feb fri").unwrap();
+        assert!(cron.matches_day(date(2026, 2, 6)));
+        assert!(!cron.matches_day(date(2026, 3, 6)));
+    }
+
+    #[test]
+    fn test_last_match() {
+        let cron = Cron::parse("30 18 * * fri").unwrap();
+        let at = |d: NaiveDate, h, m| d.and_hms_opt(h, m, 0).unwrap();
+        let friday = date(2026, 11, 6);
+        assert_eq!(
+            cron.last_match(at(friday, 18, 30), date(2026, 1, 1)),
+            Some(at(friday, 18, 30))
+        );
+        assert_eq!(
+            cron.last_match(at(friday, 18, 29), date(2026, 1, 1)),
+            Some(at(date(2026, 10, 30), 18, 30))
+        );
+        assert_eq!(
+            cron.last_match(at(date(2026, 11, 12), 9, 0), date(2026, 1, 1)),
+            Some(at(friday, 18, 30))
+        );
+        assert_eq!(cron.last_match(at(friday, 18, 29), date(2026, 11, 1)), None);
+    }
+
+    #[test]
+    fn test_schedule() {
+        let friday = schedule("0 18 * * fri", "2h");
+        assert!(!friday.is_active(local(2026, 11, 6, 17, 59)));
+        assert!(friday.is_active(local(2026, 11, 6, 18, 0)));
+        assert!(friday.is_active(local(2026, 11, 6, 19, 59)));
+        assert!(!friday.is_active(local(2026, 11, 6, 20, 0)));
+        let new_year = schedule("0 0 1 1 *", "365d");
+        assert!(new_year.is_active(local(2026, 6, 1, 12, 0)));
+        let new_year = schedule("0 0 1 1 *", "1d");
+        assert!(new_year.is_active(local(2026, 1, 1, 12, 0)));
+        assert!(!new_year.is_active(local(2026, 1, 2, 12, 0)));
+    }
+
+    #[test]
+    fn test_parse_duration() {
+        assert_eq!(parse_duration("90m"), Some(TimeDelta::minutes(90)));
+        assert_eq!(parse_duration("1h30m"), Some(TimeDelta::minutes(90)));
+        assert_eq!(parse_duration("2d"), Some(TimeDelta::days(2)));
+        assert_eq!(parse_duration("90"), None);
+        assert_eq!(parse_duration("0m"), None);
+        assert_eq!(parse_duration("1w"), None);
+    }
+
+    #[test]
+    fn test_parse_time() {
+        let utc = Utc.with_ymd_and_hms(2026, 12, 1, 18, 0, 0).unwrap();
+        assert_eq!(parse_time("2026-12-01T18:00Z"), Some(utc));
+        assert_eq!(parse_time("2026-12-01T19:00:00+01:00"), Some(utc));
+        assert_eq!(parse_time("2026-12-01"), Some(local(2026, 12, 1, 0, 0)));
+        assert_eq!(
+            parse_time("2026-12-01T18:00"),
+            Some(local(2026, 12, 1, 18, 0))
+        );
+        assert_eq!(
+            parse_time("2026-12-01 18:00"),
+            Some(local(2026, 12, 1, 18, 0))
+        );
+        assert_eq!(parse_time("tomorrow"), None);
+    }
+
+    #[test]
+    fn test_format_countdown() {
+        let now = Utc.with_ymd_and_hms(2026, 12, 1, 18, 0, 0).unwrap();
+        let countdown = |delta| format_countdown(now + delta, now);
+        assert_eq!(countdown(TimeDelta::hours(76)), "3d 4h");
+        assert_eq!(countdown(TimeDelta::minutes(90)), "1h 30m");
+        assert_eq!(countdown(TimeDelta::seconds(125)), "2m 5s");
+        assert_eq!(countdown(TimeDelta::seconds(9)), "9s");
+        assert_eq!(countdown(TimeDelta::seconds(-60)), "0s");
+    }
+}
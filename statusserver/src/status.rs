@@ -0,0 +1,212 @@
+use std::{
+    borrow::Cow,
+    sync::{Arc, RwLock},
+};
+
+use mcproto::ProtocolVersion;
+use serde::{Serialize, Serializer};
+use serde_json::Value;
+use uuid::Uuid;
+
+use crate::{
+    cache::BoundedCache,
+    forge::{FmlVersion, ForgeStatus},
+};
+
+/// How many responses a config keeps, so clients sending every protocol can't fill the memory.
+/// The oldest ones are dropped to make room for new ones
+const CACHE_LIMIT: usize = 256;
+
+/// The status response, as it's sent to clients
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct Status<'a> {
+    pub version: Version<'a>,
+    pub players: Players<'a>,
+    pub description: Value,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub favicon: Option<Favicon<'a>>,
+    pub enforces_secure_chat: bool,
+    #[serde(skip_serializing_if = "is_false")]
+    pub prevents_chat_reports: bool,
+    #[serde(skip_serializing_if = "is_false")]
+    pub previews_chat: bool,
+    #[serde(flatten)]
+    pub forge: Option<ForgeStatus<'a>>,
+}
+
+fn is_false(value: &bool) -> bool {
+    !value
+}
+
+#[derive(Serialize, Debug)]
+pub struct Version<'a> {
+    pub name: Cow<'a, str>,
+    pub protocol: i32,
+}
+
+#[derive(Serialize, Debug)]
+pub struct Players<'a> {
+    pub max: i32,
+    pub online: i32,
+    pub sample: Vec<Sample<'a>>,
+}
+
+/// A name in the player list
+#[derive(Serialize, Debug)]
+pub struct Sample<'a> {
+    pub name: Cow<'a, str>,
+    pub id: Uuid,
+}
+
+/// A base64 png icon, written as a data URL
+#[derive(Debug)]
+pub struct Favicon<'a>(pub Cow<'a, str>);
+
+impl Serialize for Favicon<'_> {
+    fn serialize<S: Serializer>(&self, s: S) -> Result<S::Ok, S::Error> {
+        s.collect_str(&format_args!("data:image/png;base64,{}", self.0))
+    }
+}
+
+/// Everything a cached response depends on
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct StatusKey {
+    pub protocol: ProtocolVersion,
+    pub fml: Option<FmlVersion>,
+    /// The indexes of the frames picked
+    pub motd: usize,
+    pub version: usize,
+    pub icon: usize,
+}
+
+/// Framed status response packets of a config, for configs where they only depend on a [`StatusKey`].
+/// A new cache is made with every config, so reloading the config or its icons clears it
+#[derive(Debug, Default)]
+pub struct StatusCache {
+    enabled: bool,
+    packets: RwLock<BoundedCache<StatusKey, Arc<[u8]>, CACHE_LIMIT>>,
+}
+
+impl StatusCache {
+    pub fn new(enabled: bool) -> Self {
+        StatusCache {
+            enabled,
+            packets: RwLock::default(),
+        }
+    }
+
+    /// The packet for `key`, rendered and kept if it isn't cached yet
+    pub fn get_or_render<E>(
+        &self,
+        key: StatusKey,
+        render: impl FnOnce() -> Result<Vec<u8>, E>,
+    ) -> Result<Arc<[u8]>, E> {
+        if !self.enabled {
+            return render().map(Arc::from);
+        }
+        if let Some(packet) = self.packets.read().unwrap().get(&key) {
+            return Ok(packet.clone());
+        }
+        let packet: Arc<[u8]> = render()?.into();
+        self.packets.write().unwrap().insert(key, packet.clone());
+        Ok(packet)
+    }
+
+    #[cfg(test)]
+    fn len(&self) -> usize {
+        self.packets.read().unwrap().len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{cell::Cell, convert::Infallible};
+
+    use crate::packets::ServerConfig;
+
+    use super::*;
+
+    fn key(protocol: i32) -> StatusKey {
+        StatusKey {
+            protocol: ProtocolVersion(protocol),
+            fml: None,
+            motd: 0,
+            version: 0,
+            icon: 0,
+        }
+    }
+
+    /// Gets `key` from the cache, counting the renders
+    fn get(cache: &StatusCache, key: StatusKey, renders: &Cell<usize>) -> Arc<[u8]> {
+        cache
+            .get_or_render(key, || {
+                renders.set(renders.get() + 1);
+                Ok::<_, Infallible>(key.protocol.0.to_be_bytes().to_vec())
+            })
+            .unwrap()
+    }
+
+    #[test]
+    fn test_static_configs() {
+        assert!(ServerConfig::for_test("§astatus server").status_is_static());
+        assert!(ServerConfig::for_test("§a{online}/{max} on {client_version}").status_is_static());
+        assert!(!ServerConfig::for_test("on {hostname}").status_is_static());
+        assert!(!ServerConfig::for_test("the time is {time}").status_is_static());
+        assert!(!ServerConfig::for_test("§a{time:%H:%M:%S}").status_is_static());
+    }
+
+    #[test]
+    fn test_disabled() {
+        let cache = StatusCache::new(false);
+        let renders = Cell::new(0);
+        get(&cache, key(767), &renders);
+        get(&cache, key(767), &renders);
+        assert_eq!(renders.get(), 2);
+        assert_eq!(cache.len(), 0);
+    }
+
+    #[test]
+    fn test_keys() {
+        let cache = StatusCache::new(true);
+        let renders = Cell::new(0);
+        assert_eq!(&*get(&cache, key(767), &renders), 767i32.to_be_bytes());
+        assert_eq!(&*get(&cache, key(767), &renders), 767i32.to_be_bytes());
+        assert_eq!(renders.get(), 1);
+        // Protocols get their own response, since `{client_version}` and `auto` depend on it
+        assert_eq!(&*get(&cache, key(766), &renders), 766i32.to_be_bytes());
+        assert_eq!(renders.get(), 2);
+        let motd = StatusKey {
+            motd: 1,
+            ..key(767)
+        };
+        let icon = StatusKey {
+            icon: 1,
+            ..key(767)
+        };
+        let fml = StatusKey {
+            fml: Some(FmlVersion::Fml3),
+            ..key(767)
+        };
+        for key in [motd, icon, fml, icon] {
+            get(&cache, key, &renders);
+        }
+        assert_eq!(renders.get(), 5);
+    }
+
+    #[test]
+    fn test_limit() {
+        let cache = StatusCache::new(true);
+        let renders = Cell::new(0);
+        for protocol in 0..CACHE_LIMIT as i32 + 10 {
+            get(&cache, key(protocol), &renders);
+        }
+        assert_eq!(cache.len(), CACHE_LIMIT);
+        // New responses are still cached, the oldest ones are dropped
+        let newest = key(CACHE_LIMIT as i32 + 9);
+        get(&cache, newest, &renders);
+        assert_eq!(renders.get(), CACHE_LIMIT + 10);
+        get(&cache, key(0), &renders);
+        assert_eq!(renders.get(), CACHE_LIMIT + 11);
+    }
+}
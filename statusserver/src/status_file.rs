@@ -0,0 +1,150 @@
+use log::warn;
+use mcproto::{ProtocolVersion, STRING_MAX_LEN};
+use serde::Deserialize;
+use serde_json::Value;
+
+use crate::placeholders::{self, Context};
+
+/// A status response in the config directory, sent instead of the one made from the config
+pub const STATUS_FILE: &str = "status.json";
+
+/// What's changed in `status.json` before it's sent
+#[derive(Deserialize, Debug, Default, Clone, Copy)]
+#[serde(deny_unknown_fields)]
+pub struct StatusFileOptions {
+    /// Replace placeholders in its strings
+    #[serde(default)]
+    pub placeholders: bool,
+    /// Send the client's protocol as `version.protocol`
+    #[serde(default)]
+    pub echo_protocol: bool,
+}
+
+/// A status response read from [`STATUS_FILE`]
+#[derive(Debug)]
+pub struct StatusFile {
+    text: String,
+    value: Value,
+}
+
+impl StatusFile {
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let value: Value = serde_json::from_str(text).map_err(|e| e.to_string())?;
+        if !value.is_object() {
+            return Err(String::from("should be a JSON object"));
+        }
+        let text = text.trim();
+        let len = text.encode_utf16().count();
+        if len > STRING_MAX_LEN {
+            return Err(format!("is {} characters long, more than the {} a response can have", len, STRING_MAX_LEN));
+        }
+        Ok(StatusFile {
+            text: text.to_string(),
+            value,
+        })
+    }
+
+    pub fn value(&self) -> &Value {
+        &self.value
+    }
+
+    /// The response for a client, which is the file as it was written unless `options` change it
+    pub fn response(&self, options: StatusFileOptions, context: &Context, protocol: ProtocolVersion) -> String {
+        if !options.placeholders && !options.echo_protocol {
+            return self.text.clone();
+        }
+        let mut value = self.value.clone();
+        if options.placeholders {
+            placeholders::replace_json(&mut value, context);
+        }
+        if options.echo_protocol
+            && let Some(Value::Object(version)) = value.get_mut("version")
+        {
+            version.insert("protocol".to_string(), protocol.0.into());
+        }
+        let response = value.to_string();
+        // Long placeholder values can push it over the limit that was checked when it was read
+        let len = response.encode_utf16().count();
+        if len > STRING_MAX_LEN {
+            warn!(
+                "{} is {} characters long with placeholders replaced, sending it as it was written",
+                STATUS_FILE, len
+            );
+            return self.text.clone();
+        }
+        response
+    }
+
+    /// Problems with the file that don't stop it from being sent
+    pub fn warnings(&self, options: StatusFileOptions) -> Vec<String> {
+        let mut warnings = Vec::new();
+        if options.echo_protocol && !self.value.get("version").is_some_and(Value::is_object) {
+            warnings.push(format!("{} has no version, so the protocol isn't echoed", STATUS_FILE));
+        }
+        warnings
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    fn context(hostname: &str) -> Context {
+        Context {
+            hostname: hostname.to_string(),
+            ..Context::for_test()
+        }
+    }
+
+    fn options(placeholders: bool, echo_protocol: bool) -> StatusFileOptions {
+        StatusFileOptions {
+            placeholders,
+            echo_protocol,
+        }
+    }
+
+    const TEXT: &str = r#"{"version": {"name": "{client_version}", "protocol": 1},   "description": "on {hostname}"}"#;
+
+    #[test]
+    fn test_verbatim() {
+        let file = StatusFile::parse(&format!("\n{}\n", TEXT)).unwrap();
+        let response = file.response(options(false, false), &context("a"), ProtocolVersion(767));
+        assert_eq!(response, TEXT);
+    }
+
+    #[test]
+    fn test_options() {
+        let file = StatusFile::parse(TEXT).unwrap();
+        let response = |options| {
+            let response = file.response(options, &context("mc.example.com"), ProtocolVersion(767));
+            serde_json::from_str::<Value>(&response).unwrap()
+        };
+        assert_eq!(
+            response(options(false, true)),
+            json!({"version": {"name": "{client_version}", "protocol": 767}, "description": "on {hostname}"})
+        );
+        assert_eq!(
+            response(options(true, false)),
+            json!({"version": {"name": "1.21.4", "protocol": 1}, "description": "on mc.example.com"})
+        );
+    }
+
+    #[test]
+    fn test_invalid() {
+        assert_eq!(StatusFile::parse("[1, 2]").unwrap_err(), "should be a JSON object");
+        assert!(StatusFile::parse("{").is_err());
+        let long = json!({"description": "a".repeat(STRING_MAX_LEN)}).to_string();
+        assert!(StatusFile::parse(&long).unwrap_err().starts_with("is 32785 characters long"));
+    }
+
+    #[test]
+    fn test_too_long_with_placeholders() {
+        let text = json!({"description": format!("{}{{hostname}}", "a".repeat(STRING_MAX_LEN - 100))}).to_string();
+        let file = StatusFile::parse(&text).unwrap();
+        let hostname = "b".repeat(200);
+        let response = file.response(options(true, false), &context(&hostname), ProtocolVersion(767));
+        assert_eq!(response, text);
+    }
+}
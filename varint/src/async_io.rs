@@ -0,0 +1,73 @@
+use std::io;
+
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+
+use crate::{MAX_VARINT_LEN, MAX_VARLONG_LEN, PartialDecoder, VarIntError};
+
+/// Reads a VarInt from an async reader, one byte at a time
+pub async fn decode_async<R: AsyncRead + Unpin>(reader: &mut R) -> Result<i32, VarIntError> {
+    let mut decoder = PartialDecoder::new();
+    loop {
+        if let Some(n) = decoder.push_varint(reader.read_u8().await?)? {
+            return Ok(n);
+        }
+    }
+}
+
+/// Reads a VarLong from an async reader, one byte at a time
+pub async fn decode_long_async<R: AsyncRead + Unpin>(reader: &mut R) -> Result<i64, VarIntError> {
+    let mut decoder = PartialDecoder::new();
+    loop {
+        if let Some(n) = decoder.push_varlong(reader.read_u8().await?)? {
+            return Ok(n);
+        }
+    }
+}
+
+/// Writes a VarInt to an async writer, returning the amount of bytes written
+pub async fn encode_async<W: AsyncWrite + Unpin>(n: i32, writer: &mut W) -> io::Result<usize> {
+    let mut buf = [0u8; MAX_VARINT_LEN];
+    let len = crate::encode_buf(n, &mut buf);
+    writer.write_all(&buf[..len]).await?;
+    Ok(len)
+}
+
+/// Writes a VarLong to an async writer, returning the amount of bytes written
+pub async fn encode_long_async<W: AsyncWrite + Unpin>(n: i64, writer: &mut W) -> io::Result<usize> {
+    let mut buf = [0u8; MAX_VARLONG_LEN];
+    let len = crate::encode_long_buf(n, &mut buf);
+    writer.write_all(&buf[..len]).await?;
+    Ok(len)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_roundtrip() {
+        let mut out = Vec::new();
+        for n in [0, 1, 128, 2835928, i32::MAX, -1, i32::MIN] {
+            encode_async(n, &mut out).await.unwrap();
+        }
+        encode_long_async(i64::MIN, &mut out).await.unwrap();
+        let mut input = out.as_slice();
+        for n in [0, 1, 128, 2835928, i32::MAX, -1, i32::MIN] {
+            assert_eq!(decode_async(&mut input).await.unwrap(), n);
+        }
+        assert_eq!(decode_long_async(&mut input).await.unwrap(), i64::MIN);
+        assert!(matches!(
+            decode_async(&mut input).await,
+            Err(VarIntError::UnexpectedEof)
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_too_long() {
+        let data = [0xffu8; 6];
+        assert!(matches!(
+            decode_async(&mut data.as_slice()).await,
+            Err(VarIntError::TooLong)
+        ));
+    }
+}
@@ -0,0 +1,92 @@
+use bytes::{Buf, BufMut};
+
+use crate::{MAX_VARINT_LEN, MAX_VARLONG_LEN, PartialDecoder, VarIntError};
+
+/// Reads a VarInt from `buf`, failing if it ends before the number does
+pub fn get_varint<B: Buf>(buf: &mut B) -> Result<i32, VarIntError> {
+    let mut decoder = PartialDecoder::new();
+    match decoder.decode_buf(buf)? {
+        Some(n) => Ok(n),
+        None => Err(VarIntError::UnexpectedEof),
+    }
+}
+
+/// Reads a VarLong from `buf`, failing if it ends before the number does
+pub fn get_varlong<B: Buf>(buf: &mut B) -> Result<i64, VarIntError> {
+    let mut decoder = PartialDecoder::new();
+    match decoder.decode_long_buf(buf)? {
+        Some(n) => Ok(n),
+        None => Err(VarIntError::UnexpectedEof),
+    }
+}
+
+/// Writes a VarInt to `buf`, returning the amount of bytes written
+pub fn put_varint<B: BufMut>(n: i32, buf: &mut B) -> usize {
+    let mut bytes = [0u8; MAX_VARINT_LEN];
+    let len = crate::encode_buf(n, &mut bytes);
+    buf.put_slice(&bytes[..len]);
+    len
+}
+
+/// Writes a VarLong to `buf`, returning the amount of bytes written
+pub fn put_varlong<B: BufMut>(n: i64, buf: &mut B) -> usize {
+    let mut bytes = [0u8; MAX_VARLONG_LEN];
+    let len = crate::encode_long_buf(n, &mut bytes);
+    buf.put_slice(&bytes[..len]);
+    len
+}
+
+impl PartialDecoder {
+    /// Consumes the bytes of a VarInt available in `buf`.
+    /// Returns `None` when more data is needed, keeping what was read so far
+    pub fn decode_buf<B: Buf>(&mut self, buf: &mut B) -> Result<Option<i32>, VarIntError> {
+        while buf.has_remaining() {
+            if let Some(n) = self.push_varint(buf.get_u8())? {
+                return Ok(Some(n));
+            }
+        }
+        Ok(None)
+    }
+
+    /// Consumes the bytes of a VarLong available in `buf`.
+    /// Returns `None` when more data is needed, keeping what was read so far
+    pub fn decode_long_buf<B: Buf>(&mut self, buf: &mut B) -> Result<Option<i64>, VarIntError> {
+        while buf.has_remaining() {
+            if let Some(n) = self.push_varlong(buf.get_u8())? {
+                return Ok(Some(n));
+            }
+        }
+        Ok(None)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bytes::BytesMut;
+
+    use super::*;
+
+    #[test]
+    fn test_roundtrip() {
+        let mut out = BytesMut::new();
+        assert_eq!(put_varint(300, &mut out), 2);
+        assert_eq!(put_varlong(-1, &mut out), 10);
+        let mut out = out.freeze();
+        assert_eq!(get_varint(&mut out).unwrap(), 300);
+        assert_eq!(get_varlong(&mut out).unwrap(), -1);
+        assert!(matches!(
+            get_varint(&mut out),
+            Err(VarIntError::UnexpectedEof)
+        ));
+    }
+
+    #[test]
+    fn test_partial() {
+        let mut decoder = PartialDecoder::new();
+        let mut first: &[u8] = &[0x80, 0x80];
+        let mut second: &[u8] = &[0x01, 0x7f];
+        assert_eq!(decoder.decode_buf(&mut first).unwrap(), None);
+        assert_eq!(decoder.decode_buf(&mut second).unwrap(), Some(16384));
+        assert_eq!(second, &[0x7f]);
+    }
+}
@@ -1,34 +1,245 @@
-use std::io::{Error, Read};
+use std::io::{self, Read, Write};
 
-pub fn decode_stream<T: Read>(stream: &mut T) -> Result<i32, Error> {
-    let mut shift: u8 = 0;
-    let mut result: i32 = 0;
+#[cfg(feature = "tokio")]
+pub mod async_io;
+#[cfg(feature = "bytes")]
+pub mod buf;
+
+/// Maximum number of bytes a VarInt can take on the wire
+pub const MAX_VARINT_LEN: usize = 5;
+/// Maximum number of bytes a VarLong can take on the wire
+pub const MAX_VARLONG_LEN: usize = 10;
+
+#[derive(Debug)]
+pub enum VarIntError {
+    /// The input ended before the last byte of the number
+    UnexpectedEof,
+    /// The number didn't end within the maximum length
+    TooLong,
+    Io(io::Error),
+}
+
+impl std::fmt::Display for VarIntError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::UnexpectedEof => write!(f, "Unexpected end of input while reading varint"),
+            Self::TooLong => write!(f, "Varint is too long"),
+            Self::Io(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl std::error::Error for VarIntError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for VarIntError {
+    fn from(value: io::Error) -> Self {
+        if value.kind() == io::ErrorKind::UnexpectedEof {
+            VarIntError::UnexpectedEof
+        } else {
+            VarIntError::Io(value)
+        }
+    }
+}
+
+impl From<VarIntError> for io::Error {
+    fn from(value: VarIntError) -> Self {
+        match value {
+            VarIntError::Io(e) => e,
+            VarIntError::UnexpectedEof => io::Error::from(io::ErrorKind::UnexpectedEof),
+            VarIntError::TooLong => io::Error::new(io::ErrorKind::InvalidData, value),
+        }
+    }
+}
+
+fn read_byte<T: Read>(stream: &mut T) -> Result<u8, VarIntError> {
     let mut buf: [u8; 1] = [0];
     loop {
-        stream.read(&mut buf)?;
-        let i = buf[0] as i32;
-        result = result | ((i & 0x7f) << shift);
-        shift += 7;
-        if i & 0x80 == 0 {
-            break;
+        match stream.read(&mut buf) {
+            Ok(0) => return Err(VarIntError::UnexpectedEof),
+            Ok(_) => return Ok(buf[0]),
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e.into()),
         }
     }
-    Ok(result)
 }
 
-pub fn encode(n: i32) -> Vec<u8> {
-    let mut res = Vec::<u8>::new();
+fn decode_bytes<I>(bytes: I, max_len: usize) -> Result<(u64, usize), VarIntError>
+where
+    I: IntoIterator<Item = Result<u8, VarIntError>>,
+{
+    let mut result: u64 = 0;
+    let mut read = 0;
+    for b in bytes {
+        let b = b?;
+        result |= ((b & 0x7f) as u64) << (7 * read);
+        read += 1;
+        if b & 0x80 == 0 {
+            return Ok((result, read));
+        }
+        if read == max_len {
+            return Err(VarIntError::TooLong);
+        }
+    }
+    Err(VarIntError::UnexpectedEof)
+}
+
+/// Reads a VarInt from a stream, one byte at a time
+pub fn decode_stream<T: Read>(stream: &mut T) -> Result<i32, VarIntError> {
+    let bytes = std::iter::repeat_with(|| read_byte(stream));
+    let (n, _) = decode_bytes(bytes, MAX_VARINT_LEN)?;
+    Ok(n as u32 as i32)
+}
+
+/// Reads a VarLong from a stream, one byte at a time
+pub fn decode_long_stream<T: Read>(stream: &mut T) -> Result<i64, VarIntError> {
+    let bytes = std::iter::repeat_with(|| read_byte(stream));
+    let (n, _) = decode_bytes(bytes, MAX_VARLONG_LEN)?;
+    Ok(n as i64)
+}
+
+/// Decodes a VarInt at the start of `data`, returning it and the amount of bytes it used
+pub fn decode_slice(data: &[u8]) -> Result<(i32, usize), VarIntError> {
+    let (n, len) = decode_bytes(data.iter().map(|b| Ok(*b)), MAX_VARINT_LEN)?;
+    Ok((n as u32 as i32, len))
+}
+
+/// Decodes a VarLong at the start of `data`, returning it and the amount of bytes it used
+pub fn decode_long_slice(data: &[u8]) -> Result<(i64, usize), VarIntError> {
+    let (n, len) = decode_bytes(data.iter().map(|b| Ok(*b)), MAX_VARLONG_LEN)?;
+    Ok((n as i64, len))
+}
+
+/// Like [`decode_slice`], but returns `None` if `data` ends before the number does
+pub fn try_decode_slice(data: &[u8]) -> Result<Option<(i32, usize)>, VarIntError> {
+    match decode_slice(data) {
+        Ok(v) => Ok(Some(v)),
+        Err(VarIntError::UnexpectedEof) => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+/// Like [`decode_long_slice`], but returns `None` if `data` ends before the number does
+pub fn try_decode_long_slice(data: &[u8]) -> Result<Option<(i64, usize)>, VarIntError> {
+    match decode_long_slice(data) {
+        Ok(v) => Ok(Some(v)),
+        Err(VarIntError::UnexpectedEof) => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+/// Decodes a number fed one byte at a time, for input that arrives in pieces
+#[derive(Debug, Default, Clone)]
+pub struct PartialDecoder {
+    value: u64,
+    read: usize,
+}
+
+impl PartialDecoder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Whether some bytes of a number have been fed already
+    pub fn in_progress(&self) -> bool {
+        self.read > 0
+    }
+
+    fn push(&mut self, b: u8, max_len: usize) -> Result<Option<u64>, VarIntError> {
+        self.value |= ((b & 0x7f) as u64) << (7 * self.read);
+        self.read += 1;
+        if b & 0x80 == 0 {
+            let value = self.value;
+            *self = Self::default();
+            return Ok(Some(value));
+        }
+        if self.read == max_len {
+            *self = Self::default();
+            return Err(VarIntError::TooLong);
+        }
+        Ok(None)
+    }
+
+    /// Feeds a byte of a VarInt, returning it once its last byte was fed
+    pub fn push_varint(&mut self, b: u8) -> Result<Option<i32>, VarIntError> {
+        Ok(self.push(b, MAX_VARINT_LEN)?.map(|n| n as u32 as i32))
+    }
+
+    /// Feeds a byte of a VarLong, returning it once its last byte was fed
+    pub fn push_varlong(&mut self, b: u8) -> Result<Option<i64>, VarIntError> {
+        Ok(self.push(b, MAX_VARLONG_LEN)?.map(|n| n as i64))
+    }
+}
+
+fn encode_bytes(n: u64, buf: &mut [u8]) -> usize {
     let mut cur = n;
+    let mut i = 0;
     loop {
         let b = (cur & 0x7f) as u8;
-        cur = cur >> 7;
+        cur >>= 7;
         if cur == 0 {
-            res.push(b);
-            break;
+            buf[i] = b;
+            return i + 1;
         }
-        res.push(b | 0x80);
+        buf[i] = b | 0x80;
+        i += 1;
     }
-    res
+}
+
+/// Encodes a VarInt into `buf`, returning the amount of bytes written
+pub fn encode_buf(n: i32, buf: &mut [u8; MAX_VARINT_LEN]) -> usize {
+    encode_bytes(n as u32 as u64, buf)
+}
+
+/// Encodes a VarLong into `buf`, returning the amount of bytes written
+pub fn encode_long_buf(n: i64, buf: &mut [u8; MAX_VARLONG_LEN]) -> usize {
+    encode_bytes(n as u64, buf)
+}
+
+/// Writes a VarInt to `writer`, returning the amount of bytes written
+pub fn encode_into<W: Write>(n: i32, writer: &mut W) -> io::Result<usize> {
+    let mut buf = [0u8; MAX_VARINT_LEN];
+    let len = encode_buf(n, &mut buf);
+    writer.write_all(&buf[..len])?;
+    Ok(len)
+}
+
+/// Writes a VarLong to `writer`, returning the amount of bytes written
+pub fn encode_long_into<W: Write>(n: i64, writer: &mut W) -> io::Result<usize> {
+    let mut buf = [0u8; MAX_VARLONG_LEN];
+    let len = encode_long_buf(n, &mut buf);
+    writer.write_all(&buf[..len])?;
+    Ok(len)
+}
+
+pub fn encode(n: i32) -> Vec<u8> {
+    let mut buf = [0u8; MAX_VARINT_LEN];
+    let len = encode_buf(n, &mut buf);
+    buf[..len].to_vec()
+}
+
+pub fn encode_long(n: i64) -> Vec<u8> {
+    let mut buf = [0u8; MAX_VARLONG_LEN];
+    let len = encode_long_buf(n, &mut buf);
+    buf[..len].to_vec()
+}
+
+/// The amount of bytes `n` takes when encoded as a VarInt
+pub fn encoded_len(n: i32) -> usize {
+    let bits = 32 - (n as u32).leading_zeros() as usize;
+    bits.div_ceil(7).max(1)
+}
+
+/// The amount of bytes `n` takes when encoded as a VarLong
+pub fn encoded_long_len(n: i64) -> usize {
+    let bits = 64 - (n as u64).leading_zeros() as usize;
+    bits.div_ceil(7).max(1)
 }
 
 #[cfg(test)]
@@ -43,6 +254,14 @@ mod tests {
         assert_eq!(res, vect);
     }
 
+    fn check_long_payload(vect: Vec<u8>, trg: i64) {
+        let mut bytes = vect.as_slice();
+        let res = decode_long_stream(&mut bytes).expect("error decoding stream");
+        assert_eq!(res, trg);
+        let res = encode_long(trg);
+        assert_eq!(res, vect);
+    }
+
     #[test]
     fn test_127() {
         check_payload(vec![0x7f], 127);
@@ -58,9 +277,121 @@ mod tests {
         check_payload(0xd88bad01u32.to_be_bytes().to_vec(), 2835928);
     }
 
+    #[test]
+    fn test_negative() {
+        check_payload(vec![0xff, 0xff, 0xff, 0xff, 0x0f], -1);
+        check_payload(vec![0x80, 0x80, 0x80, 0x80, 0x08], i32::MIN);
+    }
+
+    #[test]
+    fn test_max() {
+        check_payload(vec![0xff, 0xff, 0xff, 0xff, 0x07], i32::MAX);
+    }
+
+    #[test]
+    fn test_long() {
+        check_long_payload(vec![0x7f], 127);
+        check_long_payload(
+            vec![0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0x7f],
+            i64::MAX,
+        );
+        check_long_payload(
+            vec![0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0x01],
+            -1,
+        );
+    }
+
+    #[test]
+    fn test_too_long() {
+        let data = [0x80u8, 0x80, 0x80, 0x80, 0x80, 0x01];
+        assert!(matches!(
+            decode_stream(&mut data.as_slice()),
+            Err(VarIntError::TooLong)
+        ));
+        assert!(matches!(decode_slice(&data), Err(VarIntError::TooLong)));
+        let data = [0x80u8; 11];
+        assert!(matches!(
+            decode_long_stream(&mut data.as_slice()),
+            Err(VarIntError::TooLong)
+        ));
+    }
+
+    #[test]
+    fn test_eof() {
+        assert!(matches!(
+            decode_stream(&mut [].as_slice()),
+            Err(VarIntError::UnexpectedEof)
+        ));
+        assert!(matches!(
+            decode_stream(&mut [0x80u8, 0x80].as_slice()),
+            Err(VarIntError::UnexpectedEof)
+        ));
+        assert!(matches!(
+            decode_slice(&[0x80u8]),
+            Err(VarIntError::UnexpectedEof)
+        ));
+    }
+
+    #[test]
+    fn test_slice_consumed() {
+        let data = [0x80u8, 0x01, 0xff];
+        assert_eq!(decode_slice(&data).unwrap(), (128, 2));
+        assert_eq!(decode_long_slice(&data).unwrap(), (128, 2));
+    }
+
+    #[test]
+    fn test_try_decode() {
+        assert_eq!(try_decode_slice(&[0x80, 0x80]).unwrap(), None);
+        assert_eq!(try_decode_slice(&[0x80, 0x01]).unwrap(), Some((128, 2)));
+        assert!(try_decode_slice(&[0x80; 5]).is_err());
+        assert_eq!(try_decode_long_slice(&[0x80; 9]).unwrap(), None);
+    }
+
+    #[test]
+    fn test_partial_decoder() {
+        let mut decoder = PartialDecoder::new();
+        assert_eq!(decoder.push_varint(0xac).unwrap(), None);
+        assert!(decoder.in_progress());
+        assert_eq!(decoder.push_varint(0x02).unwrap(), Some(300));
+        assert!(!decoder.in_progress());
+        for _ in 0..4 {
+            assert_eq!(decoder.push_varint(0x80).unwrap(), None);
+        }
+        assert!(matches!(
+            decoder.push_varint(0x80),
+            Err(VarIntError::TooLong)
+        ));
+        for b in encode_long(i64::MIN) {
+            if let Some(n) = decoder.push_varlong(b).unwrap() {
+                assert_eq!(n, i64::MIN);
+            }
+        }
+    }
+
+    #[test]
+    fn test_encode_into() {
+        let mut out = Vec::new();
+        assert_eq!(encode_into(300, &mut out).unwrap(), 2);
+        assert_eq!(encode_long_into(300, &mut out).unwrap(), 2);
+        assert_eq!(out, vec![0xac, 0x02, 0xac, 0x02]);
+        let mut buf = [0u8; MAX_VARINT_LEN];
+        assert_eq!(encode_buf(-1, &mut buf), 5);
+        assert_eq!(buf, [0xff, 0xff, 0xff, 0xff, 0x0f]);
+    }
+
+    #[test]
+    fn test_encoded_len() {
+        for n in [0, 1, 127, 128, 16383, 16384, i32::MAX, -1, i32::MIN] {
+            assert_eq!(encoded_len(n), encode(n).len());
+        }
+        for n in [0, 127, 128, i64::MAX, -1, i64::MIN] {
+            assert_eq!(encoded_long_len(n), encode_long(n).len());
+        }
+    }
+
     #[test]
     fn check_equal_4bytes() {
-        for i in 0..(0xffffu32) {
+        for i in 0..0xffffu32 {
             let i = i as i32;
             let encoded = encode(i);
             let mut encoded = encoded.as_slice();